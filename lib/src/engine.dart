@@ -21,12 +21,17 @@ import 'package:rhai_dart/src/type_conversion.dart';
 /// Represents a function call request from Rust during async eval.
 class _FunctionRequest {
   final int execId;
+  final int callbackId;
   final String functionName;
   final String argsJson;
 
-  _FunctionRequest(this.execId, this.functionName, this.argsJson);
+  _FunctionRequest(this.execId, this.callbackId, this.functionName, this.argsJson);
 }
 
+/// Names of the notifications Rust posts with the function requests of an
+/// async eval. They are not calls of registered functions and expect no result.
+const _notificationFunctions = {'__rhai_cancel__', '__rhai_watch__'};
+
 /// Represents the status of an async eval operation.
 class _EvalStatus {
   final bool isComplete;
@@ -373,8 +378,10 @@ class RhaiEngine implements Finalizable {
 
         // Main polling loop with request/response pattern
         while (true) {
-          // Check for function call requests FIRST (higher priority)
-          final request = _pollFunctionRequest();
+          // Check for function call requests FIRST (higher priority).
+          // Only this eval's requests are taken, so evals running at the same
+          // time on other engines are served by their own loops.
+          final request = _pollFunctionRequest(evalId);
           if (request != null) {
             // Rust needs a Dart function executed!
             await _fulfillFunctionRequest(request);
@@ -415,15 +422,18 @@ class RhaiEngine implements Finalizable {
     }
   }
 
-  /// Poll for pending function requests from Rust
-  _FunctionRequest? _pollFunctionRequest() {
+  /// Poll for pending function requests from Rust made by one async eval
+  _FunctionRequest? _pollFunctionRequest(int evalId) {
     final execIdPtr = calloc<Int64>();
+    final callbackIdPtr = calloc<Int64>();
     final fnNamePtrPtr = calloc<Pointer<Char>>();
     final argsPtrPtr = calloc<Pointer<Char>>();
 
     try {
-      final result = _bindings.getPendingFunctionRequest(
+      final result = _bindings.getPendingFunctionRequestForEval(
+        evalId,
         execIdPtr,
+        callbackIdPtr,
         fnNamePtrPtr,
         argsPtrPtr,
       );
@@ -434,6 +444,7 @@ class RhaiEngine implements Finalizable {
 
       // Extract request data
       final execId = execIdPtr.value;
+      final callbackId = callbackIdPtr.value;
       final fnNamePtr = fnNamePtrPtr.value;
       final argsPtr = argsPtrPtr.value;
 
@@ -448,9 +459,10 @@ class RhaiEngine implements Finalizable {
       freeNativeString(_bindings, fnNamePtr.cast());
       freeNativeString(_bindings, argsPtr.cast());
 
-      return _FunctionRequest(execId, fnName, argsJson);
+      return _FunctionRequest(execId, callbackId, fnName, argsJson);
     } finally {
       calloc.free(execIdPtr);
+      calloc.free(callbackIdPtr);
       calloc.free(fnNamePtrPtr);
       calloc.free(argsPtrPtr);
     }
@@ -458,10 +470,16 @@ class RhaiEngine implements Finalizable {
 
   /// Execute Dart function and provide result back to Rust
   Future<void> _fulfillFunctionRequest(_FunctionRequest request) async {
+    // Notifications are not answered
+    if (_notificationFunctions.contains(request.functionName)) {
+      return;
+    }
+
     try {
-      // Look up registered function
+      // Look up the callback the request is for. Names are not unique across
+      // engines, callback IDs are.
       final registry = FunctionRegistry();
-      final callback = registry.getByName(request.functionName);
+      final callback = registry.get(request.callbackId);
       if (callback == null) {
         _provideErrorResult(
           request.execId,
@@ -782,9 +800,19 @@ class RhaiEngine implements Finalizable {
   /// engine.eval('age = 31');
   /// ```
   ///
+  /// Pass [typeLock] to lock the variable's type: `int`, `float`, `number`,
+  /// `string`, `bool`, `array`, `map`, or an inline JSON schema object. A
+  /// script assignment giving the variable another type then fails with a
+  /// runtime error, and the variable keeps its value.
+  ///
+  /// ```dart
+  /// engine.setVar('total', 10, typeLock: 'int');
+  /// engine.eval('total = "ten"'); // throws RhaiRuntimeError
+  /// ```
+  ///
   /// Throws [RhaiFFIError] if the operation fails.
   /// Throws [StateError] if the engine has been disposed.
-  void setVar(String name, dynamic value) {
+  void setVar(String name, dynamic value, {String? typeLock}) {
     // Check if disposed
     final enginePtr = _nativeEngine; // Will throw if disposed
 
@@ -794,6 +822,7 @@ class RhaiEngine implements Finalizable {
     // Convert strings to native
     final namePtr = name.toNativeUtf8();
     final valueJsonPtr = valueJson.toNativeUtf8();
+    final typeLockPtr = typeLock?.toNativeUtf8() ?? nullptr;
 
     try {
       // Call FFI function
@@ -801,6 +830,7 @@ class RhaiEngine implements Finalizable {
         enginePtr,
         namePtr.cast(),
         valueJsonPtr.cast(),
+        typeLockPtr.cast(),
       );
 
       // Check for errors
@@ -812,6 +842,9 @@ class RhaiEngine implements Finalizable {
       // Free native strings
       calloc.free(namePtr);
       calloc.free(valueJsonPtr);
+      if (typeLockPtr != nullptr) {
+        calloc.free(typeLockPtr);
+      }
     }
   }
 
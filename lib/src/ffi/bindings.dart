@@ -26,6 +26,19 @@ typedef RhaiEngineNewDart = Pointer<CRhaiEngine> Function(
 /// Typedef for the rhai_engine_free function
 typedef RhaiEngineFreeNative = Void Function(Pointer<CRhaiEngine>);
 
+/// Typedef for the rhai_engine_id function
+///
+/// Gets the unique ID of an engine, which tags the function requests made by
+/// its async evals.
+///
+/// Args:
+///   engine: Pointer to the Rhai engine
+///
+/// Returns:
+///   The engine ID, or -1 if the engine pointer is null
+typedef RhaiEngineIdNative = Int64 Function(Pointer<CRhaiEngine>);
+typedef RhaiEngineIdDart = int Function(Pointer<CRhaiEngine>);
+
 /// Typedef for the rhai_eval function
 typedef RhaiEvalNative = Int32 Function(
     Pointer<CRhaiEngine>, Pointer<Char>, Pointer<Pointer<Char>>);
@@ -145,6 +158,26 @@ typedef RhaiGetPendingFunctionRequestNative = Int32 Function(
 typedef RhaiGetPendingFunctionRequestDart = int Function(
     Pointer<Int64>, Pointer<Pointer<Char>>, Pointer<Pointer<Char>>);
 
+/// Typedef for the rhai_get_pending_function_request_for_engine and
+/// rhai_get_pending_function_request_for_eval functions
+///
+/// Get a pending function request made by one engine's evals, or by one async
+/// eval, with the ID of the callback it is for.
+///
+/// Args:
+///   id: The engine ID or the async eval ID
+///   execIdOut: Pointer to store the execution ID
+///   callbackIdOut: Pointer to store the callback ID
+///   functionNameOut: Pointer to store the function name string pointer
+///   argsJsonOut: Pointer to store the args JSON string pointer
+///
+/// Returns:
+///   0 if request retrieved, -1 if no pending requests
+typedef RhaiGetPendingFunctionRequestForNative = Int32 Function(Int64,
+    Pointer<Int64>, Pointer<Int64>, Pointer<Pointer<Char>>, Pointer<Pointer<Char>>);
+typedef RhaiGetPendingFunctionRequestForDart = int Function(int,
+    Pointer<Int64>, Pointer<Int64>, Pointer<Pointer<Char>>, Pointer<Pointer<Char>>);
+
 /// Typedef for the rhai_provide_function_result function
 ///
 /// Provide function result to Rust.
@@ -160,19 +193,20 @@ typedef RhaiProvideFunctionResultDart = int Function(int, Pointer<Char>);
 
 /// Typedef for the rhai_set_var function
 ///
-/// Sets a mutable variable in the engine's scope.
+/// Sets a mutable variable in the engine's scope, optionally locking its type.
 ///
 /// Args:
 ///   engine: Pointer to the Rhai engine
 ///   name: Variable name as null-terminated C string
 ///   valueJson: JSON-encoded value as null-terminated C string
+///   typeLock: Type lock mode as null-terminated C string, or nullptr
 ///
 /// Returns:
 ///   0 on success, -1 on error
 typedef RhaiSetVarNative = Int32 Function(
-    Pointer<CRhaiEngine>, Pointer<Char>, Pointer<Char>);
+    Pointer<CRhaiEngine>, Pointer<Char>, Pointer<Char>, Pointer<Char>);
 typedef RhaiSetVarDart = int Function(
-    Pointer<CRhaiEngine>, Pointer<Char>, Pointer<Char>);
+    Pointer<CRhaiEngine>, Pointer<Char>, Pointer<Char>, Pointer<Char>);
 
 /// Typedef for the rhai_set_constant function
 ///
@@ -290,6 +324,7 @@ class RhaiBindings {
   // Function pointers - Engine lifecycle
   late final RhaiEngineNewDart _engineNew;
   late final RhaiEngineFreeDart _engineFree;
+  late final RhaiEngineIdDart _engineId;
   late final RhaiEvalDart _eval;
   late final RhaiAnalyzeDart _analyze;
   late final RhaiEngineEvalDart _engineEval;
@@ -311,6 +346,8 @@ class RhaiBindings {
 
   // Function pointers - Function request/response
   late final RhaiGetPendingFunctionRequestDart _getPendingFunctionRequest;
+  late final RhaiGetPendingFunctionRequestForDart _getPendingFunctionRequestForEngine;
+  late final RhaiGetPendingFunctionRequestForDart _getPendingFunctionRequestForEval;
   late final RhaiProvideFunctionResultDart _provideFunctionResult;
 
   // Function pointers - Variable/constant setting
@@ -338,6 +375,10 @@ class RhaiBindings {
         .lookup<NativeFunction<RhaiEngineFreeNative>>('rhai_engine_free')
         .asFunction();
 
+    _engineId = _lib
+        .lookup<NativeFunction<RhaiEngineIdNative>>('rhai_engine_id')
+        .asFunction();
+
     _eval = _lib
         .lookup<NativeFunction<RhaiEvalNative>>('rhai_eval')
         .asFunction();
@@ -404,6 +445,16 @@ class RhaiBindings {
             'rhai_get_pending_function_request')
         .asFunction();
 
+    _getPendingFunctionRequestForEngine = _lib
+        .lookup<NativeFunction<RhaiGetPendingFunctionRequestForNative>>(
+            'rhai_get_pending_function_request_for_engine')
+        .asFunction();
+
+    _getPendingFunctionRequestForEval = _lib
+        .lookup<NativeFunction<RhaiGetPendingFunctionRequestForNative>>(
+            'rhai_get_pending_function_request_for_eval')
+        .asFunction();
+
     _provideFunctionResult = _lib
         .lookup<NativeFunction<RhaiProvideFunctionResultNative>>(
             'rhai_provide_function_result')
@@ -440,6 +491,9 @@ class RhaiBindings {
   /// Free a Rhai engine
   void engineFree(Pointer<CRhaiEngine> engine) => _engineFree(engine);
 
+  /// Get the unique ID of a Rhai engine
+  int engineId(Pointer<CRhaiEngine> engine) => _engineId(engine);
+
   /// Evaluate a Rhai script
   int eval(Pointer<CRhaiEngine> engine, Pointer<Char> script, Pointer<Pointer<Char>> result) =>
       _eval(engine, script, result);
@@ -536,6 +590,30 @@ class RhaiBindings {
     Pointer<Pointer<Char>> argsJsonOut,
   ) => _getPendingFunctionRequest(execIdOut, functionNameOut, argsJsonOut);
 
+  /// Get a pending function request made by one engine's evals.
+  ///
+  /// Returns 0 if request retrieved, -1 if no pending requests for the engine.
+  int getPendingFunctionRequestForEngine(
+    int engineId,
+    Pointer<Int64> execIdOut,
+    Pointer<Int64> callbackIdOut,
+    Pointer<Pointer<Char>> functionNameOut,
+    Pointer<Pointer<Char>> argsJsonOut,
+  ) => _getPendingFunctionRequestForEngine(
+      engineId, execIdOut, callbackIdOut, functionNameOut, argsJsonOut);
+
+  /// Get a pending function request made by one async eval.
+  ///
+  /// Returns 0 if request retrieved, -1 if no pending requests for the eval.
+  int getPendingFunctionRequestForEval(
+    int evalId,
+    Pointer<Int64> execIdOut,
+    Pointer<Int64> callbackIdOut,
+    Pointer<Pointer<Char>> functionNameOut,
+    Pointer<Pointer<Char>> argsJsonOut,
+  ) => _getPendingFunctionRequestForEval(
+      evalId, execIdOut, callbackIdOut, functionNameOut, argsJsonOut);
+
   /// Provide function result to Rust.
   ///
   /// Returns 0 on success, -1 if exec_id not found.
@@ -544,17 +622,19 @@ class RhaiBindings {
 
   // Public API - Variable/constant setting
 
-  /// Set a mutable variable in the engine's scope.
+  /// Set a mutable variable in the engine's scope, optionally locking its type.
   ///
   /// Args:
   ///   engine: Pointer to the Rhai engine
   ///   name: Variable name as null-terminated C string
   ///   valueJson: JSON-encoded value as null-terminated C string
+  ///   typeLock: Type lock mode as null-terminated C string, or nullptr
   ///
   /// Returns:
   ///   0 on success, -1 on error
-  int setVar(Pointer<CRhaiEngine> engine, Pointer<Char> name, Pointer<Char> valueJson) =>
-      _setVar(engine, name, valueJson);
+  int setVar(Pointer<CRhaiEngine> engine, Pointer<Char> name, Pointer<Char> valueJson,
+          Pointer<Char> typeLock) =>
+      _setVar(engine, name, valueJson, typeLock);
 
   /// Set an immutable constant in the engine's scope.
   ///
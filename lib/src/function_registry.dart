@@ -104,8 +104,9 @@ class FunctionRegistry {
   ///
   /// Returns null if the name doesn't exist in the registry.
   ///
-  /// This is used by evalAsync to look up functions by name when
-  /// handling function call requests from Rust.
+  /// Names are shared by every engine, so the function found is the one most
+  /// recently registered under the name by any engine. evalAsync looks up
+  /// function call requests from Rust by callback ID with [get] instead.
   ///
   /// Example:
   /// ```dart
@@ -6,31 +6,101 @@
 //! Instead of calling Dart callbacks directly (which fails from background threads),
 //! we use a request/response pattern:
 //! 1. When script needs Dart function, Rust posts request and blocks
-//! 2. Dart polls for requests, executes them (can be async!), posts results
+//! 2. Dart polls for requests (or is told about them, see `notify`), executes them
+//!    (can be async!), posts results
 //! 3. Rust receives result and resumes execution
+//!
+//! Cancelling an eval aborts its script at the next operation, fails the
+//! function calls it is waiting on, and posts a `__rhai_cancel__` notification
+//! listing the calls Dart had already picked up, so Dart can cancel their Futures.
+//! Notifications like this one, and the other requests the bridge makes itself
+//! (for watches, variable resolvers and definition policies), are only returned
+//! by the pollers filtered by engine or eval, which also report the callback
+//! they are for.
+//!
+//! # Async call protocol
+//!
+//! Every call from a script to a Dart function gets one answer, in one of two
+//! forms:
+//!
+//! - A status envelope: `{"status": "success", "value": <json>}` (or
+//!   `"value_json"` with the value encoded as a string),
+//!   `{"status": "error", "error": "<message>"}`, or
+//!   `{"status": "pending", "future_id": <id>}` when the function returned a Future.
+//! - A plain JSON value, taken as the result unchanged. Only an object whose
+//!   single key is an `error` string is an error.
+//!
+//! Which form an answer has is never guessed from its contents: it depends on
+//! the entry point. Sync evals call the Dart callback directly and take its
+//! return value as an envelope; they can't wait for Futures, so a pending answer
+//! fails the eval with a hint to use `evalAsync()`. Async evals post a request
+//! that Dart answers with a plain value through `rhai_provide_function_result`,
+//! or with an envelope through `rhai_provide_function_envelope`. A pending
+//! envelope keeps the call waiting in the same registry until Dart completes
+//! the Future with an envelope through `rhai_complete_future`. The call times
+//! out after the function's timeout, counted from when it was requested.
+//!
+//! Scripts can send intermediate results with `emit(value)`. The values are kept
+//! in a stream per eval, which Dart drains with `rhai_eval_async_next_event`.
 
-use crate::types::CRhaiEngine;
+use crate::types::{CRhaiEngine, CRhaiEvalOptions, CRhaiScope, ScopeState, SharedScope};
+use crate::scope::{diff_scopes, merge_scope_changes, result_with_changes, MergeConflictPolicy};
+use crate::type_locks::{check_type_locks, LiveTypeLockGuard, TypeLocks};
+use crate::watch::{ScopeWatch, WATCH_NOTIFICATION_FUNCTION};
+use crate::resolver::{ResolverCacheGuard, RESOLVE_VARIABLE_FUNCTION};
+use crate::definitions::DEFINE_VARIABLE_FUNCTION;
+use crate::eval_pool::EvalPool;
+use crate::notify::{notify, NOTIFY_EVAL_COMPLETED, NOTIFY_EVAL_EVENT, NOTIFY_FUNCTION_REQUEST};
 use crate::error::{set_last_error, clear_last_error};
 use crate::engine::format_rhai_error;
 use crate::values::rhai_dynamic_to_json;
-use crate::catch_panic;
+use crate::functions::{parse_call_envelope, plain_call_answer, CallOutcome};
+use crate::{catch_panic, catch_panic_ptr};
 use std::ffi::{CStr, CString, c_char};
 use std::sync::{Arc, Mutex};
 use std::collections::{HashMap, VecDeque};
-use std::sync::atomic::{AtomicI64, Ordering};
-use std::thread;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::cell::{Cell, RefCell};
 use tokio::sync::oneshot;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use rhai::{Dynamic, Engine, Position, Scope};
+
+/// Function name of the cancellation notifications posted to the request queue.
+pub const CANCEL_NOTIFICATION_FUNCTION: &str = "__rhai_cancel__";
+
+/// Function names of the requests the bridge makes itself, rather than for a
+/// registered Dart function.
+const INTERNAL_REQUEST_FUNCTIONS: &[&str] = &[
+    CANCEL_NOTIFICATION_FUNCTION,
+    WATCH_NOTIFICATION_FUNCTION,
+    RESOLVE_VARIABLE_FUNCTION,
+    DEFINE_VARIABLE_FUNCTION,
+];
 
 /// A request for Dart to execute a function.
 #[derive(Debug, Clone)]
 struct FunctionCallRequest {
     /// Unique ID for this request
     exec_id: i64,
+    /// ID of the engine whose eval made the request
+    engine_id: i64,
+    /// ID of the async eval that made the request, or 0 outside async evals
+    eval_id: i64,
+    /// ID of the Dart callback the request is for
+    callback_id: i64,
     /// Name of the Dart function to call
     function_name: String,
     /// JSON-encoded arguments
     args_json: String,
+    /// When the request was queued
+    queued_at: Instant,
+}
+
+impl FunctionCallRequest {
+    /// Checks whether the bridge made the request itself.
+    fn is_internal(&self) -> bool {
+        INTERNAL_REQUEST_FUNCTIONS.contains(&self.function_name.as_str())
+    }
 }
 
 /// Result of an async eval operation.
@@ -44,6 +114,90 @@ enum AsyncEvalResult {
     Error(String),
 }
 
+/// An async eval in the registry, with the engine it runs on.
+#[derive(Debug, Clone)]
+struct AsyncEvalEntry {
+    /// ID of the engine the eval runs on
+    engine_id: i64,
+    /// The eval's current result
+    result: AsyncEvalResult,
+    /// Live state of the eval, shared with its worker
+    monitor: Arc<EvalMonitor>,
+    /// When the eval was started
+    created_at: Instant,
+    /// When the eval completed, if it has
+    completed_at: Option<Instant>,
+}
+
+/// Live state of an async eval, updated by its worker and read by status queries.
+#[derive(Debug, Default)]
+struct EvalMonitor {
+    /// Set when the eval is cancelled, to abort its script
+    cancelled: AtomicBool,
+    /// When a worker started running the script
+    started_at: Mutex<Option<Instant>>,
+    /// Operations executed so far, as reported by the progress hook
+    operations: AtomicU64,
+    /// Number of Dart function calls made so far
+    host_calls: AtomicU64,
+    /// Names of the Dart functions the script is waiting on
+    awaiting: Mutex<Vec<String>>,
+    /// Position the script is at: the statement it is running, or the Dart
+    /// function call it is making
+    position: Mutex<Option<Position>>,
+}
+
+/// A function call waiting for Dart to provide its result.
+struct PendingResponse {
+    /// ID of the engine whose eval made the call
+    engine_id: i64,
+    /// ID of the async eval that made the call, or 0 outside async evals
+    eval_id: i64,
+    /// Channel that wakes up the waiting thread with the value JSON or error message
+    sender: oneshot::Sender<Result<String, String>>,
+    /// When the call was requested
+    requested_at: Instant,
+    /// Dart's ID for the call's Future, once Dart answered it with a pending status
+    future_id: Option<i64>,
+}
+
+/// A value emitted by an async eval.
+struct EmittedValue {
+    /// The JSON-encoded value
+    json: String,
+    /// When the value was emitted
+    emitted_at: Instant,
+}
+
+/// A copy of the scope state an async eval starts from.
+struct StartingScope {
+    /// The variables the script runs with
+    vars: Scope<'static>,
+    /// Types that locked variables must keep
+    type_locks: TypeLocks,
+    /// Watched variables to report changes of
+    watch: Option<ScopeWatch>,
+}
+
+impl StartingScope {
+    /// Copies the parts of a scope state an async eval needs.
+    fn of(state: &ScopeState) -> Self {
+        Self {
+            vars: state.vars.clone(),
+            type_locks: state.type_locks.clone(),
+            watch: state.watch.clone(),
+        }
+    }
+}
+
+/// Where and how to write an async eval's final scope back when it succeeds.
+struct ScopeWriteBack {
+    /// The scope the eval was started from
+    target: SharedScope,
+    /// How to resolve variables that were also changed in the target meanwhile
+    policy: MergeConflictPolicy,
+}
+
 lazy_static::lazy_static! {
     /// Queue of pending function call requests.
     ///
@@ -56,74 +210,261 @@ lazy_static::lazy_static! {
     ///
     /// When Dart finishes executing a function, it posts the result via FFI.
     /// The result is sent through the oneshot channel, waking up the waiting Rust thread.
-    static ref FUNCTION_RESPONSE_CHANNELS: Arc<Mutex<HashMap<i64, oneshot::Sender<String>>>> =
+    static ref FUNCTION_RESPONSE_CHANNELS: Arc<Mutex<HashMap<i64, PendingResponse>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+
+    /// Values emitted by async evals, per eval ID, waiting to be drained by Dart.
+    static ref ASYNC_EVAL_EVENTS: Arc<Mutex<HashMap<i64, VecDeque<EmittedValue>>>> =
         Arc::new(Mutex::new(HashMap::new()));
 
     /// Registry of async eval results.
     ///
     /// Maps eval IDs to their results. Background threads store results here,
     /// and Dart polls to retrieve them.
-    static ref ASYNC_EVAL_RESULTS: Arc<Mutex<HashMap<i64, AsyncEvalResult>>> =
+    static ref ASYNC_EVAL_RESULTS: Arc<Mutex<HashMap<i64, AsyncEvalEntry>>> =
         Arc::new(Mutex::new(HashMap::new()));
 }
 
+thread_local! {
+    /// ID of the async eval running on this thread, or 0 if none.
+    static CURRENT_EVAL_ID: Cell<i64> = const { Cell::new(0) };
+
+    /// Live state of the async eval running on this thread.
+    static CURRENT_EVAL_MONITOR: RefCell<Option<Arc<EvalMonitor>>> = const { RefCell::new(None) };
+}
+
+/// Runs a closure with the live state of the async eval running on this thread.
+///
+/// Does nothing outside async evals.
+fn with_current_monitor(f: impl FnOnce(&EvalMonitor)) {
+    CURRENT_EVAL_MONITOR.with(|current| {
+        if let Some(monitor) = current.borrow().as_ref() {
+            f(monitor);
+        }
+    });
+}
+
+/// Records the position the async eval running on this thread is at.
+///
+/// The debugger records each statement the eval runs, and Dart function calls
+/// record their own position. Does nothing outside async evals.
+pub(crate) fn record_eval_position(position: Position) {
+    with_current_monitor(|monitor| *monitor.position.lock().unwrap() = Some(position));
+}
+
+/// Installs the progress hook of async evals.
+///
+/// The hook records the operation count of the async eval running on the
+/// current thread and aborts it once cancelled, so sync evals are never affected.
+pub(crate) fn install_cancellation_check(engine: &mut Engine) {
+    engine.on_progress(|operations| {
+        let mut cancelled = false;
+        with_current_monitor(|monitor| {
+            monitor.operations.store(operations, Ordering::Relaxed);
+            cancelled = monitor.cancelled.load(Ordering::SeqCst);
+        });
+
+        if cancelled {
+            Some(Dynamic::from("Eval was cancelled"))
+        } else {
+            None
+        }
+    });
+}
+
+/// Registers the `emit()` function on an engine.
+///
+/// `emit(value)` appends a value to the event stream of the async eval running
+/// on the current thread. It fails in sync evals, which have no event stream.
+pub(crate) fn install_emit_function(engine: &mut Engine) {
+    engine.register_fn("emit", |value: Dynamic| -> Result<(), Box<rhai::EvalAltResult>> {
+        let eval_id = CURRENT_EVAL_ID.with(Cell::get);
+        if eval_id == 0 {
+            return Err("emit() is only available in async evals".into());
+        }
+
+        let json = rhai_dynamic_to_json(&value)
+            .map_err(|e| format!("Failed to convert emitted value to JSON: {}", e))?;
+
+        // The results lock is held so a cancellation can't slip in between
+        {
+            let results = ASYNC_EVAL_RESULTS.lock().unwrap();
+            if !is_in_progress(&results, eval_id) {
+                return Err("Eval was cancelled".into());
+            }
+            ASYNC_EVAL_EVENTS.lock().unwrap()
+                .entry(eval_id)
+                .or_default()
+                .push_back(EmittedValue { json, emitted_at: Instant::now() });
+        }
+        notify(NOTIFY_EVAL_EVENT, eval_id);
+        Ok(())
+    });
+}
+
 /// Atomic counter for generating unique function request IDs.
 static NEXT_REQUEST_ID: AtomicI64 = AtomicI64::new(1);
 
 /// Atomic counter for generating unique async eval IDs.
 static NEXT_ASYNC_EVAL_ID: AtomicI64 = AtomicI64::new(1);
 
+/// A Dart function call to request from an async eval.
+pub(crate) struct DartCall {
+    /// ID of the engine the function is registered with
+    pub(crate) engine_id: i64,
+    /// ID of the Dart callback to invoke
+    pub(crate) callback_id: i64,
+    /// Name of the Dart function to call
+    pub(crate) function_name: String,
+    /// JSON-encoded arguments
+    pub(crate) args_json: String,
+    /// How long to wait for Dart to provide the result
+    pub(crate) timeout_seconds: u64,
+}
+
 /// Requests execution of a Dart function and waits for the result.
 ///
 /// This function posts a request to the global queue and blocks waiting for
 /// Dart to provide the result. The calling thread (background eval thread)
 /// is blocked, but the Dart main thread remains free to handle async work.
 ///
+/// Requests made by an async eval are tagged with its ID. Once the eval has been
+/// cancelled, no new requests are posted and the call fails right away.
+///
 /// # Arguments
 ///
+/// * `engine_id` - ID of the engine the function is registered with
+/// * `callback_id` - ID of the Dart callback to invoke
 /// * `function_name` - Name of the Dart function to call
 /// * `args_json` - JSON-encoded arguments
+/// * `timeout_seconds` - How long to wait for Dart to provide the result
 ///
 /// # Returns
 ///
 /// JSON-encoded result from the Dart function, or error message
 pub async fn request_dart_function_execution(
+    engine_id: i64,
+    callback_id: i64,
     function_name: String,
     args_json: String,
+    timeout_seconds: u64,
 ) -> Result<String, String> {
-    let request_id = NEXT_REQUEST_ID.fetch_add(1, Ordering::SeqCst);
+    let call = DartCall { engine_id, callback_id, function_name, args_json, timeout_seconds };
+    request_dart_function_executions(vec![call]).await.remove(0)
+}
+
+/// Requests execution of several Dart functions at once and waits for all results.
+///
+/// All requests are queued together before waiting, so Dart can run them
+/// concurrently. Each call keeps its own timeout, counted from when it was queued.
+///
+/// # Returns
+///
+/// The JSON-encoded result or error message of each call, in order
+pub(crate) async fn request_dart_function_executions(calls: Vec<DartCall>) -> Vec<Result<String, String>> {
+    let eval_id = CURRENT_EVAL_ID.with(Cell::get);
+    let queued_at = tokio::time::Instant::now();
 
-    // Create oneshot channel for response
-    let (tx, rx) = oneshot::channel();
+    let mut requests = Vec::with_capacity(calls.len());
+    let mut waits = Vec::with_capacity(calls.len());
 
-    // Store response channel
+    // Store response channels, unless the eval was cancelled meanwhile. The
+    // results lock is held so a cancellation can't slip in between.
     {
+        let results = ASYNC_EVAL_RESULTS.lock().unwrap();
+        if eval_id != 0 && !is_in_progress(&results, eval_id) {
+            return calls.iter().map(|_| Err("Eval was cancelled".to_string())).collect();
+        }
+
         let mut channels = FUNCTION_RESPONSE_CHANNELS.lock().unwrap();
-        channels.insert(request_id, tx);
+        for call in calls {
+            let request_id = NEXT_REQUEST_ID.fetch_add(1, Ordering::SeqCst);
+
+            // Create oneshot channel for response
+            let (tx, rx) = oneshot::channel();
+            channels.insert(request_id, PendingResponse {
+                engine_id: call.engine_id,
+                eval_id,
+                sender: tx,
+                requested_at: Instant::now(),
+                future_id: None,
+            });
+
+            requests.push(FunctionCallRequest {
+                exec_id: request_id,
+                engine_id: call.engine_id,
+                eval_id,
+                callback_id: call.callback_id,
+                function_name: call.function_name.clone(),
+                args_json: call.args_json,
+                queued_at: Instant::now(),
+            });
+            waits.push((request_id, call.function_name, call.timeout_seconds, rx));
+        }
+    }
+
+    // Post requests to queue
+    with_current_monitor(|monitor| {
+        monitor.host_calls.fetch_add(requests.len() as u64, Ordering::Relaxed);
+        *monitor.awaiting.lock().unwrap() = requests.iter().map(|req| req.function_name.clone()).collect();
+    });
+    queue_requests(requests);
+
+    // Wait for Dart to provide the results (with timeout)
+    let mut outcomes = Vec::with_capacity(waits.len());
+    for (request_id, function_name, timeout_seconds, rx) in waits {
+        let deadline = queued_at + Duration::from_secs(timeout_seconds);
+        outcomes.push(match tokio::time::timeout_at(deadline, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err("Function call was cancelled".into()),
+            Err(_) => {
+                // Clean up on timeout
+                FUNCTION_RESPONSE_CHANNELS.lock().unwrap().remove(&request_id);
+                PENDING_FUNCTION_REQUESTS.lock().unwrap().retain(|req| req.exec_id != request_id);
+                Err(format!(
+                    "Function '{}' timed out after {} seconds",
+                    function_name, timeout_seconds
+                ))
+            }
+        });
     }
 
-    // Post request to queue
+    with_current_monitor(|monitor| monitor.awaiting.lock().unwrap().clear());
+    outcomes
+}
+
+/// Adds requests to the function request queue and tells the notification
+/// listener about them.
+fn queue_requests(requests: Vec<FunctionCallRequest>) {
+    let exec_ids: Vec<i64> = requests.iter().map(|req| req.exec_id).collect();
+    PENDING_FUNCTION_REQUESTS.lock().unwrap().extend(requests);
+
+    for exec_id in exec_ids {
+        notify(NOTIFY_FUNCTION_REQUEST, exec_id);
+    }
+}
+
+/// Posts a notification to the function request queue.
+///
+/// Unlike function calls, notifications do not wait for a result, and no
+/// response channel is registered for them.
+pub(crate) fn post_notification_request(
+    engine_id: i64,
+    callback_id: i64,
+    function_name: &str,
+    args_json: String,
+) {
     let request = FunctionCallRequest {
-        exec_id: request_id,
-        function_name,
+        exec_id: NEXT_REQUEST_ID.fetch_add(1, Ordering::SeqCst),
+        engine_id,
+        eval_id: CURRENT_EVAL_ID.with(Cell::get),
+        callback_id,
+        function_name: function_name.to_string(),
         args_json,
+        queued_at: Instant::now(),
     };
-    {
-        let mut requests = PENDING_FUNCTION_REQUESTS.lock().unwrap();
-        requests.push_back(request);
-    }
 
-    // Wait for Dart to provide result (with timeout)
-    match tokio::time::timeout(Duration::from_secs(30), rx).await {
-        Ok(Ok(result)) => Ok(result),
-        Ok(Err(_)) => Err("Response channel closed unexpectedly".into()),
-        Err(_) => {
-            // Clean up on timeout
-            let mut channels = FUNCTION_RESPONSE_CHANNELS.lock().unwrap();
-            channels.remove(&request_id);
-            Err("Function call timed out after 30 seconds".into())
-        }
-    }
+    queue_requests(vec![request]);
 }
 
 /// Get a pending function call request (polled by Dart).
@@ -131,6 +472,12 @@ pub async fn request_dart_function_execution(
 /// Dart calls this repeatedly to check for pending function requests.
 /// If a request is available, it's removed from the queue and returned.
 ///
+/// Only calls of registered Dart functions are returned. Requests the bridge
+/// makes itself (`__rhai_cancel__` and `__rhai_watch__` notifications, and
+/// `__rhai_resolve_var__` and `__rhai_define_var__` requests) are not functions
+/// Dart can look up by name: they are left for the pollers filtered by engine
+/// or eval, which report the callback they are for.
+///
 /// # Safety
 ///
 /// Safe to call from FFI when pointers are valid.
@@ -153,66 +500,125 @@ pub extern "C" fn rhai_get_pending_function_request(
     catch_panic! {{
         clear_last_error();
 
-        // Validate pointers
-        if exec_id_out.is_null() {
-            set_last_error("Exec ID output pointer is null");
+        if let Err(e) = check_request_outputs(exec_id_out, function_name_out, args_json_out) {
+            set_last_error(e);
             return -1;
         }
 
-        if function_name_out.is_null() {
-            set_last_error("Function name output pointer is null");
-            return -1;
+        // Try to pop a request from the queue
+        match pop_request(|req| !req.is_internal()) {
+            Some(req) => write_function_request(req, exec_id_out, function_name_out, args_json_out),
+            None => {
+                -1 // No pending requests
+            }
         }
+    }}
+}
+
+/// Get a pending function call request made by one engine's evals.
+///
+/// Works like `rhai_get_pending_function_request`, but only returns requests
+/// made by evals of the given engine, and also returns the ID of the callback the
+/// request is for. Engines that register the same function name with different
+/// callbacks can therefore be served side by side.
+///
+/// # Safety
+///
+/// Safe to call from FFI when pointers are valid.
+///
+/// # Arguments
+///
+/// * `engine_id` - The engine ID, as returned by `rhai_engine_id`
+/// * `exec_id_out` - Pointer to store the request ID
+/// * `callback_id_out` - Pointer to store the callback ID
+/// * `function_name_out` - Pointer to store the function name C string
+/// * `args_json_out` - Pointer to store the args JSON C string
+///
+/// # Returns
+///
+/// 0 if request was retrieved, -1 if no pending requests for the engine
+#[no_mangle]
+pub extern "C" fn rhai_get_pending_function_request_for_engine(
+    engine_id: i64,
+    exec_id_out: *mut i64,
+    callback_id_out: *mut i64,
+    function_name_out: *mut *mut c_char,
+    args_json_out: *mut *mut c_char,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
 
-        if args_json_out.is_null() {
-            set_last_error("Args JSON output pointer is null");
+        if let Err(e) = check_request_outputs(exec_id_out, function_name_out, args_json_out) {
+            set_last_error(e);
             return -1;
         }
 
-        // Try to pop a request from the queue
-        let request = {
-            let mut requests = PENDING_FUNCTION_REQUESTS.lock().unwrap();
-            requests.pop_front()
-        };
+        if callback_id_out.is_null() {
+            set_last_error("Callback ID output pointer is null");
+            return -1;
+        }
 
-        match request {
+        match pop_request(|req| req.engine_id == engine_id) {
             Some(req) => {
-                // Store exec_id
                 unsafe {
-                    *exec_id_out = req.exec_id;
+                    *callback_id_out = req.callback_id;
                 }
+                write_function_request(req, exec_id_out, function_name_out, args_json_out)
+            }
+            None => {
+                -1 // No pending requests
+            }
+        }
+    }}
+}
 
-                // Convert function name to C string
-                match CString::new(req.function_name) {
-                    Ok(c_fn_name) => {
-                        unsafe {
-                            *function_name_out = c_fn_name.into_raw();
-                        }
-                    }
-                    Err(e) => {
-                        set_last_error(&format!("Failed to create function name C string: {}", e));
-                        return -1;
-                    }
-                }
+/// Get a pending function call request made by one async eval.
+///
+/// Works like `rhai_get_pending_function_request_for_engine`, but only returns
+/// requests made by the given eval, so each eval can be served by its own poller.
+///
+/// # Safety
+///
+/// Safe to call from FFI when pointers are valid.
+///
+/// # Arguments
+///
+/// * `eval_id` - The unique ID of the async eval
+/// * `exec_id_out` - Pointer to store the request ID
+/// * `callback_id_out` - Pointer to store the callback ID
+/// * `function_name_out` - Pointer to store the function name C string
+/// * `args_json_out` - Pointer to store the args JSON C string
+///
+/// # Returns
+///
+/// 0 if request was retrieved, -1 if no pending requests for the eval
+#[no_mangle]
+pub extern "C" fn rhai_get_pending_function_request_for_eval(
+    eval_id: i64,
+    exec_id_out: *mut i64,
+    callback_id_out: *mut i64,
+    function_name_out: *mut *mut c_char,
+    args_json_out: *mut *mut c_char,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
 
-                // Convert args JSON to C string
-                match CString::new(req.args_json) {
-                    Ok(c_args) => {
-                        unsafe {
-                            *args_json_out = c_args.into_raw();
-                        }
-                    }
-                    Err(e) => {
-                        set_last_error(&format!("Failed to create args JSON C string: {}", e));
-                        // Free the function name string we already allocated
-                        unsafe {
-                            let _ = CString::from_raw(*function_name_out);
-                        }
-                        return -1;
-                    }
-                }
+        if let Err(e) = check_request_outputs(exec_id_out, function_name_out, args_json_out) {
+            set_last_error(e);
+            return -1;
+        }
+
+        if callback_id_out.is_null() {
+            set_last_error("Callback ID output pointer is null");
+            return -1;
+        }
 
-                0 // Success - request retrieved
+        match pop_request(|req| req.eval_id == eval_id) {
+            Some(req) => {
+                unsafe {
+                    *callback_id_out = req.callback_id;
+                }
+                write_function_request(req, exec_id_out, function_name_out, args_json_out)
             }
             None => {
                 -1 // No pending requests
@@ -221,12 +627,96 @@ pub extern "C" fn rhai_get_pending_function_request(
     }}
 }
 
+/// Removes the first pending request that matches a filter from the queue.
+fn pop_request(filter: impl Fn(&FunctionCallRequest) -> bool) -> Option<FunctionCallRequest> {
+    let mut requests = PENDING_FUNCTION_REQUESTS.lock().unwrap();
+    requests
+        .iter()
+        .position(filter)
+        .and_then(|index| requests.remove(index))
+}
+
+/// Checks the output pointers shared by the pending request getters.
+fn check_request_outputs(
+    exec_id_out: *mut i64,
+    function_name_out: *mut *mut c_char,
+    args_json_out: *mut *mut c_char,
+) -> Result<(), &'static str> {
+    if exec_id_out.is_null() {
+        return Err("Exec ID output pointer is null");
+    }
+
+    if function_name_out.is_null() {
+        return Err("Function name output pointer is null");
+    }
+
+    if args_json_out.is_null() {
+        return Err("Args JSON output pointer is null");
+    }
+
+    Ok(())
+}
+
+/// Writes a popped request to the output pointers of a pending request getter.
+///
+/// # Returns
+///
+/// 0 on success, -1 if a C string could not be created
+fn write_function_request(
+    req: FunctionCallRequest,
+    exec_id_out: *mut i64,
+    function_name_out: *mut *mut c_char,
+    args_json_out: *mut *mut c_char,
+) -> i32 {
+    // Store exec_id
+    unsafe {
+        *exec_id_out = req.exec_id;
+    }
+
+    // Convert function name to C string
+    match CString::new(req.function_name) {
+        Ok(c_fn_name) => {
+            unsafe {
+                *function_name_out = c_fn_name.into_raw();
+            }
+        }
+        Err(e) => {
+            set_last_error(&format!("Failed to create function name C string: {}", e));
+            return -1;
+        }
+    }
+
+    // Convert args JSON to C string
+    match CString::new(req.args_json) {
+        Ok(c_args) => {
+            unsafe {
+                *args_json_out = c_args.into_raw();
+            }
+        }
+        Err(e) => {
+            set_last_error(&format!("Failed to create args JSON C string: {}", e));
+            // Free the function name string we already allocated
+            unsafe {
+                let _ = CString::from_raw(*function_name_out);
+            }
+            return -1;
+        }
+    }
+
+    0 // Success - request retrieved
+}
+
 /// Provide a function call result (called by Dart after executing function).
 ///
 /// When Dart finishes executing a requested function, it calls this to provide
 /// the result. This sends the result through the oneshot channel, waking up
 /// the waiting Rust background thread.
 ///
+/// The result is a plain JSON value and reaches the script unchanged, even if
+/// it looks like a status envelope. Only an object whose single key is an
+/// `error` string fails the call. Use `rhai_provide_function_envelope` to
+/// answer with an envelope.
+///
 /// # Safety
 ///
 /// Safe to call from FFI when pointers are valid.
@@ -264,33 +754,23 @@ pub extern "C" fn rhai_provide_function_result(
             }
         };
 
-        // Look up and remove the response channel
-        let sender = {
-            let mut channels = FUNCTION_RESPONSE_CHANNELS.lock().unwrap();
-            channels.remove(&exec_id)
-        };
-
-        match sender {
-            Some(tx) => {
-                // Send result through channel (wakes up Rust thread!)
-                if tx.send(result_str).is_err() {
-                    set_last_error("Failed to send result through channel (receiver dropped)");
-                    return -1;
-                }
-                0 // Success
-            }
-            None => {
-                set_last_error(&format!("Function request ID not found: {}", exec_id));
-                -1 // ID not found
+        let mut channels = FUNCTION_RESPONSE_CHANNELS.lock().unwrap();
+        match complete_function_call(&mut channels, exec_id, plain_call_answer(result_str)) {
+            Ok(()) => 0, // Success
+            Err(e) => {
+                set_last_error(&e);
+                -1
             }
         }
     }}
 }
 
-/// Starts an async evaluation on a background thread.
+/// Answers a function call request with a status envelope.
 ///
-/// This spawns a new thread to evaluate the script. The thread will post
-/// function call requests when needed, and Dart will fulfill them.
+/// Like `rhai_provide_function_result`, but the answer is a status envelope
+/// (see the async call protocol above), so Dart can report a returned Future.
+/// A pending envelope keeps the call waiting until its Future is completed
+/// with `rhai_complete_future`.
 ///
 /// # Safety
 ///
@@ -298,40 +778,142 @@ pub extern "C" fn rhai_provide_function_result(
 ///
 /// # Arguments
 ///
-/// * `engine` - Pointer to the Rhai engine
-/// * `script` - Pointer to the script string
-/// * `eval_id_out` - Pointer to store the unique eval ID
+/// * `exec_id` - The request ID
+/// * `envelope_json` - JSON status envelope
 ///
 /// # Returns
 ///
-/// 0 on success (eval started), -1 on error
+/// 0 on success, -1 if exec_id not found, the envelope is malformed, or on error
 #[no_mangle]
-pub extern "C" fn rhai_eval_async_start(
-    engine: *const CRhaiEngine,
-    script: *const c_char,
-    eval_id_out: *mut i64,
+pub extern "C" fn rhai_provide_function_envelope(
+    exec_id: i64,
+    envelope_json: *const c_char,
 ) -> i32 {
     catch_panic! {{
         clear_last_error();
 
-        // Validate pointers
-        if engine.is_null() {
-            set_last_error("Engine pointer is null");
+        // Validate pointer
+        if envelope_json.is_null() {
+            set_last_error("Envelope JSON pointer is null");
             return -1;
         }
 
-        if script.is_null() {
-            set_last_error("Script pointer is null");
-            return -1;
+        let outcome = unsafe {
+            match CStr::from_ptr(envelope_json).to_str() {
+                Ok(s) => parse_call_envelope(s),
+                Err(e) => Err(format!("Invalid UTF-8 in envelope JSON: {}", e)),
+            }
+        };
+
+        let mut channels = FUNCTION_RESPONSE_CHANNELS.lock().unwrap();
+        match outcome.and_then(|outcome| complete_function_call(&mut channels, exec_id, outcome)) {
+            Ok(()) => 0, // Success
+            Err(e) => {
+                set_last_error(&e);
+                -1
+            }
         }
+    }}
+}
 
-        if eval_id_out.is_null() {
-            set_last_error("Eval ID output pointer is null");
-            return -1;
+/// Answers a waiting function call.
+///
+/// A pending outcome records Dart's future ID and keeps the call waiting; any
+/// other outcome is sent to the waiting thread.
+fn complete_function_call(
+    channels: &mut HashMap<i64, PendingResponse>,
+    exec_id: i64,
+    outcome: CallOutcome,
+) -> Result<(), String> {
+    let result = match outcome {
+        CallOutcome::Value(value_json) => Ok(value_json),
+        CallOutcome::Error(error_msg) => Err(error_msg),
+        CallOutcome::Pending(future_id) => {
+            return match channels.get_mut(&exec_id) {
+                Some(pending) => {
+                    pending.future_id = Some(future_id);
+                    Ok(())
+                }
+                None => Err(format!("Function request ID not found: {}", exec_id)),
+            };
         }
+    };
 
-        // Convert script to Rust string
-        let script_str = unsafe {
+    // Look up and remove the response channel
+    match channels.remove(&exec_id) {
+        // Send result through channel (wakes up Rust thread!)
+        Some(pending) => pending.sender.send(result)
+            .map_err(|_| "Failed to send result through channel (receiver dropped)".to_string()),
+        None => Err(format!("Function request ID not found: {}", exec_id)),
+    }
+}
+
+/// Answers the function call waiting on a Dart Future.
+///
+/// Called by `rhai_complete_future` with the envelope Dart sends once the
+/// Future of a pending answer completes.
+pub(crate) fn complete_dart_future(future_id: i64, envelope_json: &str) -> Result<(), String> {
+    let outcome = parse_call_envelope(envelope_json)?;
+    if let CallOutcome::Pending(_) = outcome {
+        return Err(format!("Future ID {} can't be completed with a pending status", future_id));
+    }
+
+    let mut channels = FUNCTION_RESPONSE_CHANNELS.lock().unwrap();
+    let exec_id = channels
+        .iter()
+        .find(|(_, pending)| pending.future_id == Some(future_id))
+        .map(|(exec_id, _)| *exec_id)
+        .ok_or_else(|| format!("Future ID {} not found in registry", future_id))?;
+
+    complete_function_call(&mut channels, exec_id, outcome)
+}
+
+/// Starts an async evaluation on a background thread.
+///
+/// This queues the script on the eval worker pool (see `eval_pool`). The worker
+/// thread will post function call requests when needed, and Dart will fulfill
+/// them. Starting fails if the pool's queue is full.
+///
+/// # Safety
+///
+/// Safe to call from FFI when pointers are valid.
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `script` - Pointer to the script string
+/// * `eval_id_out` - Pointer to store the unique eval ID
+///
+/// # Returns
+///
+/// 0 on success (eval started), -1 on error
+#[no_mangle]
+pub extern "C" fn rhai_eval_async_start(
+    engine: *const CRhaiEngine,
+    script: *const c_char,
+    eval_id_out: *mut i64,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        // Validate pointers
+        if engine.is_null() {
+            set_last_error("Engine pointer is null");
+            return -1;
+        }
+
+        if script.is_null() {
+            set_last_error("Script pointer is null");
+            return -1;
+        }
+
+        if eval_id_out.is_null() {
+            set_last_error("Eval ID output pointer is null");
+            return -1;
+        }
+
+        // Convert script to Rust string
+        let script_str = unsafe {
             match CStr::from_ptr(script).to_str() {
                 Ok(s) => s.to_string(),
                 Err(e) => {
@@ -344,39 +926,361 @@ pub extern "C" fn rhai_eval_async_start(
         // Get engine wrapper and clone Arc
         let engine_wrapper = unsafe { &*engine };
         let engine_arc = engine_wrapper.inner.clone();
+        let engine_id = engine_wrapper.engine_id();
 
         // Clone the scope for the background thread
         // This makes variables set via setVar/setConstant available to async scripts
         // Note: Changes made by the script to the scope are isolated to this execution
-        let mut scope = engine_wrapper.scope().clone();
+        // (use rhai_eval_async_start_with_options to write them back)
+        let start = StartingScope::of(&engine_wrapper.scope());
 
-        // Generate unique eval ID
-        let eval_id = NEXT_ASYNC_EVAL_ID.fetch_add(1, Ordering::SeqCst);
+        let eval_id = match spawn_async_eval(engine_arc, engine_id, start, script_str, None, false) {
+            Ok(id) => id,
+            Err(e) => {
+                set_last_error(&e);
+                return -1;
+            }
+        };
 
-        // Mark eval as in progress
-        {
-            let mut results = ASYNC_EVAL_RESULTS.lock().unwrap();
-            results.insert(eval_id, AsyncEvalResult::InProgress);
+        // Return eval ID to caller
+        unsafe {
+            *eval_id_out = eval_id;
+        }
+
+        0 // Success
+    }}
+}
+
+/// Starts an async evaluation against a scope handle on a background thread.
+///
+/// Works like `rhai_eval_async_start`, but the script sees the variables of the
+/// given scope handle instead of the engine's own scope. As with the engine
+/// scope, the script runs on a clone, so its changes are isolated to this execution.
+///
+/// # Safety
+///
+/// Safe to call from FFI when pointers are valid.
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `scope` - Pointer to the scope handle created by `rhai_scope_new()`
+/// * `script` - Pointer to the script string
+/// * `eval_id_out` - Pointer to store the unique eval ID
+///
+/// # Returns
+///
+/// 0 on success (eval started), -1 on error
+#[no_mangle]
+pub extern "C" fn rhai_eval_async_start_in_scope(
+    engine: *const CRhaiEngine,
+    scope: *const CRhaiScope,
+    script: *const c_char,
+    eval_id_out: *mut i64,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        // Validate pointers
+        if engine.is_null() {
+            set_last_error("Engine pointer is null");
+            return -1;
+        }
+
+        if scope.is_null() {
+            set_last_error("Scope pointer is null");
+            return -1;
+        }
+
+        if script.is_null() {
+            set_last_error("Script pointer is null");
+            return -1;
+        }
+
+        if eval_id_out.is_null() {
+            set_last_error("Eval ID output pointer is null");
+            return -1;
+        }
+
+        // Convert script to Rust string
+        let script_str = unsafe {
+            match CStr::from_ptr(script).to_str() {
+                Ok(s) => s.to_string(),
+                Err(e) => {
+                    set_last_error(&format!("Invalid UTF-8 in script: {}", e));
+                    return -1;
+                }
+            }
+        };
+
+        let engine_wrapper = unsafe { &*engine };
+        let engine_arc = engine_wrapper.inner.clone();
+        let engine_id = engine_wrapper.engine_id();
+        let start = StartingScope::of(&unsafe { &*scope }.scope());
+
+        let eval_id = match spawn_async_eval(engine_arc, engine_id, start, script_str, None, false) {
+            Ok(id) => id,
+            Err(e) => {
+                set_last_error(&e);
+                return -1;
+            }
+        };
+
+        // Return eval ID to caller
+        unsafe {
+            *eval_id_out = eval_id;
         }
 
-        // Spawn background thread to execute eval
-        thread::spawn(move || {
-            // Set async eval mode for this thread
-            crate::functions::set_async_eval_mode(true);
+        0 // Success
+    }}
+}
+
+/// Starts an async evaluation with per-call options.
+///
+/// This is the general form of `rhai_eval_async_start`. The script runs against
+/// a clone of the given scope handle, or of the engine's own scope when `scope`
+/// is null. When `options.merge_scope` is set, the changes the script made to
+/// its scope are written back to that scope once the eval finishes successfully.
+///
+/// # Write-back rules
+///
+/// - Only variables the script changed (compared to the scope at eval start) are written.
+/// - New variables and constants defined at the script's top level are added.
+/// - Constants already in the target scope are never overwritten.
+/// - If a variable the script changed was also changed in the target after the eval
+///   started (e.g. by a sync eval), `options.merge_conflict` decides: 0 = script value
+///   wins, 1 = current value is kept, 2 = nothing is written and the eval reports an error.
+/// - Nothing is written back when the eval fails, so async evals are always
+///   transactional and `options.transactional` has no additional effect.
+///
+/// When `options.track_changes` is set, the success result reported by
+/// `rhai_eval_async_poll` has the same `{"value": .., "changes": ..}` shape as
+/// `rhai_eval_with_options`, listing the variables the script changed.
+///
+/// # Safety
+///
+/// Safe to call from FFI when pointers are valid.
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `scope` - Pointer to a scope handle, or null to use the engine's scope
+/// * `script` - Pointer to the script string
+/// * `options` - Pointer to the eval options, or null for defaults
+/// * `eval_id_out` - Pointer to store the unique eval ID
+///
+/// # Returns
+///
+/// 0 on success (eval started), -1 on error
+#[no_mangle]
+pub extern "C" fn rhai_eval_async_start_with_options(
+    engine: *const CRhaiEngine,
+    scope: *const CRhaiScope,
+    script: *const c_char,
+    options: *const CRhaiEvalOptions,
+    eval_id_out: *mut i64,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        // Validate pointers
+        if engine.is_null() {
+            set_last_error("Engine pointer is null");
+            return -1;
+        }
+
+        if script.is_null() {
+            set_last_error("Script pointer is null");
+            return -1;
+        }
+
+        if eval_id_out.is_null() {
+            set_last_error("Eval ID output pointer is null");
+            return -1;
+        }
+
+        let options = if options.is_null() {
+            CRhaiEvalOptions::default()
+        } else {
+            unsafe { *options }
+        };
+
+        let policy = match MergeConflictPolicy::from_c(options.merge_conflict) {
+            Ok(p) => p,
+            Err(e) => {
+                set_last_error(&e);
+                return -1;
+            }
+        };
+
+        // Convert script to Rust string
+        let script_str = unsafe {
+            match CStr::from_ptr(script).to_str() {
+                Ok(s) => s.to_string(),
+                Err(e) => {
+                    set_last_error(&format!("Invalid UTF-8 in script: {}", e));
+                    return -1;
+                }
+            }
+        };
+
+        let engine_wrapper = unsafe { &*engine };
+        let engine_arc = engine_wrapper.inner.clone();
+        let engine_id = engine_wrapper.engine_id();
+
+        // Resolve the scope the eval runs against: a scope handle or the engine scope
+        let target = engine_wrapper.scope_target(scope);
+        let start = StartingScope::of(&target.lock().unwrap());
+
+        let write_back = if options.merge_scope != 0 {
+            Some(ScopeWriteBack { target, policy })
+        } else {
+            None
+        };
+
+        let track_changes = options.track_changes != 0;
+
+        let eval_id = match spawn_async_eval(engine_arc, engine_id, start, script_str, write_back, track_changes) {
+            Ok(id) => id,
+            Err(e) => {
+                set_last_error(&e);
+                return -1;
+            }
+        };
+
+        // Return eval ID to caller
+        unsafe {
+            *eval_id_out = eval_id;
+        }
+
+        0 // Success
+    }}
+}
+
+/// Gets the message of a panic payload.
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Unknown panic occurred".to_string()
+    }
+}
+
+/// Registers a new async eval and queues it on the eval worker pool.
+///
+/// The script is evaluated against a copy of the starting scope, and the outcome
+/// is stored in `ASYNC_EVAL_RESULTS` for Dart to poll. The eval fails if it leaves
+/// a type-locked variable with another type, and on success changes to watched
+/// variables are posted as a notification request. If
+/// `write_back` is set, the script's scope changes are merged into the target
+/// scope before the eval is reported as successful. If `track_changes` is set,
+/// the success result is wrapped together with the script's change set.
+///
+/// # Returns
+///
+/// The unique ID of the new async eval, or an error message if the pool's
+/// queue is full
+fn spawn_async_eval(
+    engine_arc: Arc<Engine>,
+    engine_id: i64,
+    start: StartingScope,
+    script_str: String,
+    write_back: Option<ScopeWriteBack>,
+    track_changes: bool,
+) -> Result<i64, String> {
+    // Clean up after abandoned evals now and then
+    crate::retention::collect_garbage_if_due();
+
+    // Generate unique eval ID
+    let eval_id = NEXT_ASYNC_EVAL_ID.fetch_add(1, Ordering::SeqCst);
+
+    // Mark eval as in progress
+    let monitor = Arc::new(EvalMonitor::default());
+    {
+        let mut results = ASYNC_EVAL_RESULTS.lock().unwrap();
+        results.insert(eval_id, AsyncEvalEntry {
+            engine_id,
+            result: AsyncEvalResult::InProgress,
+            monitor: monitor.clone(),
+            created_at: Instant::now(),
+            completed_at: None,
+        });
+    }
+
+    // Run the eval on a pool worker
+    let submitted = EvalPool::global().submit(engine_id, move || {
+        // Cancelled while queued
+        if monitor.cancelled.load(Ordering::SeqCst) {
+            return;
+        }
+
+        // Set async eval mode for this thread
+        crate::functions::set_async_eval_mode(true);
+        CURRENT_EVAL_ID.with(|id| id.set(eval_id));
+        *monitor.started_at.lock().unwrap() = Some(Instant::now());
+        CURRENT_EVAL_MONITOR.with(|current| *current.borrow_mut() = Some(monitor));
+
+        // A panicking eval must still complete, or Dart would poll it forever
+        let outcome = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let StartingScope { vars: mut scope, type_locks, watch } = start;
+
+            // Keep the starting state to tell which variables the script changed
+            let snapshot = if write_back.is_some() || track_changes || watch.is_some() {
+                Some(scope.clone())
+            } else {
+                None
+            };
 
             // Execute the script with the cloned scope
+            let resolver_cache = ResolverCacheGuard::begin();
+            let live_locks = LiveTypeLockGuard::begin(&type_locks);
             let result = engine_arc.eval_with_scope::<rhai::Dynamic>(&mut scope, &script_str);
-
-            // Clear async eval mode
-            crate::functions::set_async_eval_mode(false);
+            let last_statement = live_locks.finish();
+            drop(resolver_cache);
 
             // Store the result in the registry
-            let async_result = match result {
+            match result {
                 Ok(value) => {
-                    // Convert to JSON
-                    match rhai_dynamic_to_json(&value) {
-                        Ok(json) => AsyncEvalResult::Success(json),
-                        Err(e) => AsyncEvalResult::Error(format!("Failed to convert result to JSON: {}", e)),
+                    // Write scope changes back before reporting success,
+                    // unless the script broke a type lock
+                    let merged = check_type_locks(&type_locks, &scope, last_statement).and_then(|_| match (write_back, &snapshot) {
+                        (Some(wb), Some(snapshot)) => {
+                            let mut target = wb.target.lock().unwrap();
+                            merge_scope_changes(snapshot, &scope, &mut target, wb.policy)
+                        }
+                        _ => Ok(()),
+                    });
+
+                    // Convert to JSON, adding the change set if requested
+                    let json = merged.and_then(|_| rhai_dynamic_to_json(&value)
+                        .map_err(|e| format!("Failed to convert result to JSON: {}", e)))
+                        .and_then(|json| match &snapshot {
+                            Some(before) if track_changes => {
+                                result_with_changes(&json, &diff_scopes(before, &scope))
+                            }
+                            _ => Ok(json),
+                        });
+
+                    match json {
+                        Ok(json) => {
+                            // Report changes to watched variables in one batch
+                            if let (Some(watch), Some(before)) = (&watch, &snapshot) {
+                                if let Some(changes) = watch.changes(before, &scope) {
+                                    if let Ok(payload) = watch.payload(&changes) {
+                                        post_notification_request(
+                                            engine_id,
+                                            watch.callback_id(),
+                                            WATCH_NOTIFICATION_FUNCTION,
+                                            payload,
+                                        );
+                                    }
+                                }
+                            }
+                            AsyncEvalResult::Success(json)
+                        }
+                        Err(e) => AsyncEvalResult::Error(e),
                     }
                 }
                 Err(err) => {
@@ -384,20 +1288,42 @@ pub extern "C" fn rhai_eval_async_start(
                     let error_msg = format_rhai_error(&err);
                     AsyncEvalResult::Error(error_msg)
                 }
-            };
+            }
+        }));
 
-            // Store result in registry
-            let mut results = ASYNC_EVAL_RESULTS.lock().unwrap();
-            results.insert(eval_id, async_result);
+        // Clear async eval mode, also after a panic, since the worker thread is reused
+        crate::functions::set_async_eval_mode(false);
+        CURRENT_EVAL_ID.with(|id| id.set(0));
+        CURRENT_EVAL_MONITOR.with(|current| *current.borrow_mut() = None);
+
+        let async_result = outcome.unwrap_or_else(|panic| {
+            AsyncEvalResult::Error(format!("eval panicked: {}", panic_message(panic.as_ref())))
         });
 
-        // Return eval ID to caller
-        unsafe {
-            *eval_id_out = eval_id;
+        // Store result in registry, unless the eval was cancelled meanwhile
+        let stored = {
+            let mut results = ASYNC_EVAL_RESULTS.lock().unwrap();
+            match results.get_mut(&eval_id) {
+                Some(entry) if matches!(entry.result, AsyncEvalResult::InProgress) => {
+                    entry.result = async_result;
+                    entry.completed_at = Some(Instant::now());
+                    true
+                }
+                _ => false,
+            }
+        };
+        if stored {
+            notify(NOTIFY_EVAL_COMPLETED, eval_id);
         }
+    });
 
-        0 // Success
-    }}
+    match submitted {
+        Ok(()) => Ok(eval_id),
+        Err(e) => {
+            ASYNC_EVAL_RESULTS.lock().unwrap().remove(&eval_id);
+            Err(e)
+        }
+    }
 }
 
 /// Polls for the result of an async evaluation.
@@ -441,7 +1367,7 @@ pub extern "C" fn rhai_eval_async_poll(
         let result = {
             let results = ASYNC_EVAL_RESULTS.lock().unwrap();
             match results.get(&eval_id) {
-                Some(r) => r.clone(),
+                Some(entry) => entry.result.clone(),
                 None => {
                     set_last_error(&format!("Invalid eval ID: {}", eval_id));
                     return -1;
@@ -502,23 +1428,176 @@ pub extern "C" fn rhai_eval_async_poll(
     }}
 }
 
-/// Cancels an async evaluation.
+/// Takes the next value emitted by an async eval.
 ///
-/// This removes the eval from the registry. Note: doesn't actually stop
-/// the background thread, just discards the result.
+/// Values are returned in the order the script emitted them, as JSON strings.
+/// They stay available after the eval completes, until drained.
 ///
-/// # Arguments
+/// # Safety
 ///
-/// * `eval_id` - The unique ID of the async eval to cancel
+/// Safe to call from FFI when pointers are valid.
 ///
-/// # Returns
+/// # Arguments
+///
+/// * `eval_id` - The unique ID of the async eval
+/// * `event_out` - Pointer to store the emitted value's JSON string
+///
+/// # Returns
+///
+/// 0 if a value was retrieved, 1 if there is no value yet, -1 on error
+/// (including an unknown eval ID with no values left)
+#[no_mangle]
+pub extern "C" fn rhai_eval_async_next_event(
+    eval_id: i64,
+    event_out: *mut *mut c_char,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        if event_out.is_null() {
+            set_last_error("Event output pointer is null");
+            return -1;
+        }
+
+        let event = {
+            let mut events = ASYNC_EVAL_EVENTS.lock().unwrap();
+            let event = events.get_mut(&eval_id).and_then(VecDeque::pop_front);
+            if events.get(&eval_id).is_some_and(VecDeque::is_empty) {
+                events.remove(&eval_id);
+            }
+            event
+        };
+
+        match event {
+            Some(EmittedValue { json, .. }) => match CString::new(json) {
+                Ok(c_string) => {
+                    unsafe {
+                        *event_out = c_string.into_raw();
+                    }
+                    0 // Success
+                }
+                Err(e) => {
+                    set_last_error(&format!("Failed to create C string: {}", e));
+                    -1
+                }
+            },
+            None if ASYNC_EVAL_RESULTS.lock().unwrap().contains_key(&eval_id) => {
+                unsafe {
+                    *event_out = std::ptr::null_mut();
+                }
+                1 // No value yet
+            }
+            None => {
+                set_last_error(&format!("Invalid eval ID: {}", eval_id));
+                -1
+            }
+        }
+    }}
+}
+
+/// Reports the status of an async evaluation.
+///
+/// Returns a JSON object with the fields:
+/// - `eval_id` and `engine_id`
+/// - `state`: `"queued"`, `"running"`, `"success"`, or `"error"`
+/// - `elapsed_ms`: time since the eval was started, up to its completion
+/// - `operations`: operations executed so far
+/// - `host_calls`: number of Dart function calls made so far
+/// - `awaiting`: names of the Dart functions the script is waiting on
+/// - `position`: `{"line", "column"}` of the statement the script is running,
+///   or of the Dart function call it is waiting on, or null before it starts
+///
+/// The status is available until the eval's result has been polled.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. The returned string must be freed
+/// with `rhai_free_error()`.
+///
+/// # Arguments
+///
+/// * `eval_id` - The unique ID of the async eval
+///
+/// # Returns
+///
+/// The JSON string, or null on error (including an unknown eval ID)
+#[no_mangle]
+pub extern "C" fn rhai_eval_async_status(eval_id: i64) -> *mut c_char {
+    catch_panic_ptr! {{
+        clear_last_error();
+
+        let status = match ASYNC_EVAL_RESULTS.lock().unwrap().get(&eval_id) {
+            Some(entry) => eval_status(eval_id, entry),
+            None => {
+                set_last_error(&format!("Invalid eval ID: {}", eval_id));
+                return std::ptr::null_mut();
+            }
+        };
+
+        match CString::new(status.to_string()) {
+            Ok(c_string) => c_string.into_raw(),
+            Err(e) => {
+                set_last_error(&format!("Failed to create C string: {}", e));
+                std::ptr::null_mut()
+            }
+        }
+    }}
+}
+
+/// Builds the status report of an async eval.
+fn eval_status(eval_id: i64, entry: &AsyncEvalEntry) -> serde_json::Value {
+    let monitor = &entry.monitor;
+    let state = match &entry.result {
+        AsyncEvalResult::InProgress if monitor.started_at.lock().unwrap().is_none() => "queued",
+        AsyncEvalResult::InProgress => "running",
+        AsyncEvalResult::Success(_) => "success",
+        AsyncEvalResult::Error(_) => "error",
+    };
+    let elapsed = entry.completed_at.unwrap_or_else(Instant::now) - entry.created_at;
+    let position = match *monitor.position.lock().unwrap() {
+        Some(position) if !position.is_none() => serde_json::json!({
+            "line": position.line(),
+            "column": position.position(),
+        }),
+        _ => serde_json::Value::Null,
+    };
+
+    serde_json::json!({
+        "eval_id": eval_id,
+        "engine_id": entry.engine_id,
+        "state": state,
+        "elapsed_ms": elapsed.as_millis() as u64,
+        "operations": monitor.operations.load(Ordering::Relaxed),
+        "host_calls": monitor.host_calls.load(Ordering::Relaxed),
+        "awaiting": *monitor.awaiting.lock().unwrap(),
+        "position": position,
+    })
+}
+
+/// Cancels an async evaluation.
+///
+/// This removes the eval from the registry and aborts its script at the next
+/// operation. Its queued function requests and undrained emitted values are
+/// dropped, and the calls it is waiting on fail. If Dart had already picked up
+/// some of those calls, a `__rhai_cancel__` notification with
+/// `{"eval_id": <id>, "exec_ids": [...]}` is posted to the request queue so Dart
+/// can cancel their Futures.
+///
+/// # Arguments
+///
+/// * `eval_id` - The unique ID of the async eval to cancel
+///
+/// # Returns
 ///
 /// 0 on success, -1 if eval_id not found
 #[no_mangle]
 pub extern "C" fn rhai_eval_async_cancel(eval_id: i64) -> i32 {
     catch_panic! {{
-        let mut results = ASYNC_EVAL_RESULTS.lock().unwrap();
-        if results.remove(&eval_id).is_some() {
+        let removed = ASYNC_EVAL_RESULTS.lock().unwrap().remove(&eval_id);
+        if let Some(entry) = removed {
+            entry.monitor.cancelled.store(true, Ordering::SeqCst);
+            ASYNC_EVAL_EVENTS.lock().unwrap().remove(&eval_id);
+            drop_pending_requests(|_, pending_eval_id| pending_eval_id == eval_id);
             0 // Success
         } else {
             set_last_error(&format!("Invalid eval ID: {}", eval_id));
@@ -526,3 +1605,1171 @@ pub extern "C" fn rhai_eval_async_cancel(eval_id: i64) -> i32 {
         }
     }}
 }
+
+/// Cancels the in-flight async evals and pending requests of a freed engine.
+///
+/// In-progress evals of the engine fail with a cancellation error, which Dart can
+/// still poll. Function calls they are waiting on fail, and they can't post new ones.
+pub(crate) fn cancel_engine_evals(engine_id: i64) {
+    let mut cancelled = Vec::new();
+    {
+        let mut results = ASYNC_EVAL_RESULTS.lock().unwrap();
+        for (eval_id, entry) in results.iter_mut() {
+            if entry.engine_id == engine_id && matches!(entry.result, AsyncEvalResult::InProgress) {
+                entry.result = AsyncEvalResult::Error("Eval cancelled: engine was freed".to_string());
+                entry.completed_at = Some(Instant::now());
+                entry.monitor.cancelled.store(true, Ordering::SeqCst);
+                cancelled.push(*eval_id);
+            }
+        }
+    }
+
+    drop_pending_requests(|pending_engine_id, _| pending_engine_id == engine_id);
+
+    // The cancellation error is ready to poll
+    for eval_id in cancelled {
+        notify(NOTIFY_EVAL_COMPLETED, eval_id);
+    }
+}
+
+/// Drops the queued requests and response channels matching a filter.
+///
+/// The filter receives the engine ID and eval ID of each request. Dropping a
+/// response channel wakes up its waiting thread with an error. Calls that Dart
+/// had already picked up are reported with a cancellation notification per eval.
+fn drop_pending_requests(filter: impl Fn(i64, i64) -> bool) {
+    let mut queued = Vec::new();
+    PENDING_FUNCTION_REQUESTS.lock().unwrap().retain(|req| {
+        if !filter(req.engine_id, req.eval_id) {
+            return true;
+        }
+        queued.push(req.exec_id);
+        false
+    });
+
+    // Calls whose request is no longer queued are being executed by Dart
+    let mut in_flight: HashMap<(i64, i64), Vec<i64>> = HashMap::new();
+    FUNCTION_RESPONSE_CHANNELS.lock().unwrap().retain(|exec_id, pending| {
+        if !filter(pending.engine_id, pending.eval_id) {
+            return true;
+        }
+        if !queued.contains(exec_id) {
+            in_flight.entry((pending.engine_id, pending.eval_id)).or_default().push(*exec_id);
+        }
+        false
+    });
+
+    for ((engine_id, eval_id), mut exec_ids) in in_flight {
+        exec_ids.sort_unstable();
+        let payload = serde_json::json!({ "eval_id": eval_id, "exec_ids": exec_ids });
+        post_cancel_notification(engine_id, eval_id, payload.to_string());
+    }
+}
+
+/// Posts a cancellation notification for the calls of an eval.
+///
+/// Tagged with the cancelled eval, so pollers filtered by eval also receive it.
+fn post_cancel_notification(engine_id: i64, eval_id: i64, args_json: String) {
+    let request = FunctionCallRequest {
+        exec_id: NEXT_REQUEST_ID.fetch_add(1, Ordering::SeqCst),
+        engine_id,
+        eval_id,
+        callback_id: 0,
+        function_name: CANCEL_NOTIFICATION_FUNCTION.to_string(),
+        args_json,
+        queued_at: Instant::now(),
+    };
+
+    queue_requests(vec![request]);
+}
+
+/// Removes abandoned async eval state.
+///
+/// Completed results that were not polled within `completed_ttl` are removed with
+/// their emitted values, and so are values of polled evals that were not drained
+/// within it. Queued
+/// requests that nobody picked up within `stale_request_ttl` are removed, as are
+/// response channels whose call was never answered within it or whose waiting
+/// thread is gone; their waiting threads fail with a cancellation error. A zero
+/// duration disables the corresponding expiry.
+///
+/// # Returns
+///
+/// The number of removed entries
+pub(crate) fn expire_async_state(completed_ttl: Duration, stale_request_ttl: Duration) -> usize {
+    let now = Instant::now();
+    let expired = |since: Instant, ttl: Duration| !ttl.is_zero() && now.duration_since(since) >= ttl;
+    let mut removed = 0;
+
+    {
+        let mut results = ASYNC_EVAL_RESULTS.lock().unwrap();
+        let before = results.len();
+        results.retain(|_, entry| !entry.completed_at.is_some_and(|at| expired(at, completed_ttl)));
+        removed += before - results.len();
+
+        // Values of registered evals expire with their result, the others
+        // (already polled) on their own
+        let mut events = ASYNC_EVAL_EVENTS.lock().unwrap();
+        events.retain(|eval_id, values| {
+            if !results.contains_key(eval_id) {
+                let before = values.len();
+                values.retain(|value| !expired(value.emitted_at, completed_ttl));
+                removed += before - values.len();
+            }
+            !values.is_empty()
+        });
+    }
+
+    {
+        let mut requests = PENDING_FUNCTION_REQUESTS.lock().unwrap();
+        let before = requests.len();
+        requests.retain(|req| !expired(req.queued_at, stale_request_ttl));
+        removed += before - requests.len();
+    }
+
+    {
+        let mut channels = FUNCTION_RESPONSE_CHANNELS.lock().unwrap();
+        let before = channels.len();
+        channels.retain(|_, pending| {
+            !pending.sender.is_closed() && !expired(pending.requested_at, stale_request_ttl)
+        });
+        removed += before - channels.len();
+    }
+
+    removed
+}
+
+/// Gets the sizes of the async eval registries, as (name, size) pairs.
+pub(crate) fn async_registry_sizes() -> Vec<(&'static str, usize)> {
+    vec![
+        ("async_eval_results", ASYNC_EVAL_RESULTS.lock().unwrap().len()),
+        ("async_eval_events", ASYNC_EVAL_EVENTS.lock().unwrap().values().map(VecDeque::len).sum()),
+        ("pending_function_requests", PENDING_FUNCTION_REQUESTS.lock().unwrap().len()),
+        ("function_response_channels", FUNCTION_RESPONSE_CHANNELS.lock().unwrap().len()),
+    ]
+}
+
+/// Checks whether an async eval is registered and still running.
+fn is_in_progress(results: &HashMap<i64, AsyncEvalEntry>, eval_id: i64) -> bool {
+    matches!(results.get(&eval_id), Some(AsyncEvalEntry { result: AsyncEvalResult::InProgress, .. }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use crate::engine::{rhai_engine_new, rhai_engine_free, rhai_eval, rhai_set_var};
+
+    /// Polls an async eval until it completes, returning (status, result).
+    fn wait_for_eval(eval_id: i64) -> (i32, String) {
+        let mut status = 0_i32;
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        for _ in 0..500 {
+            assert_eq!(rhai_eval_async_poll(eval_id, &mut status, &mut result_ptr), 0);
+            if status != 0 {
+                let result = unsafe { CString::from_raw(result_ptr).into_string().unwrap() };
+                return (status, result);
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("async eval {} did not complete", eval_id);
+    }
+
+    fn eval_sync(engine: *const CRhaiEngine, script: &str) -> String {
+        let script = CString::new(script).unwrap();
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        assert_eq!(rhai_eval(engine, script.as_ptr(), &mut result_ptr), 0);
+        unsafe { CString::from_raw(result_ptr).into_string().unwrap() }
+    }
+
+    fn start_with_options(engine: *const CRhaiEngine, script: &str, options: &CRhaiEvalOptions) -> i64 {
+        let script = CString::new(script).unwrap();
+        let mut eval_id = 0_i64;
+        let ret = rhai_eval_async_start_with_options(
+            engine,
+            std::ptr::null(),
+            script.as_ptr(),
+            options,
+            &mut eval_id,
+        );
+        assert_eq!(ret, 0);
+        eval_id
+    }
+
+    #[test]
+    fn test_async_scope_changes_isolated_by_default() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("count").unwrap();
+        let value = CString::new("1").unwrap();
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), std::ptr::null()), 0);
+
+        let eval_id = start_with_options(engine, "count = 5; let extra = 1; count", &CRhaiEvalOptions::default());
+        assert_eq!(wait_for_eval(eval_id), (1, "5".to_string()));
+
+        assert_eq!(eval_sync(engine, "count"), "1");
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_async_scope_write_back() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("count").unwrap();
+        let value = CString::new("1").unwrap();
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), std::ptr::null()), 0);
+
+        let options = CRhaiEvalOptions { merge_scope: 1, merge_conflict: 0, ..Default::default() };
+        let eval_id = start_with_options(engine, "count = 5; let extra = 7; count", &options);
+        assert_eq!(wait_for_eval(eval_id), (1, "5".to_string()));
+
+        assert_eq!(eval_sync(engine, "count + extra"), "12");
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_async_scope_write_back_skipped_on_error() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("count").unwrap();
+        let value = CString::new("1").unwrap();
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), std::ptr::null()), 0);
+
+        let options = CRhaiEvalOptions { merge_scope: 1, merge_conflict: 0, ..Default::default() };
+        let eval_id = start_with_options(engine, "count = 5; throw \"boom\";", &options);
+        assert_eq!(wait_for_eval(eval_id).0, 2);
+
+        assert_eq!(eval_sync(engine, "count"), "1");
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_async_change_tracking() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("count").unwrap();
+        let value = CString::new("1").unwrap();
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), std::ptr::null()), 0);
+
+        let options = CRhaiEvalOptions { track_changes: 1, ..Default::default() };
+        let eval_id = start_with_options(engine, "count += 1; let added = [1]; count", &options);
+        let (status, result_json) = wait_for_eval(eval_id);
+        assert_eq!(status, 1);
+
+        let result: serde_json::Value = serde_json::from_str(&result_json).unwrap();
+        assert_eq!(result["value"], 2);
+        assert_eq!(result["changes"]["modified"], serde_json::json!({ "count": 2 }));
+        assert_eq!(result["changes"]["added"], serde_json::json!({ "added": [1] }));
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_async_scope_write_back_invalid_policy() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let script = CString::new("1").unwrap();
+        let options = CRhaiEvalOptions { merge_scope: 1, merge_conflict: 9, ..Default::default() };
+        let mut eval_id = 0_i64;
+
+        let ret = rhai_eval_async_start_with_options(
+            engine,
+            std::ptr::null(),
+            script.as_ptr(),
+            &options,
+            &mut eval_id,
+        );
+        assert_eq!(ret, -1);
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_async_type_lock_violation_skips_write_back() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("count").unwrap();
+        let value = CString::new("1").unwrap();
+        let lock = CString::new("number").unwrap();
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), lock.as_ptr()), 0);
+
+        let options = CRhaiEvalOptions { merge_scope: 1, ..Default::default() };
+        let eval_id = start_with_options(engine, "count = [1, 2];\ncount = 2;", &options);
+        let (status, error) = wait_for_eval(eval_id);
+        assert_eq!(status, 2);
+        assert!(error.starts_with("Runtime error at line 1:"), "{}", error);
+        assert!(error.contains("'count' is locked to number, but was assigned array"));
+
+        assert_eq!(eval_sync(engine, "count"), "1");
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_async_eval_posts_watch_notification() {
+        extern "C" fn unused_callback(_: i64, _: *const c_char) -> *mut c_char {
+            panic!("async evals must notify through the request queue");
+        }
+
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("count").unwrap();
+        let value = CString::new("1").unwrap();
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), std::ptr::null()), 0);
+
+        let names = CString::new(r#"["count"]"#).unwrap();
+        assert_eq!(crate::watch::rhai_scope_watch(engine, std::ptr::null(), names.as_ptr(), 777, unused_callback), 0);
+
+        let eval_id = start_with_options(engine, "count = 2; count = 3; count", &CRhaiEvalOptions::default());
+        assert_eq!(wait_for_eval(eval_id), (1, "3".to_string()));
+
+        let notifications: Vec<serde_json::Value> = PENDING_FUNCTION_REQUESTS.lock().unwrap()
+            .iter()
+            .filter(|r| r.function_name == WATCH_NOTIFICATION_FUNCTION)
+            .map(|r| serde_json::from_str::<serde_json::Value>(&r.args_json).unwrap())
+            .filter(|args| args["callback_id"] == 777)
+            .collect();
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0]["changes"]["modified"], serde_json::json!({ "count": 3 }));
+
+        // The legacy poller leaves the notification for the engine's poller
+        assert!(poll_legacy_requests(eval_id).is_empty());
+        let mut exec_id = 0_i64;
+        let mut callback_id = 0_i64;
+        let mut function_name: *mut c_char = std::ptr::null_mut();
+        let mut args_json: *mut c_char = std::ptr::null_mut();
+        assert_eq!(rhai_get_pending_function_request_for_eval(
+            eval_id,
+            &mut exec_id,
+            &mut callback_id,
+            &mut function_name,
+            &mut args_json,
+        ), 0);
+        unsafe {
+            assert_eq!(CString::from_raw(function_name).into_string().unwrap(), WATCH_NOTIFICATION_FUNCTION);
+            drop(CString::from_raw(args_json));
+        }
+        assert_eq!(callback_id, 777);
+
+        rhai_engine_free(engine);
+    }
+
+    extern "C" fn fetch_from_first(_: i64, _: *const c_char) -> *mut c_char {
+        let response = CString::new(r#"{"status":"success","value":1}"#).unwrap();
+        unsafe { libc::strdup(response.as_ptr()) }
+    }
+
+    extern "C" fn fetch_from_second(_: i64, _: *const c_char) -> *mut c_char {
+        let response = CString::new(r#"{"status":"success","value":2}"#).unwrap();
+        unsafe { libc::strdup(response.as_ptr()) }
+    }
+
+    fn register_fetch(engine: *mut CRhaiEngine, callback_id: i64, callback: crate::functions::DartCallback) {
+        let name = CString::new("fetch").unwrap();
+        assert_eq!(crate::functions::rhai_register_function(engine, name.as_ptr(), callback_id, callback), 0);
+    }
+
+    #[test]
+    fn test_same_function_name_is_isolated_per_engine() {
+        let first = rhai_engine_new(std::ptr::null());
+        let second = rhai_engine_new(std::ptr::null());
+        register_fetch(first, 1, fetch_from_first);
+        register_fetch(second, 2, fetch_from_second);
+
+        assert_eq!(eval_sync(first, "fetch()"), "1");
+        assert_eq!(eval_sync(second, "fetch()"), "2");
+
+        // Freeing an engine drops its callbacks only
+        let first_id = crate::engine::rhai_engine_id(first);
+        let second_id = crate::engine::rhai_engine_id(second);
+        rhai_engine_free(first);
+        assert_eq!(crate::functions::registered_callback_count(first_id), 0);
+        assert_eq!(crate::functions::registered_callback_count(second_id), 1);
+        assert_eq!(eval_sync(second, "fetch()"), "2");
+
+        rhai_engine_free(second);
+        assert_eq!(crate::functions::registered_callback_count(second_id), 0);
+    }
+
+    #[test]
+    fn test_async_requests_are_tagged_with_engine_and_callback() {
+        let first = rhai_engine_new(std::ptr::null());
+        let second = rhai_engine_new(std::ptr::null());
+        register_fetch(first, 11, fetch_from_first);
+        register_fetch(second, 22, fetch_from_second);
+
+        let first_eval = start_with_options(first, "fetch() * 10", &CRhaiEvalOptions::default());
+        let second_eval = start_with_options(second, "fetch() * 10", &CRhaiEvalOptions::default());
+
+        // Serve each engine's requests, dispatching on the callback ID
+        for (engine, expected_callback_id) in [(first, 11), (second, 22)] {
+            let engine_id = crate::engine::rhai_engine_id(engine);
+            let mut exec_id = 0_i64;
+            let mut callback_id = 0_i64;
+            let mut function_name: *mut c_char = std::ptr::null_mut();
+            let mut args_json: *mut c_char = std::ptr::null_mut();
+
+            let mut served = false;
+            for _ in 0..500 {
+                if rhai_get_pending_function_request_for_engine(
+                    engine_id,
+                    &mut exec_id,
+                    &mut callback_id,
+                    &mut function_name,
+                    &mut args_json,
+                ) == 0 {
+                    served = true;
+                    break;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            assert!(served, "no request for engine {}", engine_id);
+            assert_eq!(callback_id, expected_callback_id);
+            unsafe {
+                assert_eq!(CString::from_raw(function_name).to_str().unwrap(), "fetch");
+                drop(CString::from_raw(args_json));
+            }
+
+            let result = CString::new(if callback_id == 11 { "1" } else { "2" }).unwrap();
+            assert_eq!(rhai_provide_function_result(exec_id, result.as_ptr()), 0);
+        }
+
+        assert_eq!(wait_for_eval(first_eval), (1, "10".to_string()));
+        assert_eq!(wait_for_eval(second_eval), (1, "20".to_string()));
+
+        rhai_engine_free(first);
+        rhai_engine_free(second);
+    }
+
+    /// Waits until an async eval has posted a function request.
+    fn wait_for_request(eval_id: i64) {
+        for _ in 0..500 {
+            if PENDING_FUNCTION_REQUESTS.lock().unwrap().iter().any(|r| r.eval_id == eval_id) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("async eval {} did not post a request", eval_id);
+    }
+
+    /// Runs the legacy poller until it has nothing left for one eval.
+    ///
+    /// Other evals' requests are set aside meanwhile, so the poller doesn't take
+    /// requests of tests running alongside.
+    ///
+    /// # Returns
+    ///
+    /// The function names of the requests the poller returned
+    fn poll_legacy_requests(eval_id: i64) -> Vec<String> {
+        let others: VecDeque<FunctionCallRequest> = {
+            let mut requests = PENDING_FUNCTION_REQUESTS.lock().unwrap();
+            let (own, others) = std::mem::take(&mut *requests).into_iter().partition(|req| req.eval_id == eval_id);
+            *requests = own;
+            others
+        };
+
+        let mut names = Vec::new();
+        let mut exec_id = 0_i64;
+        let mut function_name: *mut c_char = std::ptr::null_mut();
+        let mut args_json: *mut c_char = std::ptr::null_mut();
+        while rhai_get_pending_function_request(&mut exec_id, &mut function_name, &mut args_json) == 0 {
+            unsafe {
+                names.push(CString::from_raw(function_name).into_string().unwrap());
+                drop(CString::from_raw(args_json));
+            }
+        }
+
+        let mut requests = PENDING_FUNCTION_REQUESTS.lock().unwrap();
+        let queued_meanwhile = std::mem::replace(&mut *requests, others);
+        requests.extend(queued_meanwhile);
+        names
+    }
+
+    #[test]
+    fn test_requests_can_be_polled_per_eval() {
+        let engine = rhai_engine_new(std::ptr::null());
+        register_fetch(engine, 33, fetch_from_first);
+
+        let first_eval = start_with_options(engine, "fetch() + 1", &CRhaiEvalOptions::default());
+        let second_eval = start_with_options(engine, "fetch() + 2", &CRhaiEvalOptions::default());
+        wait_for_request(first_eval);
+        wait_for_request(second_eval);
+
+        // Answer the second eval first, each with its own value
+        for (eval_id, answer) in [(second_eval, "200"), (first_eval, "100")] {
+            let mut exec_id = 0_i64;
+            let mut callback_id = 0_i64;
+            let mut function_name: *mut c_char = std::ptr::null_mut();
+            let mut args_json: *mut c_char = std::ptr::null_mut();
+            assert_eq!(rhai_get_pending_function_request_for_eval(
+                eval_id,
+                &mut exec_id,
+                &mut callback_id,
+                &mut function_name,
+                &mut args_json,
+            ), 0);
+            assert_eq!(callback_id, 33);
+            unsafe {
+                drop(CString::from_raw(function_name));
+                drop(CString::from_raw(args_json));
+            }
+
+            let result = CString::new(answer).unwrap();
+            assert_eq!(rhai_provide_function_result(exec_id, result.as_ptr()), 0);
+        }
+
+        assert_eq!(wait_for_eval(first_eval), (1, "101".to_string()));
+        assert_eq!(wait_for_eval(second_eval), (1, "202".to_string()));
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_engine_free_cancels_in_flight_evals() {
+        let engine = rhai_engine_new(std::ptr::null());
+        register_fetch(engine, 44, fetch_from_first);
+
+        let eval_id = start_with_options(engine, "fetch()", &CRhaiEvalOptions::default());
+        wait_for_request(eval_id);
+
+        rhai_engine_free(engine);
+
+        // The pending request is gone, and the eval reports the cancellation
+        assert!(!PENDING_FUNCTION_REQUESTS.lock().unwrap().iter().any(|r| r.eval_id == eval_id));
+        let (status, error) = wait_for_eval(eval_id);
+        assert_eq!(status, 2);
+        assert!(error.contains("engine was freed"), "{}", error);
+    }
+
+    #[test]
+    fn test_async_calls_use_engine_and_function_timeouts() {
+        let config = crate::types::CRhaiConfig {
+            async_timeout_seconds: 1,
+            ..crate::types::CRhaiConfig::default()
+        };
+        let engine = crate::engine::rhai_engine_new(&config);
+        register_fetch(engine, 55, fetch_from_first);
+
+        let name = CString::new("slow_fetch").unwrap();
+        assert_eq!(crate::functions::rhai_register_function_with_timeout(
+            engine,
+            name.as_ptr(),
+            56,
+            fetch_from_first,
+            2,
+        ), 0);
+
+        // Nobody answers the requests, so both calls time out
+        let fetch_eval = start_with_options(engine, "fetch()", &CRhaiEvalOptions::default());
+        let slow_eval = start_with_options(engine, "slow_fetch()", &CRhaiEvalOptions::default());
+
+        let (status, error) = wait_for_eval(fetch_eval);
+        assert_eq!(status, 2);
+        assert!(error.contains("Function 'fetch' timed out after 1 seconds"), "{}", error);
+
+        let (status, error) = wait_for_eval(slow_eval);
+        assert_eq!(status, 2);
+        assert!(error.contains("Function 'slow_fetch' timed out after 2 seconds"), "{}", error);
+
+        // Timed out requests are removed from the queue
+        assert!(!PENDING_FUNCTION_REQUESTS.lock().unwrap().iter().any(|r| r.eval_id == fetch_eval || r.eval_id == slow_eval));
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_cancel_aborts_running_script() {
+        let config = crate::types::CRhaiConfig {
+            max_operations: 0,
+            ..crate::types::CRhaiConfig::default()
+        };
+        let engine = crate::engine::rhai_engine_new(&config);
+        let inner = unsafe { &*engine }.inner.clone();
+
+        let eval_id = start_with_options(engine, "let x = 0; loop { x += 1; }", &CRhaiEvalOptions::default());
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(rhai_eval_async_cancel(eval_id), 0);
+
+        // The eval thread lets go of the engine once the script is aborted
+        for _ in 0..500 {
+            if Arc::strong_count(&inner) == 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(Arc::strong_count(&inner), 2);
+
+        drop(inner);
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_cancel_notifies_dart_of_in_flight_calls() {
+        let engine = rhai_engine_new(std::ptr::null());
+        register_fetch(engine, 66, fetch_from_first);
+
+        let eval_id = start_with_options(engine, "fetch()", &CRhaiEvalOptions::default());
+        wait_for_request(eval_id);
+
+        // Dart picks up the call, then the eval is cancelled
+        let mut exec_id = 0_i64;
+        let mut callback_id = 0_i64;
+        let mut function_name: *mut c_char = std::ptr::null_mut();
+        let mut args_json: *mut c_char = std::ptr::null_mut();
+        assert_eq!(rhai_get_pending_function_request_for_eval(
+            eval_id,
+            &mut exec_id,
+            &mut callback_id,
+            &mut function_name,
+            &mut args_json,
+        ), 0);
+        unsafe {
+            drop(CString::from_raw(function_name));
+            drop(CString::from_raw(args_json));
+        }
+        assert_eq!(rhai_eval_async_cancel(eval_id), 0);
+
+        // The notification isn't a function the legacy poller could look up
+        assert!(poll_legacy_requests(eval_id).is_empty());
+
+        let mut cancel_exec_id = 0_i64;
+        assert_eq!(rhai_get_pending_function_request_for_eval(
+            eval_id,
+            &mut cancel_exec_id,
+            &mut callback_id,
+            &mut function_name,
+            &mut args_json,
+        ), 0);
+        let (name, args) = unsafe {
+            (
+                CString::from_raw(function_name).into_string().unwrap(),
+                CString::from_raw(args_json).into_string().unwrap(),
+            )
+        };
+        assert_eq!(name, CANCEL_NOTIFICATION_FUNCTION);
+        let args: serde_json::Value = serde_json::from_str(&args).unwrap();
+        assert_eq!(args, serde_json::json!({ "eval_id": eval_id, "exec_ids": [exec_id] }));
+
+        // The call's result is no longer expected
+        let result = CString::new("1").unwrap();
+        assert_eq!(rhai_provide_function_result(exec_id, result.as_ptr()), -1);
+
+        rhai_engine_free(engine);
+    }
+
+    extern "C" fn double_or_fail(_: i64, args_json: *const c_char) -> *mut c_char {
+        let args: serde_json::Value =
+            serde_json::from_str(unsafe { CStr::from_ptr(args_json) }.to_str().unwrap()).unwrap();
+        let response = match args[0].as_i64() {
+            Some(n) => format!(r#"{{"status":"success","value":{}}}"#, n * 2),
+            None => r#"{"status":"error","error":"not a number"}"#.to_string(),
+        };
+        let response = CString::new(response).unwrap();
+        unsafe { libc::strdup(response.as_ptr()) }
+    }
+
+    #[test]
+    fn test_await_all_in_sync_eval() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("double").unwrap();
+        assert_eq!(crate::functions::rhai_register_function(engine, name.as_ptr(), 1, double_or_fail), 0);
+
+        let result = eval_sync(engine, r#"await_all([defer("double", [1]), defer("double", ["x"]), defer("double", [3])])"#);
+        let result: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(result[0], serde_json::json!({ "ok": true, "value": 2 }));
+        assert_eq!(result[1]["ok"], false);
+        assert!(result[1]["error"].as_str().unwrap().contains("not a number"));
+        assert_eq!(result[2], serde_json::json!({ "ok": true, "value": 6 }));
+
+        // Deferring an unknown function fails the script
+        let script = CString::new(r#"defer("missing")"#).unwrap();
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        assert_eq!(rhai_eval(engine, script.as_ptr(), &mut result_ptr), -1);
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_await_all_queues_calls_together() {
+        let engine = rhai_engine_new(std::ptr::null());
+        register_fetch(engine, 77, fetch_from_first);
+
+        let eval_id = start_with_options(
+            engine,
+            r#"await_all([defer("fetch", [1]), defer("fetch", [2]), defer("fetch")])"#,
+            &CRhaiEvalOptions::default(),
+        );
+
+        // All three requests are queued before any result is provided
+        for _ in 0..500 {
+            if PENDING_FUNCTION_REQUESTS.lock().unwrap().iter().filter(|r| r.eval_id == eval_id).count() == 3 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let mut requests: Vec<(i64, String)> = PENDING_FUNCTION_REQUESTS.lock().unwrap()
+            .iter()
+            .filter(|r| r.eval_id == eval_id)
+            .map(|r| (r.exec_id, r.args_json.clone()))
+            .collect();
+        assert_eq!(requests.len(), 3);
+
+        // Answer in reverse order, failing the last call
+        requests.reverse();
+        for (exec_id, args_json) in requests {
+            let answer = match args_json.as_str() {
+                "[1]" => "10".to_string(),
+                "[2]" => "20".to_string(),
+                _ => r#"{"error":"no argument"}"#.to_string(),
+            };
+            let answer = CString::new(answer).unwrap();
+            assert_eq!(rhai_provide_function_result(exec_id, answer.as_ptr()), 0);
+        }
+
+        let (status, result) = wait_for_eval(eval_id);
+        assert_eq!(status, 1);
+        let result: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(result[0], serde_json::json!({ "ok": true, "value": 10 }));
+        assert_eq!(result[1], serde_json::json!({ "ok": true, "value": 20 }));
+        assert_eq!(result[2]["ok"], false);
+        assert!(result[2]["error"].as_str().unwrap().contains("no argument"));
+
+        rhai_engine_free(engine);
+    }
+
+    fn next_event(eval_id: i64) -> (i32, Option<String>) {
+        let mut event_ptr: *mut c_char = std::ptr::null_mut();
+        let status = rhai_eval_async_next_event(eval_id, &mut event_ptr);
+        let event = (status == 0).then(|| unsafe { CString::from_raw(event_ptr).into_string().unwrap() });
+        (status, event)
+    }
+
+    #[test]
+    fn test_emitted_values_are_streamed_in_order() {
+        let engine = rhai_engine_new(std::ptr::null());
+        register_fetch(engine, 88, fetch_from_first);
+
+        let eval_id = start_with_options(
+            engine,
+            r#"emit(#{ progress: 50 }); emit("half"); let x = fetch(); emit(x); "done""#,
+            &CRhaiEvalOptions::default(),
+        );
+
+        // Values emitted before the function call are available while it is pending
+        wait_for_request(eval_id);
+        assert_eq!(next_event(eval_id), (0, Some(r#"{"progress":50}"#.to_string())));
+        assert_eq!(next_event(eval_id), (0, Some(r#""half""#.to_string())));
+        assert_eq!(next_event(eval_id), (1, None));
+
+        let mut exec_id = 0_i64;
+        let mut callback_id = 0_i64;
+        let mut function_name: *mut c_char = std::ptr::null_mut();
+        let mut args_json: *mut c_char = std::ptr::null_mut();
+        assert_eq!(rhai_get_pending_function_request_for_eval(
+            eval_id,
+            &mut exec_id,
+            &mut callback_id,
+            &mut function_name,
+            &mut args_json,
+        ), 0);
+        unsafe {
+            drop(CString::from_raw(function_name));
+            drop(CString::from_raw(args_json));
+        }
+        let result = CString::new("7").unwrap();
+        assert_eq!(rhai_provide_function_result(exec_id, result.as_ptr()), 0);
+
+        // The last value can still be drained after completion
+        assert_eq!(wait_for_eval(eval_id), (1, r#""done""#.to_string()));
+        assert_eq!(next_event(eval_id), (0, Some("7".to_string())));
+        assert_eq!(next_event(eval_id), (-1, None));
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_emit_fails_in_sync_eval() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let script = CString::new("emit(1)").unwrap();
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        assert_eq!(rhai_eval(engine, script.as_ptr(), &mut result_ptr), -1);
+        rhai_engine_free(engine);
+    }
+
+    extern "C" fn sync_callback(_: i64, _: *const c_char) -> *mut c_char {
+        let response = CString::new(r#"{"status":"success","value_json":"[1,2]"}"#).unwrap();
+        unsafe { libc::strdup(response.as_ptr()) }
+    }
+
+    extern "C" fn async_callback(_: i64, _: *const c_char) -> *mut c_char {
+        let response = CString::new(r#"{"status":"pending","future_id":9001}"#).unwrap();
+        unsafe { libc::strdup(response.as_ptr()) }
+    }
+
+    /// Answers the next function request of an async eval with a plain value.
+    fn answer_request(eval_id: i64, answer: &str) {
+        let answer = CString::new(answer).unwrap();
+        assert_eq!(rhai_provide_function_result(take_request(eval_id), answer.as_ptr()), 0);
+    }
+
+    /// Answers the next function request of an async eval with an envelope.
+    fn answer_request_envelope(eval_id: i64, envelope: &str) {
+        let envelope = CString::new(envelope).unwrap();
+        assert_eq!(rhai_provide_function_envelope(take_request(eval_id), envelope.as_ptr()), 0);
+    }
+
+    /// Takes the next function request of an async eval and returns its ID.
+    fn take_request(eval_id: i64) -> i64 {
+        wait_for_request(eval_id);
+
+        let mut exec_id = 0_i64;
+        let mut callback_id = 0_i64;
+        let mut function_name: *mut c_char = std::ptr::null_mut();
+        let mut args_json: *mut c_char = std::ptr::null_mut();
+        assert_eq!(rhai_get_pending_function_request_for_eval(
+            eval_id,
+            &mut exec_id,
+            &mut callback_id,
+            &mut function_name,
+            &mut args_json,
+        ), 0);
+        unsafe {
+            drop(CString::from_raw(function_name));
+            drop(CString::from_raw(args_json));
+        }
+        exec_id
+    }
+
+    fn complete_future(future_id: i64, result: &str) -> i32 {
+        let result = CString::new(result).unwrap();
+        crate::functions::rhai_complete_future(future_id, result.as_ptr())
+    }
+
+    #[test]
+    fn test_sync_eval_calls_sync_and_async_callbacks() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("pair").unwrap();
+        assert_eq!(crate::functions::rhai_register_function(engine, name.as_ptr(), 61, sync_callback), 0);
+        let name = CString::new("later").unwrap();
+        assert_eq!(crate::functions::rhai_register_function(engine, name.as_ptr(), 62, async_callback), 0);
+
+        assert_eq!(eval_sync(engine, "pair()[1]"), "2");
+
+        // Futures can't be awaited in sync evals
+        let script = CString::new("later()").unwrap();
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        assert_eq!(rhai_eval(engine, script.as_ptr(), &mut result_ptr), -1);
+        let error_ptr = crate::error::rhai_get_last_error();
+        let error = unsafe { CStr::from_ptr(error_ptr) }.to_str().unwrap().to_string();
+        crate::error::rhai_free_error(error_ptr);
+        assert!(error.contains("evalAsync()"), "{}", error);
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_async_eval_accepts_every_answer_form() {
+        let engine = rhai_engine_new(std::ptr::null());
+        register_fetch(engine, 63, fetch_from_first);
+
+        let eval_id = start_with_options(engine, "fetch() + fetch() + fetch()", &CRhaiEvalOptions::default());
+        answer_request_envelope(eval_id, r#"{"status":"success","value":1}"#);
+        answer_request(eval_id, "2");
+
+        // A pending answer waits for its Future to complete
+        answer_request_envelope(eval_id, r#"{"status":"pending","future_id":4501}"#);
+        assert_eq!(complete_future(4501, r#"{"status":"success","value":3}"#), 0);
+        assert_eq!(wait_for_eval(eval_id), (1, "6".to_string()));
+        assert_eq!(complete_future(4501, r#"{"status":"success","value":3}"#), -1);
+
+        let eval_id = start_with_options(engine, "fetch()", &CRhaiEvalOptions::default());
+        answer_request_envelope(eval_id, r#"{"status":"pending","future_id":4502}"#);
+        assert_eq!(complete_future(4502, r#"{"status":"error","error":"boom"}"#), 0);
+        let (status, error) = wait_for_eval(eval_id);
+        assert_eq!(status, 2);
+        assert!(error.contains("Function error: boom"), "{}", error);
+
+        // Malformed envelopes are refused, and the call keeps waiting
+        let eval_id = start_with_options(engine, "fetch()", &CRhaiEvalOptions::default());
+        let exec_id = take_request(eval_id);
+        for envelope in ["2", r#"{"status":"pending"}"#, r#"{"status":"done","value":2}"#] {
+            let envelope = CString::new(envelope).unwrap();
+            assert_eq!(rhai_provide_function_envelope(exec_id, envelope.as_ptr()), -1);
+        }
+        let answer = CString::new("2").unwrap();
+        assert_eq!(rhai_provide_function_result(exec_id, answer.as_ptr()), 0);
+        assert_eq!(wait_for_eval(eval_id), (1, "2".to_string()));
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_plain_answers_that_look_like_envelopes_are_values() {
+        let engine = rhai_engine_new(std::ptr::null());
+        register_fetch(engine, 65, fetch_from_first);
+
+        let answers = [
+            r#"{"status":"error","error":"not found"}"#,
+            r#"{"status":"success","value":5}"#,
+            r#"{"status":"pending"}"#,
+        ];
+        for answer in answers {
+            let eval_id = start_with_options(engine, "fetch()", &CRhaiEvalOptions::default());
+            answer_request(eval_id, answer);
+
+            let (status, result) = wait_for_eval(eval_id);
+            assert_eq!(status, 1, "{}", result);
+            let result: serde_json::Value = serde_json::from_str(&result).unwrap();
+            assert_eq!(result, serde_json::from_str::<serde_json::Value>(answer).unwrap());
+        }
+
+        // An object with just an error string is still an error
+        let eval_id = start_with_options(engine, "fetch()", &CRhaiEvalOptions::default());
+        answer_request(eval_id, r#"{"error":"not found"}"#);
+        let (status, error) = wait_for_eval(eval_id);
+        assert_eq!(status, 2);
+        assert!(error.contains("Function error: not found"), "{}", error);
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_pending_answer_times_out_and_is_cleaned_up() {
+        let config = crate::types::CRhaiConfig {
+            async_timeout_seconds: 1,
+            ..crate::types::CRhaiConfig::default()
+        };
+        let engine = rhai_engine_new(&config);
+        register_fetch(engine, 64, fetch_from_first);
+
+        let eval_id = start_with_options(engine, "fetch()", &CRhaiEvalOptions::default());
+        answer_request_envelope(eval_id, r#"{"status":"pending","future_id":4601}"#);
+
+        let (status, error) = wait_for_eval(eval_id);
+        assert_eq!(status, 2);
+        assert!(error.contains("Function 'fetch' timed out after 1 seconds"), "{}", error);
+
+        // The timed out call no longer waits for its Future
+        assert_eq!(complete_future(4601, r#"{"status":"success","value":1}"#), -1);
+
+        rhai_engine_free(engine);
+    }
+
+    fn eval_status_json(eval_id: i64) -> serde_json::Value {
+        let status_ptr = rhai_eval_async_status(eval_id);
+        assert!(!status_ptr.is_null());
+        let status = unsafe { CString::from_raw(status_ptr).into_string().unwrap() };
+        serde_json::from_str(&status).unwrap()
+    }
+
+    #[test]
+    fn test_status_reports_in_flight_eval() {
+        let engine = rhai_engine_new(std::ptr::null());
+        register_fetch(engine, 44, fetch_from_first);
+
+        let eval_id = start_with_options(engine, "let x = 1;\nlet y = x + fetch(x);\ny", &CRhaiEvalOptions::default());
+        wait_for_request(eval_id);
+
+        let status = eval_status_json(eval_id);
+        assert_eq!(status["state"], "running");
+        assert_eq!(status["engine_id"], crate::engine::rhai_engine_id(engine));
+        assert_eq!(status["awaiting"], serde_json::json!(["fetch"]));
+        assert_eq!(status["host_calls"], 1);
+        assert!(status["operations"].as_u64().unwrap() > 0);
+        assert_eq!(status["position"]["line"], 2);
+
+        let mut exec_id = 0_i64;
+        let mut callback_id = 0_i64;
+        let mut function_name: *mut c_char = std::ptr::null_mut();
+        let mut args_json: *mut c_char = std::ptr::null_mut();
+        assert_eq!(rhai_get_pending_function_request_for_eval(
+            eval_id,
+            &mut exec_id,
+            &mut callback_id,
+            &mut function_name,
+            &mut args_json,
+        ), 0);
+        unsafe {
+            drop(CString::from_raw(function_name));
+            drop(CString::from_raw(args_json));
+        }
+        let result = CString::new("41").unwrap();
+        assert_eq!(rhai_provide_function_result(exec_id, result.as_ptr()), 0);
+
+        // The status stays available until the result is polled
+        for _ in 0..500 {
+            if eval_status_json(eval_id)["state"] == "success" {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let status = eval_status_json(eval_id);
+        assert_eq!(status["state"], "success");
+        assert_eq!(status["awaiting"], serde_json::json!([]));
+
+        assert_eq!(wait_for_eval(eval_id), (1, "42".to_string()));
+        assert!(rhai_eval_async_status(eval_id).is_null());
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_status_reports_busy_script_position() {
+        let config = crate::types::CRhaiConfig {
+            max_operations: 0,
+            ..crate::types::CRhaiConfig::default()
+        };
+        let engine = rhai_engine_new(&config);
+
+        // The script never calls Dart, so only the debugger records where it is
+        let eval_id = start_with_options(engine, "let i = 0;\nloop {\n    i += 1;\n}", &CRhaiEvalOptions::default());
+        for _ in 0..500 {
+            if eval_status_json(eval_id)["operations"].as_u64().unwrap_or(0) > 1000 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let status = eval_status_json(eval_id);
+        assert_eq!(status["state"], "running");
+        assert_eq!(status["position"]["line"], 3);
+
+        let inner = unsafe { &*engine }.inner.clone();
+        assert_eq!(rhai_eval_async_cancel(eval_id), 0);
+        for _ in 0..500 {
+            if Arc::strong_count(&inner) == 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        drop(inner);
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_register_during_long_running_eval() {
+        let engine = rhai_engine_new(std::ptr::null());
+        register_fetch(engine, 91, fetch_from_first);
+
+        // The eval holds the engine while it waits for Dart
+        let eval_id = start_with_options(engine, "let x = fetch();\nx + added()", &CRhaiEvalOptions::default());
+        wait_for_request(eval_id);
+
+        let name = CString::new("added").unwrap();
+        assert_eq!(crate::functions::rhai_register_function(engine, name.as_ptr(), 92, fetch_from_second), 0);
+        let name = CString::new("fetch").unwrap();
+        assert_eq!(crate::functions::rhai_register_function(engine, name.as_ptr(), 93, fetch_from_second), 0);
+
+        // The running eval keeps the functions it started with
+        answer_request(eval_id, "1");
+        let (status, error) = wait_for_eval(eval_id);
+        assert_eq!(status, 2);
+        assert!(error.contains("added"), "{}", error);
+
+        // Later evals see the new ones
+        assert_eq!(eval_sync(engine, "fetch() + added()"), "4");
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_unregister_during_long_running_eval() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let engine_id = crate::engine::rhai_engine_id(engine);
+        register_fetch(engine, 94, fetch_from_first);
+
+        let eval_id = start_with_options(engine, "let calls = [defer(\"fetch\")];\nfetch() + fetch() + await_all(calls)[0].value", &CRhaiEvalOptions::default());
+        wait_for_request(eval_id);
+
+        let name = CString::new("fetch").unwrap();
+        assert_eq!(crate::functions::rhai_unregister_function(engine, name.as_ptr()), 0);
+        assert_eq!(crate::functions::registered_callback_count(engine_id), 0);
+
+        // The running eval can still call the function, directly and deferred
+        answer_request(eval_id, "1");
+        answer_request(eval_id, "2");
+        answer_request(eval_id, "3");
+        assert_eq!(wait_for_eval(eval_id), (1, "6".to_string()));
+
+        let script = CString::new("fetch()").unwrap();
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        assert_eq!(rhai_eval(engine, script.as_ptr(), &mut result_ptr), -1);
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_panicking_eval_completes_with_error() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let inner = Arc::get_mut(&mut unsafe { &mut *engine }.inner).unwrap();
+        inner.register_fn("boom", || -> i64 { panic!("kaboom") });
+
+        let eval_id = start_with_options(engine, "boom()", &CRhaiEvalOptions::default());
+        for _ in 0..500 {
+            if eval_status_json(eval_id)["state"] == "error" {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let (status, error) = wait_for_eval(eval_id);
+        assert_eq!(status, 2);
+        assert!(error.contains("eval panicked: kaboom"), "{}", error);
+
+        // The worker is usable again afterwards
+        register_fetch(engine, 95, fetch_from_first);
+        let eval_id = start_with_options(engine, "fetch() + 1", &CRhaiEvalOptions::default());
+        answer_request(eval_id, "1");
+        assert_eq!(wait_for_eval(eval_id), (1, "2".to_string()));
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_abandoned_state_expires() {
+        let minute_ago = Instant::now() - Duration::from_secs(60);
+        let ttl = Duration::from_secs(30);
+        let eval_id = NEXT_ASYNC_EVAL_ID.fetch_add(1, Ordering::SeqCst);
+        let exec_id = NEXT_REQUEST_ID.fetch_add(1, Ordering::SeqCst);
+
+        // A result nobody polled, with an undrained value, a request nobody
+        // picked up, and a call nobody answered
+        ASYNC_EVAL_RESULTS.lock().unwrap().insert(eval_id, AsyncEvalEntry {
+            engine_id: 0,
+            result: AsyncEvalResult::Success("1".to_string()),
+            monitor: Arc::new(EvalMonitor::default()),
+            created_at: minute_ago,
+            completed_at: Some(minute_ago),
+        });
+        ASYNC_EVAL_EVENTS.lock().unwrap().entry(eval_id).or_default().push_back(EmittedValue {
+            json: "1".to_string(),
+            emitted_at: minute_ago,
+        });
+        PENDING_FUNCTION_REQUESTS.lock().unwrap().push_back(FunctionCallRequest {
+            exec_id,
+            engine_id: 0,
+            eval_id,
+            callback_id: 0,
+            function_name: "fetch".to_string(),
+            args_json: "[]".to_string(),
+            queued_at: minute_ago,
+        });
+        let (tx, mut rx) = oneshot::channel();
+        FUNCTION_RESPONSE_CHANNELS.lock().unwrap().insert(exec_id, PendingResponse {
+            engine_id: 0,
+            eval_id,
+            sender: tx,
+            requested_at: minute_ago,
+            future_id: None,
+        });
+
+        // Nothing expires without a TTL
+        expire_async_state(Duration::ZERO, Duration::ZERO);
+        assert!(ASYNC_EVAL_RESULTS.lock().unwrap().contains_key(&eval_id));
+        assert!(FUNCTION_RESPONSE_CHANNELS.lock().unwrap().contains_key(&exec_id));
+
+        assert!(expire_async_state(ttl, ttl) >= 4);
+        assert!(!ASYNC_EVAL_RESULTS.lock().unwrap().contains_key(&eval_id));
+        assert!(!ASYNC_EVAL_EVENTS.lock().unwrap().contains_key(&eval_id));
+        assert!(!PENDING_FUNCTION_REQUESTS.lock().unwrap().iter().any(|r| r.exec_id == exec_id));
+        assert!(!FUNCTION_RESPONSE_CHANNELS.lock().unwrap().contains_key(&exec_id));
+
+        // The waiting side sees the call fail
+        assert!(rx.try_recv().is_err());
+    }
+}
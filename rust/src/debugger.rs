@@ -0,0 +1,53 @@
+//! Script debugger hooks
+//!
+//! Rhai's debugger interface is the only hook that sees each statement of a
+//! running script with its position and the live scope. An engine can only
+//! have one debugger, so this module installs a single one serving:
+//!
+//! - variadic dispatch, which needs the position of long variadic calls
+//!   (see `functions::record_variadic_call`),
+//! - async eval status, which reports the statement an eval is running
+//!   (see `async_eval::record_eval_position`), and
+//! - type locks, which are checked before each top-level statement so a
+//!   violation is reported at the statement that made it
+//!   (see `type_locks::check_live_type_locks`).
+//!
+//! The debugger only stops at variadic break points unless the running eval
+//! is async or has type-locked variables, in which case it steps through
+//! every node.
+
+use crate::async_eval::record_eval_position;
+use crate::functions::{add_variadic_break_points, is_async_eval_mode, record_variadic_call, EngineFunctions};
+use crate::type_locks::{check_live_type_locks, has_live_type_locks};
+use rhai::debugger::DebuggerCommand;
+use rhai::Engine;
+
+/// Installs the debugger of an engine.
+#[allow(deprecated)]
+pub(crate) fn install_debugger(engine: &mut Engine, functions: EngineFunctions) {
+    engine.register_debugger(
+        move |_, mut debugger| {
+            add_variadic_break_points(&functions, &mut debugger);
+            debugger
+        },
+        |context, _, node, _, position| {
+            record_variadic_call(&node, position);
+
+            // Async evals report the statement they are running
+            let is_async = is_async_eval_mode();
+            if is_async && node.is_stmt() && !position.is_none() {
+                record_eval_position(position);
+            }
+
+            if !has_live_type_locks() {
+                return Ok(if is_async { DebuggerCommand::StepInto } else { DebuggerCommand::Continue });
+            }
+
+            // Script functions have their own scope, without the locked variables
+            if node.is_stmt() && context.call_level() == 0 {
+                check_live_type_locks(context.scope(), position)?;
+            }
+            Ok(DebuggerCommand::StepInto)
+        },
+    );
+}
@@ -0,0 +1,385 @@
+//! Variable definition policy
+//!
+//! An engine can restrict which variables scripts may define with `let` and
+//! `const`. The policy is checked by Rhai's variable definition hook, which is
+//! installed on every engine at creation and reads the policy from a shared slot.
+//!
+//! A policy can:
+//!
+//! - reserve names, such as inputs provided by Dart, so scripts cannot define them
+//! - forbid shadowing constants that are already in scope
+//! - require names to match a regular expression
+//! - ask a Dart callback to decide
+//!
+//! The callback receives `{"callback_id", "name", "is_const", "nesting_level",
+//! "will_shadow"}` and answers `{"allow": true}` or `{"allow": false, "reason": "..."}`.
+//! Sync evals invoke the callback directly, and async evals post a request named
+//! `__rhai_define_var__` to the function request queue, which the pollers
+//! filtered by engine or eval return.
+//!
+//! A denied definition fails the eval with a runtime error that gives the
+//! position of the definition and the reason.
+
+use crate::error::clear_last_error;
+use crate::functions::{DartCallback, HookCallback};
+use crate::types::CRhaiEngine;
+use crate::catch_panic;
+use regex::Regex;
+use rhai::{Engine, Scope, VarDefInfo};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::ffi::{CStr, c_char};
+use std::sync::{Arc, Mutex};
+
+/// Function name of the definition requests posted by async evals.
+pub const DEFINE_VARIABLE_FUNCTION: &str = "__rhai_define_var__";
+
+/// Definition policy settings, as passed from Dart.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DefinitionPolicyConfig {
+    /// Names scripts may not define
+    #[serde(default)]
+    reserved_names: Vec<String>,
+
+    /// Whether scripts may not shadow constants in scope
+    #[serde(default)]
+    no_shadow_constants: bool,
+
+    /// Regular expression every defined name must match
+    #[serde(default)]
+    name_pattern: Option<String>,
+}
+
+/// A variable definition policy.
+pub(crate) struct DefinitionPolicy {
+    /// Names scripts may not define
+    reserved_names: HashSet<String>,
+
+    /// Whether scripts may not shadow constants in scope
+    no_shadow_constants: bool,
+
+    /// Pattern every defined name must match
+    name_pattern: Option<Regex>,
+
+    /// Dart callback consulted after the built-in rules
+    callback: Option<HookCallback>,
+}
+
+/// The definition policy slot of an engine, shared with its definition hook.
+pub(crate) type SharedDefinitionPolicy = Arc<Mutex<Option<Arc<DefinitionPolicy>>>>;
+
+/// Decision of a Dart definition callback.
+#[derive(Debug, Deserialize)]
+struct DefinitionDecision {
+    /// Whether the definition is allowed
+    #[serde(default)]
+    allow: bool,
+
+    /// Why the definition was denied
+    #[serde(default)]
+    reason: Option<String>,
+
+    /// Error message, when an async request failed on the Dart side
+    #[serde(default)]
+    error: Option<String>,
+}
+
+thread_local! {
+    /// Why the last variable definition on this thread was denied.
+    ///
+    /// Rhai's definition errors only carry the variable name and position, so the
+    /// reason is kept here until the error is formatted.
+    static LAST_VIOLATION: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Takes the reason the last variable definition on this thread was denied.
+pub(crate) fn take_definition_violation() -> Option<String> {
+    LAST_VIOLATION.with(|violation| violation.borrow_mut().take())
+}
+
+impl DefinitionPolicy {
+    /// Builds a policy from its JSON settings and an optional Dart callback.
+    fn from_json(json: &str, callback: Option<HookCallback>) -> Result<Self, String> {
+        let config: DefinitionPolicyConfig = serde_json::from_str(json)
+            .map_err(|e| format!("Invalid definition policy JSON: {}", e))?;
+
+        let name_pattern = match config.name_pattern {
+            Some(pattern) => Some(Regex::new(&pattern)
+                .map_err(|e| format!("Invalid name pattern in definition policy: {}", e))?),
+            None => None,
+        };
+
+        Ok(Self {
+            reserved_names: config.reserved_names.into_iter().collect(),
+            no_shadow_constants: config.no_shadow_constants,
+            name_pattern,
+            callback,
+        })
+    }
+
+    /// Checks a variable definition against the policy.
+    ///
+    /// # Returns
+    ///
+    /// Ok if the definition is allowed, or the reason it is denied
+    fn check(&self, info: &VarDefInfo, scope: &Scope) -> Result<(), String> {
+        let name = info.name();
+
+        if self.reserved_names.contains(name) {
+            return Err(format!("'{}' is a reserved name", name));
+        }
+
+        if self.no_shadow_constants && scope.is_constant(name) == Some(true) {
+            return Err(format!("'{}' would shadow a constant", name));
+        }
+
+        if let Some(pattern) = &self.name_pattern {
+            if !pattern.is_match(name) {
+                return Err(format!("'{}' does not match the name pattern {}", name, pattern.as_str()));
+            }
+        }
+
+        match &self.callback {
+            Some(callback) => ask_dart(callback, info),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Asks the Dart definition callback whether a variable may be defined.
+fn ask_dart(callback: &HookCallback, info: &VarDefInfo) -> Result<(), String> {
+    let args_json = serde_json::to_string(&serde_json::json!({
+        "callback_id": callback.callback_id,
+        "name": info.name(),
+        "is_const": info.is_const(),
+        "nesting_level": info.nesting_level(),
+        "will_shadow": info.will_shadow_other_variables(),
+    }))
+    .map_err(|e| format!("Failed to serialize definition request: {}", e))?;
+
+    let decision: DefinitionDecision = callback.call(DEFINE_VARIABLE_FUNCTION, args_json)
+        .and_then(|json| serde_json::from_str(&json)
+            .map_err(|e| format!("Failed to parse definition callback response: {}", e)))
+        .map_err(|e| format!("definition callback failed: {}", e))?;
+
+    if let Some(error) = decision.error {
+        return Err(format!("definition callback failed: {}", error));
+    }
+
+    if decision.allow {
+        Ok(())
+    } else {
+        Err(decision.reason.unwrap_or_else(|| format!("'{}' was rejected by the definition callback", info.name())))
+    }
+}
+
+/// Installs the variable definition hook on an engine.
+///
+/// Definitions are checked when they run rather than when the script is
+/// compiled, so the policy can see the constants in the eval's scope.
+pub(crate) fn install_definition_policy(engine: &mut Engine, slot: SharedDefinitionPolicy) {
+    #[allow(deprecated)]
+    engine.on_def_var(move |is_runtime, info, context| {
+        if !is_runtime {
+            return Ok(true);
+        }
+
+        // Release the slot before the policy can call back into Dart
+        let policy = match slot.lock().unwrap().clone() {
+            Some(policy) => policy,
+            None => return Ok(true),
+        };
+
+        match policy.check(&info, context.scope()) {
+            Ok(()) => Ok(true),
+            Err(reason) => {
+                LAST_VIOLATION.with(|violation| *violation.borrow_mut() = Some(reason));
+                Ok(false)
+            }
+        }
+    });
+}
+
+/// Sets or removes the variable definition policy of an engine.
+///
+/// The policy JSON accepts `reserved_names` (array of names scripts may not
+/// define), `no_shadow_constants` (whether scripts may not shadow constants in
+/// scope), and `name_pattern` (regular expression every defined name must match).
+/// If a callback is given, it decides on definitions the built-in rules allow
+/// (see the module documentation for its request and response formats).
+/// Pass a null policy JSON to remove the policy.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. The engine pointer must be valid, the
+/// policy_json pointer must be valid or null, and `callback_ptr` must be null or
+/// stay valid while the policy is set.
+///
+/// # Returns
+///
+/// 0 on success, -1 on error
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `policy_json` - JSON object with the policy settings, or null
+/// * `callback_id` - ID passed back to the callback with each request
+/// * `callback_ptr` - Function pointer to the Dart definition callback, or null
+#[no_mangle]
+pub extern "C" fn rhai_set_definition_policy(
+    engine: *const CRhaiEngine,
+    policy_json: *const c_char,
+    callback_id: i64,
+    callback_ptr: Option<DartCallback>,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        // Validate pointer
+        if engine.is_null() {
+            set_last_error("Engine pointer is null");
+            return -1;
+        }
+
+        let engine_wrapper = unsafe { &*engine };
+
+        if policy_json.is_null() {
+            *engine_wrapper.definition_policy.lock().unwrap() = None;
+            return 0;
+        }
+
+        let json_str = unsafe {
+            match CStr::from_ptr(policy_json).to_str() {
+                Ok(s) => s,
+                Err(e) => {
+                    set_last_error(&format!("Invalid UTF-8 in policy JSON: {}", e));
+                    return -1;
+                }
+            }
+        };
+
+        let callback = callback_ptr.map(|callback_ptr| HookCallback {
+            engine_id: engine_wrapper.engine_id(),
+            callback_id,
+            callback_ptr,
+            async_timeout_seconds: engine_wrapper.async_timeout_seconds(),
+        });
+        let policy = match DefinitionPolicy::from_json(json_str, callback) {
+            Ok(policy) => policy,
+            Err(e) => {
+                set_last_error(&e);
+                return -1;
+            }
+        };
+
+        *engine_wrapper.definition_policy.lock().unwrap() = Some(Arc::new(policy));
+
+        0 // Success
+    }}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{rhai_engine_free, rhai_engine_new, rhai_eval, rhai_set_constant};
+    use crate::error::{rhai_free_error, rhai_get_last_error};
+    use std::ffi::CString;
+
+    fn eval(engine: *const CRhaiEngine, script: &str) -> Result<String, String> {
+        let script = CString::new(script).unwrap();
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        if rhai_eval(engine, script.as_ptr(), &mut result_ptr) == 0 {
+            Ok(unsafe { CString::from_raw(result_ptr).into_string().unwrap() })
+        } else {
+            let error_ptr = rhai_get_last_error();
+            let error = unsafe { CStr::from_ptr(error_ptr).to_str().unwrap().to_string() };
+            rhai_free_error(error_ptr);
+            Err(error)
+        }
+    }
+
+    fn set_policy(engine: *const CRhaiEngine, json: &str, callback: Option<DartCallback>) -> i32 {
+        let json = CString::new(json).unwrap();
+        rhai_set_definition_policy(engine, json.as_ptr(), 1, callback)
+    }
+
+    extern "C" fn deny_temp(_callback_id: i64, args_json: *const c_char) -> *mut c_char {
+        let args: serde_json::Value =
+            serde_json::from_str(unsafe { CStr::from_ptr(args_json) }.to_str().unwrap()).unwrap();
+        let response = if args["name"].as_str().unwrap().starts_with("tmp") {
+            r#"{"allow": false, "reason": "temporary names are not allowed"}"#
+        } else {
+            r#"{"allow": true}"#
+        };
+
+        // Dart allocates results with malloc
+        let c_response = CString::new(response).unwrap();
+        unsafe { libc::strdup(c_response.as_ptr()) }
+    }
+
+    #[test]
+    fn test_reserved_names_report_position() {
+        let engine = rhai_engine_new(std::ptr::null());
+        assert_eq!(set_policy(engine, r#"{"reserved_names": ["input"]}"#, None), 0);
+
+        let err = eval(engine, "let a = 1;\nlet input = 2;").unwrap_err();
+        assert!(err.starts_with("Runtime error at line 2, position 1"), "{}", err);
+        assert!(err.contains("'input' is a reserved name"), "{}", err);
+
+        assert_eq!(eval(engine, "let a = 1; a").unwrap(), "1");
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_no_shadow_constants_and_name_pattern() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("LIMIT").unwrap();
+        let value = CString::new("10").unwrap();
+        assert_eq!(rhai_set_constant(engine, name.as_ptr(), value.as_ptr()), 0);
+
+        let policy = r#"{"no_shadow_constants": true, "name_pattern": "^[a-z_][a-z0-9_]*$"}"#;
+        assert_eq!(set_policy(engine, policy, None), 0);
+
+        assert!(eval(engine, "let LIMIT = 5;").unwrap_err().contains("would shadow a constant"));
+        assert!(eval(engine, "let camelCase = 5;").unwrap_err().contains("does not match the name pattern"));
+        assert_eq!(eval(engine, "let snake_case = LIMIT; snake_case").unwrap(), "10");
+
+        // Removing the policy allows the definitions again
+        assert_eq!(rhai_set_definition_policy(engine, std::ptr::null(), 0, None), 0);
+        assert_eq!(eval(engine, "let camelCase = 5; camelCase").unwrap(), "5");
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_callback_decides() {
+        let engine = rhai_engine_new(std::ptr::null());
+        assert_eq!(set_policy(engine, "{}", Some(deny_temp)), 0);
+
+        let err = eval(engine, "let tmp_total = 1;").unwrap_err();
+        assert!(err.contains("temporary names are not allowed"), "{}", err);
+        assert_eq!(eval(engine, "let total = 1; total").unwrap(), "1");
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_invalid_policy() {
+        let engine = rhai_engine_new(std::ptr::null());
+        assert_eq!(set_policy(engine, r#"{"name_pattern": "("}"#, None), -1);
+        assert_eq!(set_policy(engine, "[1]", None), -1);
+
+        // A misspelled setting must not silently leave names unreserved
+        assert_eq!(set_policy(engine, r#"{"reserved_name": ["input"]}"#, None), -1);
+        let error_ptr = rhai_get_last_error();
+        let error = unsafe { CStr::from_ptr(error_ptr).to_str().unwrap().to_string() };
+        rhai_free_error(error_ptr);
+        assert!(error.contains("unknown field `reserved_name`"), "{}", error);
+        assert_eq!(eval(engine, "let input = 1; input").unwrap(), "1");
+
+        rhai_engine_free(engine);
+    }
+}
@@ -3,9 +3,14 @@
 //! This module provides FFI functions for Rhai engine lifecycle management
 //! and configuration.
 
-use crate::types::{CRhaiEngine, CRhaiConfig};
-use crate::error::{clear_last_error, set_last_error};
+use crate::types::{CRhaiEngine, CRhaiConfig, CRhaiEvalOptions, CRhaiScope, ScopeState};
+use crate::type_locks::{check_type_locks, push_with_lock, type_lock_from_c, LiveTypeLockGuard};
+use crate::watch::{deliver_watch_notifications, queue_watch_notification};
+use crate::resolver::ResolverCacheGuard;
+use crate::definitions::take_definition_violation;
+use crate::error::clear_last_error;
 use crate::values::rhai_dynamic_to_json;
+use crate::scope::{diff_scopes, result_with_changes};
 use crate::{catch_panic, catch_panic_ptr};
 use rhai::{Engine, Dynamic};
 use std::ffi::{CString, CStr, c_char};
@@ -15,6 +20,7 @@ use tera::{Tera, Context};
 ///
 /// This struct provides a Rust-side representation of engine configuration
 /// with secure defaults and a builder pattern for customization.
+#[derive(Clone)]
 pub struct EngineConfig {
     max_operations: Option<u64>,
     max_stack_depth: Option<usize>,
@@ -188,25 +194,16 @@ pub extern "C" fn rhai_engine_new(config: *const CRhaiConfig) -> *mut CRhaiEngin
             EngineConfig::from_c_config(c_config)
         };
 
-        // Get the async timeout before creating the engine
-        let async_timeout_seconds = engine_config.async_timeout_seconds();
-
-        // Create a new Rhai engine
-        let mut engine = Engine::new();
-
-        // Apply configuration to the engine
-        engine_config.apply_to_engine(&mut engine);
-
-        // Wrap in our opaque handle and return
-        let wrapper = CRhaiEngine::new(engine, async_timeout_seconds);
+        // Create the engine and wrap it in our opaque handle
+        let wrapper = CRhaiEngine::new(engine_config);
         Box::into_raw(Box::new(wrapper))
     }}
 }
 
 /// Frees a Rhai engine instance.
 ///
-/// This function cleans up the engine and removes any pending async futures
-/// associated with this engine from the global registry.
+/// This function cleans up the engine, drops the callbacks registered with it,
+/// and cancels its in-flight async evals and pending function requests.
 ///
 /// # Safety
 ///
@@ -224,12 +221,10 @@ pub extern "C" fn rhai_engine_new(config: *const CRhaiConfig) -> *mut CRhaiEngin
 pub extern "C" fn rhai_engine_free(engine: *mut CRhaiEngine) {
     let _result = catch_panic! {{
         if !engine.is_null() {
-            // Note: In a per-engine future registry, we would clean up pending futures here.
-            // Since we're using a global registry, we log a debug message but can't
-            // distinguish which futures belong to this engine.
-            // This is acceptable as futures will be cleaned up on timeout or completion.
-            #[cfg(debug_assertions)]
-            eprintln!("[DEBUG] Freeing engine - pending futures (if any) will be cleaned up on timeout");
+            // Cancel the engine's async evals and drop its callbacks
+            let engine_id = unsafe { &*engine }.engine_id();
+            crate::async_eval::cancel_engine_evals(engine_id);
+            crate::functions::unregister_engine_callbacks(engine_id);
 
             unsafe {
                 // Reclaim ownership and drop
@@ -241,6 +236,32 @@ pub extern "C" fn rhai_engine_free(engine: *mut CRhaiEngine) {
     }};
 }
 
+/// Gets the unique ID of an engine.
+///
+/// Async function requests are tagged with the ID of the engine whose eval made
+/// them; see `rhai_get_pending_function_request_for_engine`.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. The engine pointer must be valid.
+///
+/// # Returns
+///
+/// The engine ID, or -1 if the engine pointer is null.
+#[no_mangle]
+pub extern "C" fn rhai_engine_id(engine: *const CRhaiEngine) -> i64 {
+    catch_panic! {{
+        clear_last_error();
+
+        if engine.is_null() {
+            set_last_error("Engine pointer is null");
+            return -1;
+        }
+
+        unsafe { &*engine }.engine_id()
+    }}
+}
+
 /// Evaluates a Rhai script and returns the result as a JSON string.
 ///
 /// This function runs the script within a Tokio runtime context to support
@@ -286,9 +307,10 @@ pub extern "C" fn rhai_eval(
             return -1;
         }
 
-        // Get the engine
+        // Hold the engine for the whole eval: Dart callbacks may register or
+        // unregister functions meanwhile, which replaces the engine's own copy
         let engine_wrapper = unsafe { &*engine };
-        let rhai_engine = engine_wrapper.engine();
+        let rhai_engine = engine_wrapper.inner.clone();
 
         // Convert C string to Rust string
         let script_str = unsafe {
@@ -303,51 +325,224 @@ pub extern "C" fn rhai_eval(
 
         // Get the scope and evaluate the script with it
         // This allows variables set via rhai_set_var/rhai_set_constant to be available
-        let mut scope = engine_wrapper.scope();
-        let result: Result<Dynamic, Box<rhai::EvalAltResult>> = rhai_engine.eval_with_scope(&mut scope, script_str);
+        let result = eval_to_json(&rhai_engine, &mut engine_wrapper.scope(), script_str);
+        deliver_watch_notifications();
+
+        match result {
+            Ok(json) => {
+                // Convert to C string
+                match CString::new(json) {
+                    Ok(c_string) => {
+                        unsafe {
+                            *result_out = c_string.into_raw();
+                        }
+                        0 // Success
+                    }
+                    Err(e) => {
+                        set_last_error(&format!("Failed to create C string: {}", e));
+                        -1
+                    }
+                }
+            }
+            Err(error_msg) => {
+                set_last_error(&error_msg);
+                -1
+            }
+        }
+    }}
+}
 
-        // Check if async functions were invoked during eval
-        // Sync eval() should not be used with async functions - users should use evalAsync()
-        if crate::functions::check_and_clear_async_flag() {
-            set_last_error("Script attempted to call async functions. Use evalAsync() instead of eval() for scripts with async functions.");
+/// Evaluates a Rhai script with per-call options and returns the result as JSON.
+///
+/// This is the general form of `rhai_eval`. The script runs against the given
+/// scope handle, or against the engine's own scope when `scope` is null.
+///
+/// When `options.transactional` is set, the scope is snapshotted before the
+/// script runs and restored if the script errors or times out, so a script that
+/// throws halfway through never leaves the scope partially mutated. The
+/// `merge_scope` and `merge_conflict` options only apply to async evals.
+///
+/// When `options.track_changes` is set, the result is wrapped as
+/// `{"value": <result>, "changes": {"added": {..}, "removed": [..], "modified": {..}}}`,
+/// listing the scope variables the script changed with their new values.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. The engine and script pointers must be valid,
+/// and the scope and options pointers must be valid or null.
+///
+/// # Returns
+///
+/// 0 on success (with result stored via result_out), -1 on error.
+/// On error, use `rhai_get_last_error()` to retrieve the error message.
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `scope` - Pointer to a scope handle, or null to use the engine's scope
+/// * `script` - Pointer to a null-terminated C string containing the script
+/// * `options` - Pointer to the eval options, or null for defaults
+/// * `result_out` - Pointer to store the result JSON string (must be freed with rhai_free_error)
+#[no_mangle]
+pub extern "C" fn rhai_eval_with_options(
+    engine: *const CRhaiEngine,
+    scope: *const CRhaiScope,
+    script: *const c_char,
+    options: *const CRhaiEvalOptions,
+    result_out: *mut *mut c_char,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        // Validate pointers
+        if engine.is_null() {
+            set_last_error("Engine pointer is null");
             return -1;
         }
 
-        match result {
-            Ok(value) => {
-                // Convert the result to JSON
-                match rhai_dynamic_to_json(&value) {
-                    Ok(json) => {
-                        // Convert to C string
-                        match CString::new(json) {
-                            Ok(c_string) => {
-                                unsafe {
-                                    *result_out = c_string.into_raw();
-                                }
-                                0 // Success
-                            }
-                            Err(e) => {
-                                set_last_error(&format!("Failed to create C string: {}", e));
-                                -1
-                            }
+        if script.is_null() {
+            set_last_error("Script pointer is null");
+            return -1;
+        }
+
+        if result_out.is_null() {
+            set_last_error("Result output pointer is null");
+            return -1;
+        }
+
+        let options = if options.is_null() {
+            CRhaiEvalOptions::default()
+        } else {
+            unsafe { *options }
+        };
+
+        let engine_wrapper = unsafe { &*engine };
+
+        // Convert C string to Rust string
+        let script_str = unsafe {
+            match CStr::from_ptr(script).to_str() {
+                Ok(s) => s,
+                Err(e) => {
+                    set_last_error(&format!("Invalid UTF-8 in script: {}", e));
+                    return -1;
+                }
+            }
+        };
+
+        let target = engine_wrapper.scope_target(scope);
+        let mut state = target.lock().unwrap();
+
+        // Snapshot the scope so a failed script can be rolled back,
+        // or so the changes it made can be reported
+        let snapshot = if options.transactional != 0 || options.track_changes != 0 {
+            Some(state.vars.clone())
+        } else {
+            None
+        };
+
+        // Hold the engine for the whole eval, as in rhai_eval
+        let rhai_engine = engine_wrapper.inner.clone();
+        let result = eval_to_json(&rhai_engine, &mut state, script_str)
+            .and_then(|json| match &snapshot {
+                Some(before) if options.track_changes != 0 => {
+                    result_with_changes(&json, &diff_scopes(before, &state.vars))
+                }
+                _ => Ok(json),
+            });
+
+        let status = match result {
+            Ok(json) => {
+                // Convert to C string
+                match CString::new(json) {
+                    Ok(c_string) => {
+                        unsafe {
+                            *result_out = c_string.into_raw();
                         }
+                        0 // Success
                     }
                     Err(e) => {
-                        set_last_error(&format!("Failed to convert result to JSON: {}", e));
+                        set_last_error(&format!("Failed to create C string: {}", e));
                         -1
                     }
                 }
             }
-            Err(err) => {
-                // Format the error with type and position information
-                let error_msg = format_rhai_error(&err);
+            Err(error_msg) => {
+                if let Some(snapshot) = snapshot.filter(|_| options.transactional != 0) {
+                    state.vars = snapshot;
+                }
                 set_last_error(&error_msg);
                 -1
             }
-        }
+        };
+
+        // Notify watchers only once the scope is unlocked
+        drop(state);
+        deliver_watch_notifications();
+
+        status
     }}
 }
 
+/// Evaluates a script against the given scope and converts the result to JSON.
+///
+/// This is the shared sync evaluation path used by `rhai_eval` and the scope
+/// handle variants. Errors are returned already formatted for `set_last_error`.
+/// If the script gives a type-locked variable another type, the scope is
+/// restored and a type lock violation is returned, at the position of the
+/// statement that made it. Changes to watched variables
+/// are queued; callers deliver them with `deliver_watch_notifications` once the
+/// scope lock is released.
+pub(crate) fn eval_to_json(
+    engine: &Engine,
+    state: &mut ScopeState,
+    script: &str,
+) -> Result<String, String> {
+    // Keep the starting variables while any are type-locked or watched, so a
+    // script that changes a locked variable's type can be rolled back, and
+    // changes to watched variables can be reported
+    let before = if state.type_locks.is_empty() && state.watch.is_none() {
+        None
+    } else {
+        Some(state.vars.clone())
+    };
+
+    // Resolved variables are cached for this eval only
+    let resolver_cache = ResolverCacheGuard::begin();
+    let live_locks = LiveTypeLockGuard::begin(&state.type_locks);
+    let result: Result<Dynamic, Box<rhai::EvalAltResult>> = engine.eval_with_scope(&mut state.vars, script);
+    let last_statement = live_locks.finish();
+    drop(resolver_cache);
+
+    // Check if async functions were invoked during eval
+    // Sync eval() should not be used with async functions - users should use evalAsync()
+    if crate::functions::check_and_clear_async_flag() {
+        return Err("Script attempted to call async functions. Use evalAsync() instead of eval() for scripts with async functions.".to_string());
+    }
+
+    if let Some(before) = before {
+        if let Err(violation) = check_type_locks(&state.type_locks, &state.vars, last_statement) {
+            state.vars = before;
+            // A violation caught while the script ran is already its error
+            return Err(match result {
+                Err(err) => format_rhai_error(&err),
+                Ok(_) => violation,
+            });
+        }
+
+        if let (Some(watch), Ok(_)) = (&state.watch, &result) {
+            queue_watch_notification(watch, &before, &state.vars);
+        }
+    }
+
+    match result {
+        // Convert the result to JSON
+        Ok(value) => rhai_dynamic_to_json(&value)
+            .map_err(|e| format!("Failed to convert result to JSON: {}", e)),
+        // Format the error with type and position information
+        Err(err) => Err(format_rhai_error(&err)),
+    }
+}
+
 /// Formats a Rhai error with type and position information.
 ///
 /// This function extracts line numbers from syntax errors and formats
@@ -410,6 +605,18 @@ pub fn format_rhai_error(err: &rhai::EvalAltResult) -> String {
             format!("Runtime error at line {}: Stack overflow", pos.line().unwrap_or(0))
         }
 
+        // Variable definition denied by the definition policy
+        EvalAltResult::ErrorForbiddenVariable(name, pos) => {
+            let reason = take_definition_violation()
+                .unwrap_or_else(|| format!("'{}' is forbidden", name));
+            format!(
+                "Runtime error at line {}, position {}: Variable definition not allowed: {}",
+                pos.line().unwrap_or(0),
+                pos.position().unwrap_or(0),
+                reason
+            )
+        }
+
         // Generic catch-all for other errors
         _ => {
             format!("Runtime error: {}", err)
@@ -555,18 +762,29 @@ pub extern "C" fn rhai_analyze(
     }}
 }
 
-/// Sets a mutable variable in the engine's scope.
+/// Sets a mutable variable in the engine's scope, optionally locking its type.
 ///
 /// This variable will be available to scripts executed with this engine.
 /// Variables set this way can be modified by the script.
 ///
+/// With a type lock, any script assignment that gives the variable a value of
+/// another type fails with a runtime error naming the variable and both types,
+/// at the position of the statement that made it, and the scope is restored to
+/// its state before that eval. The lock stays in place when the variable is set
+/// again without one, and is removed by `rhai_clear_scope`.
+///
+/// Supported lock modes are `int`, `float`, `number` (int or float), `string`,
+/// `bool`, `array`, `map`, or an inline JSON schema object (supporting the
+/// `type`, `enum`, `properties`, `required` and `items` keywords).
+///
 /// # Safety
 ///
 /// This function is safe to call from FFI. The engine, name, and value_json pointers must be valid.
+/// The type_lock pointer may be null, in which case the variable keeps any existing lock.
 ///
 /// # Returns
 ///
-/// 0 on success, -1 on error.
+/// 0 on success, -1 on error (including when the value does not satisfy the lock).
 /// On error, use `rhai_get_last_error()` to retrieve the error message.
 ///
 /// # Arguments
@@ -574,11 +792,13 @@ pub extern "C" fn rhai_analyze(
 /// * `engine` - Pointer to the Rhai engine
 /// * `name` - Pointer to a null-terminated C string containing the variable name
 /// * `value_json` - Pointer to a null-terminated C string containing the JSON-encoded value
+/// * `type_lock` - Pointer to a null-terminated C string containing the lock mode, or null
 #[no_mangle]
 pub extern "C" fn rhai_set_var(
     engine: *mut CRhaiEngine,
     name: *const c_char,
     value_json: *const c_char,
+    type_lock: *const c_char,
 ) -> i32 {
     catch_panic! {{
         clear_last_error();
@@ -633,9 +853,20 @@ pub extern "C" fn rhai_set_var(
             }
         };
 
-        // Get scope and push the variable
+        let lock = match type_lock_from_c(type_lock) {
+            Ok(lock) => lock,
+            Err(e) => {
+                set_last_error(&e);
+                return -1;
+            }
+        };
+
+        // Get scope and push the variable, enforcing its type lock
         let mut scope = engine_wrapper.scope();
-        scope.push(var_name, dynamic_value);
+        if let Err(e) = push_with_lock(&mut scope, var_name, dynamic_value, false, lock) {
+            set_last_error(&e);
+            return -1;
+        }
 
         0 // Success
     }}
@@ -719,9 +950,12 @@ pub extern "C" fn rhai_set_constant(
             }
         };
 
-        // Get scope and push the constant
+        // Get scope and push the constant, respecting any type lock on the name
         let mut scope = engine_wrapper.scope();
-        scope.push_constant(const_name, dynamic_value);
+        if let Err(e) = push_with_lock(&mut scope, const_name, dynamic_value, true, None) {
+            set_last_error(&e);
+            return -1;
+        }
 
         0 // Success
     }}
@@ -729,8 +963,8 @@ pub extern "C" fn rhai_set_constant(
 
 /// Clears all variables and constants from the engine's scope.
 ///
-/// This removes all variables previously set via `rhai_set_var` and `rhai_set_constant`.
-/// Registered functions are not affected.
+/// This removes all variables previously set via `rhai_set_var` and `rhai_set_constant`,
+/// and any type locks. Registered functions are not affected.
 ///
 /// # Safety
 ///
@@ -758,9 +992,10 @@ pub extern "C" fn rhai_clear_scope(engine: *mut CRhaiEngine) -> i32 {
         // Get the engine wrapper
         let engine_wrapper = unsafe { &*engine };
 
-        // Get scope and clear it
+        // Get scope and clear it, along with its type locks
         let mut scope = engine_wrapper.scope();
         scope.clear();
+        scope.type_locks.clear();
 
         0 // Success
     }}
@@ -1187,4 +1422,225 @@ mod tests {
 
         rhai_engine_free(engine);
     }
+
+    #[test]
+    fn test_eval_transactional_rolls_back_on_error() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("balance").unwrap();
+        let value = CString::new("100").unwrap();
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), std::ptr::null()), 0);
+
+        let options = CRhaiEvalOptions { transactional: 1, ..Default::default() };
+        let script = CString::new("balance -= 30; let fee = 5; throw \"declined\";").unwrap();
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        let ret = rhai_eval_with_options(engine, std::ptr::null(), script.as_ptr(), &options, &mut result_ptr);
+        assert_eq!(ret, -1);
+
+        unsafe {
+            let scope = (*engine).scope();
+            assert_eq!(scope.get_value::<i64>("balance"), Some(100));
+            assert!(!scope.contains("fee"));
+        }
+
+        // Without the transactional flag, the partial change stays
+        let ret = rhai_eval_with_options(engine, std::ptr::null(), script.as_ptr(), std::ptr::null(), &mut result_ptr);
+        assert_eq!(ret, -1);
+        unsafe {
+            assert_eq!((*engine).scope().get_value::<i64>("balance"), Some(70));
+        }
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_eval_transactional_rolls_back_on_timeout() {
+        let c_config = CRhaiConfig {
+            max_operations: 100,
+            ..CRhaiConfig::secure_defaults()
+        };
+        let engine = rhai_engine_new(&c_config as *const CRhaiConfig);
+        let name = CString::new("x").unwrap();
+        let value = CString::new("0").unwrap();
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), std::ptr::null()), 0);
+
+        let options = CRhaiEvalOptions { transactional: 1, ..Default::default() };
+        let script = CString::new("loop { x += 1; }").unwrap();
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        let ret = rhai_eval_with_options(engine, std::ptr::null(), script.as_ptr(), &options, &mut result_ptr);
+        assert_eq!(ret, -1);
+
+        unsafe {
+            assert_eq!((*engine).scope().get_value::<i64>("x"), Some(0));
+        }
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_eval_with_change_tracking() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("total").unwrap();
+        let value = CString::new("10").unwrap();
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), std::ptr::null()), 0);
+        let name = CString::new("untouched").unwrap();
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), std::ptr::null()), 0);
+
+        let options = CRhaiEvalOptions { track_changes: 1, ..Default::default() };
+        let script = CString::new("total += 5; let label = \"done\"; total").unwrap();
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        let ret = rhai_eval_with_options(engine, std::ptr::null(), script.as_ptr(), &options, &mut result_ptr);
+        assert_eq!(ret, 0);
+
+        let result_json = unsafe { CString::from_raw(result_ptr).into_string().unwrap() };
+        let result: serde_json::Value = serde_json::from_str(&result_json).unwrap();
+        assert_eq!(result["value"], 15);
+        assert_eq!(result["changes"]["modified"], serde_json::json!({ "total": 15 }));
+        assert_eq!(result["changes"]["added"], serde_json::json!({ "label": "done" }));
+        assert_eq!(result["changes"]["removed"], serde_json::json!([]));
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_type_locked_var_rejects_type_change() {
+        use crate::error::{rhai_get_last_error, rhai_free_error};
+
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("total").unwrap();
+        let value = CString::new("10").unwrap();
+        let lock = CString::new("int").unwrap();
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), lock.as_ptr()), 0);
+
+        // Same-type assignments are fine
+        let script = CString::new("total += 5; total").unwrap();
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        assert_eq!(rhai_eval(engine, script.as_ptr(), &mut result_ptr), 0);
+        unsafe { drop(CString::from_raw(result_ptr)) };
+
+        let script = CString::new("total = \"oops\"; let other = 1;").unwrap();
+        assert_eq!(rhai_eval(engine, script.as_ptr(), &mut result_ptr), -1);
+
+        let error_ptr = rhai_get_last_error();
+        unsafe {
+            let error_str = CStr::from_ptr(error_ptr).to_str().unwrap();
+            assert!(error_str.starts_with("Runtime error"));
+            assert!(error_str.contains("'total' is locked to int, but was assigned string"));
+            rhai_free_error(error_ptr);
+        }
+
+        // The scope is rolled back to its state before the failing eval
+        unsafe {
+            let scope = (*engine).scope();
+            assert_eq!(scope.get_value::<i64>("total"), Some(15));
+            assert!(!scope.contains("other"));
+        }
+
+        // Setting the variable again from Dart keeps the lock
+        let value = CString::new("\"text\"").unwrap();
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), std::ptr::null()), -1);
+
+        // Clearing the scope removes the lock
+        assert_eq!(rhai_clear_scope(engine), 0);
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), std::ptr::null()), 0);
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_type_locked_var_is_checked_while_running() {
+        use crate::error::{rhai_get_last_error, rhai_free_error};
+        use crate::functions::rhai_register_function;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        extern "C" fn count_call(_: i64, _: *const c_char) -> *mut c_char {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+            unsafe { libc::strdup(cr#"{"status":"success","value":null}"#.as_ptr()) }
+        }
+
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("total").unwrap();
+        let value = CString::new("10").unwrap();
+        let lock = CString::new("int").unwrap();
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), lock.as_ptr()), 0);
+        let function = CString::new("f").unwrap();
+        assert_eq!(rhai_register_function(engine, function.as_ptr(), 1, count_call), 0);
+
+        let last_error = || unsafe {
+            let error_ptr = rhai_get_last_error();
+            let error = CStr::from_ptr(error_ptr).to_str().unwrap().to_string();
+            rhai_free_error(error_ptr);
+            error
+        };
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+
+        // Restoring the type later doesn't hide the violation, and the
+        // statements after it don't run
+        let script = CString::new("total = \"x\";\nf(total);\ntotal = 5;").unwrap();
+        assert_eq!(rhai_eval(engine, script.as_ptr(), &mut result_ptr), -1);
+        let error = last_error();
+        assert!(error.starts_with("Runtime error at line 1:"), "{}", error);
+        assert!(error.contains("'total' is locked to int, but was assigned string"));
+        assert_eq!(CALLS.load(Ordering::SeqCst), 0);
+
+        // Violations inside blocks and in the last statement are reported at their line
+        let script = CString::new("if true {\n    total = [];\n}\nf(total);").unwrap();
+        assert_eq!(rhai_eval(engine, script.as_ptr(), &mut result_ptr), -1);
+        assert!(last_error().starts_with("Runtime error at line 2:"));
+
+        let script = CString::new("f(total);\ntotal = false;").unwrap();
+        assert_eq!(rhai_eval(engine, script.as_ptr(), &mut result_ptr), -1);
+        assert!(last_error().starts_with("Runtime error at line 2:"));
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+
+        unsafe {
+            assert_eq!((*engine).scope().get_value::<i64>("total"), Some(10));
+        }
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_schema_locked_var_is_checked_when_eval_finishes() {
+        use crate::error::{rhai_get_last_error, rhai_free_error};
+
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("user").unwrap();
+        let value = CString::new(r#"{"id": 1}"#).unwrap();
+        let lock = CString::new(r#"{"type": "object", "required": ["id"]}"#).unwrap();
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), lock.as_ptr()), 0);
+
+        // Schema locks only have to hold once the eval is over
+        let script = CString::new("user = #{};\nuser.id = 2;").unwrap();
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        assert_eq!(rhai_eval(engine, script.as_ptr(), &mut result_ptr), 0);
+        unsafe { drop(CString::from_raw(result_ptr)) };
+
+        let script = CString::new("user.id = 3;\nuser = #{ name: \"x\" };").unwrap();
+        assert_eq!(rhai_eval(engine, script.as_ptr(), &mut result_ptr), -1);
+        unsafe {
+            let error_ptr = rhai_get_last_error();
+            let error = CStr::from_ptr(error_ptr).to_str().unwrap().to_string();
+            rhai_free_error(error_ptr);
+            assert!(error.starts_with("Runtime error at line 2:"), "{}", error);
+            assert!(error.contains("missing required property 'id'"), "{}", error);
+            assert_eq!((*engine).scope().get_value::<rhai::Map>("user").unwrap()["id"].as_int(), Ok(2));
+        }
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_type_locked_var_invalid_mode() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("total").unwrap();
+        let value = CString::new("10").unwrap();
+        let lock = CString::new("integer").unwrap();
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), lock.as_ptr()), -1);
+
+        let lock = CString::new("string").unwrap();
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), lock.as_ptr()), -1);
+
+        rhai_engine_free(engine);
+    }
 }
@@ -0,0 +1,322 @@
+//! Bounded worker pool for async evals
+//!
+//! Async evals run on a pool of worker threads instead of one new thread per
+//! eval. At most `max_concurrent` evals run at once; the others wait in a queue
+//! of at most `max_queued` evals, and starting an eval fails once the queue is
+//! full. Queued evals are taken from the engines in turn, so an engine that
+//! starts a burst of evals can't starve the others.
+//!
+//! Workers are started on demand, up to `max_concurrent`, and exit when the
+//! queue is empty.
+
+use crate::error::clear_last_error;
+use crate::catch_panic;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Default maximum number of async evals running at once.
+pub const DEFAULT_MAX_CONCURRENT_EVALS: usize = 16;
+
+/// Default maximum number of async evals waiting for a worker.
+pub const DEFAULT_MAX_QUEUED_EVALS: usize = 1024;
+
+/// An async eval waiting for a worker.
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// The mutable state of a pool.
+struct PoolState {
+    /// Maximum number of evals running at once
+    max_concurrent: usize,
+
+    /// Maximum number of evals waiting for a worker
+    max_queued: usize,
+
+    /// Number of running workers
+    active: usize,
+
+    /// Number of evals waiting for a worker
+    queued: usize,
+
+    /// Waiting evals per engine
+    queues: HashMap<i64, VecDeque<Job>>,
+
+    /// Engines with waiting evals, in the order they get their next turn
+    turns: VecDeque<i64>,
+}
+
+impl PoolState {
+    /// Takes the next eval, from the engine whose turn it is.
+    fn next_job(&mut self) -> Option<Job> {
+        let engine_id = self.turns.pop_front()?;
+        let queue = self.queues.get_mut(&engine_id)?;
+        let job = queue.pop_front();
+
+        if queue.is_empty() {
+            self.queues.remove(&engine_id);
+        } else {
+            self.turns.push_back(engine_id);
+        }
+
+        self.queued -= 1;
+        job
+    }
+}
+
+/// A bounded pool of worker threads for async evals.
+pub(crate) struct EvalPool {
+    state: Mutex<PoolState>,
+}
+
+lazy_static::lazy_static! {
+    /// The pool all async evals run on.
+    static ref EVAL_POOL: Arc<EvalPool> = Arc::new(EvalPool::new(
+        DEFAULT_MAX_CONCURRENT_EVALS,
+        DEFAULT_MAX_QUEUED_EVALS,
+    ));
+}
+
+impl EvalPool {
+    /// Creates an empty pool with the given limits.
+    pub(crate) fn new(max_concurrent: usize, max_queued: usize) -> Self {
+        Self {
+            state: Mutex::new(PoolState {
+                max_concurrent,
+                max_queued,
+                active: 0,
+                queued: 0,
+                queues: HashMap::new(),
+                turns: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Gets the pool all async evals run on.
+    pub(crate) fn global() -> Arc<EvalPool> {
+        EVAL_POOL.clone()
+    }
+
+    /// Queues an eval for an engine, starting a worker if one is free.
+    ///
+    /// # Returns
+    ///
+    /// Ok, or an error message if the queue is full
+    pub(crate) fn submit(
+        self: &Arc<Self>,
+        engine_id: i64,
+        job: impl FnOnce() + Send + 'static,
+    ) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if state.queued >= state.max_queued {
+            return Err(format!(
+                "Async eval queue is full ({} evals queued)",
+                state.queued
+            ));
+        }
+
+        let queue = state.queues.entry(engine_id).or_default();
+        queue.push_back(Box::new(job));
+        if queue.len() == 1 {
+            state.turns.push_back(engine_id);
+        }
+        state.queued += 1;
+
+        if state.active < state.max_concurrent {
+            state.active += 1;
+            let pool = self.clone();
+            thread::spawn(move || pool.run_worker());
+        }
+
+        Ok(())
+    }
+
+    /// Runs queued evals until the queue is empty.
+    fn run_worker(&self) {
+        loop {
+            let job = {
+                let mut state = self.state.lock().unwrap();
+
+                // Leave if the pool was shrunk below the running workers
+                let job = if state.active > state.max_concurrent {
+                    None
+                } else {
+                    state.next_job()
+                };
+
+                if job.is_none() {
+                    state.active -= 1;
+                }
+                job
+            };
+
+            match job {
+                // A panicking eval must not take the worker's slot with it
+                Some(job) => {
+                    let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(job));
+                }
+                None => return,
+            }
+        }
+    }
+
+    /// Changes the limits of the pool.
+    ///
+    /// Running evals are not interrupted, and queued evals are kept even if the
+    /// new queue limit is lower.
+    pub(crate) fn configure(self: &Arc<Self>, max_concurrent: usize, max_queued: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.max_concurrent = max_concurrent;
+        state.max_queued = max_queued;
+
+        // Start workers for queued evals the old limit held back
+        let idle = state.max_concurrent.saturating_sub(state.active);
+        for _ in 0..idle.min(state.queued) {
+            state.active += 1;
+            let pool = self.clone();
+            thread::spawn(move || pool.run_worker());
+        }
+    }
+
+    /// Gets the number of running and queued evals.
+    pub(crate) fn stats(&self) -> (usize, usize) {
+        let state = self.state.lock().unwrap();
+        (state.active, state.queued)
+    }
+}
+
+/// Configures the async eval worker pool.
+///
+/// Running evals are not interrupted when the limits are lowered.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI.
+///
+/// # Returns
+///
+/// 0 on success, -1 on error
+///
+/// # Arguments
+///
+/// * `max_concurrent` - Maximum number of async evals running at once (at least 1)
+/// * `max_queued` - Maximum number of async evals waiting for a worker
+#[no_mangle]
+pub extern "C" fn rhai_eval_pool_configure(max_concurrent: u64, max_queued: u64) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        if max_concurrent == 0 {
+            set_last_error("Maximum number of concurrent evals must be at least 1");
+            return -1;
+        }
+
+        EvalPool::global().configure(max_concurrent as usize, max_queued as usize);
+        0 // Success
+    }}
+}
+
+/// Gets the metrics of the async eval worker pool.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. The output pointers must be valid.
+///
+/// # Returns
+///
+/// 0 on success, -1 on error
+///
+/// # Arguments
+///
+/// * `active_out` - Pointer to store the number of running evals
+/// * `queued_out` - Pointer to store the number of evals waiting for a worker
+#[no_mangle]
+pub extern "C" fn rhai_eval_pool_stats(active_out: *mut u64, queued_out: *mut u64) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        if active_out.is_null() {
+            set_last_error("Active count output pointer is null");
+            return -1;
+        }
+
+        if queued_out.is_null() {
+            set_last_error("Queued count output pointer is null");
+            return -1;
+        }
+
+        let (active, queued) = EvalPool::global().stats();
+        unsafe {
+            *active_out = active as u64;
+            *queued_out = queued as u64;
+        }
+
+        0 // Success
+    }}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_pool_bounds_workers_and_queue() {
+        let pool = Arc::new(EvalPool::new(1, 2));
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let release_rx = Arc::new(Mutex::new(release_rx));
+
+        let wait_for_stats = |expected: (usize, usize)| {
+            for _ in 0..100 {
+                if pool.stats() == expected {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            assert_eq!(pool.stats(), expected);
+        };
+
+        for queued in 0..3 {
+            let release_rx = release_rx.clone();
+            pool.submit(1, move || {
+                release_rx.lock().unwrap().recv().unwrap();
+            }).unwrap();
+            wait_for_stats((1, queued));
+        }
+
+        // One eval is running, two are queued, and the queue is full
+        let err = pool.submit(1, || {}).unwrap_err();
+        assert!(err.contains("queue is full"), "{}", err);
+
+        for _ in 0..3 {
+            release_tx.send(()).unwrap();
+        }
+        wait_for_stats((0, 0));
+    }
+
+    #[test]
+    fn test_pool_takes_engines_in_turn() {
+        let pool = Arc::new(EvalPool::new(1, 10));
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+
+        // Hold the only worker until the burst is queued
+        pool.submit(0, move || {
+            release_rx.recv().unwrap();
+        }).unwrap();
+
+        for (engine_id, label) in [(1, "a1"), (1, "a2"), (1, "a3"), (2, "b1"), (2, "b2")] {
+            let order = order.clone();
+            pool.submit(engine_id, move || order.lock().unwrap().push(label)).unwrap();
+        }
+        release_tx.send(()).unwrap();
+
+        for _ in 0..100 {
+            if order.lock().unwrap().len() == 5 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(*order.lock().unwrap(), vec!["a1", "b1", "a2", "b2", "a3"]);
+    }
+}
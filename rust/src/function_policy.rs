@@ -0,0 +1,240 @@
+//! Dart function policy
+//!
+//! An engine can restrict which of its registered Dart functions scripts may
+//! call. The policy JSON has `allow` and `deny` lists of rules:
+//!
+//! ```json
+//! {"allow": ["http::*", "log"], "deny": ["http::delete"]}
+//! ```
+//!
+//! A rule is a function name (`log`), a namespaced function name
+//! (`http::delete`), every function of a namespace (`http::*`), or every
+//! function (`*`). With an `allow` list, a function must match one of its
+//! rules; a function matching a `deny` rule is never allowed.
+//!
+//! Functions the policy does not allow stay registered, and are listed by
+//! `rhai_list_functions`, but are left out of the engine: scripts calling them
+//! fail with "function not found". The policy only applies to Dart functions,
+//! not to Rhai's built-in functions.
+
+use crate::error::clear_last_error;
+use crate::types::CRhaiEngine;
+use crate::catch_panic;
+use serde::Deserialize;
+use std::ffi::{CStr, c_char};
+
+/// Function policy settings, as passed from Dart.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FunctionPolicyConfig {
+    /// Rules a function must match one of, if not empty
+    #[serde(default)]
+    allow: Vec<String>,
+
+    /// Rules no function may match
+    #[serde(default)]
+    deny: Vec<String>,
+}
+
+/// A rule of a function policy.
+#[derive(Debug, Clone, PartialEq)]
+enum FunctionRule {
+    /// Every function
+    Any,
+
+    /// Every function of a namespace
+    Namespace(String),
+
+    /// One function, by its full name
+    Function(String),
+}
+
+impl FunctionRule {
+    /// Parses a rule.
+    fn parse(rule: &str) -> Result<Self, String> {
+        let rule = rule.trim();
+        if rule == "*" {
+            return Ok(Self::Any);
+        }
+        match rule.strip_suffix("::*") {
+            Some(namespace) if !namespace.is_empty() => Ok(Self::Namespace(namespace.to_string())),
+            Some(_) => Err(format!("Invalid function policy rule: {}", rule)),
+            None if !rule.is_empty() => Ok(Self::Function(rule.to_string())),
+            None => Err("Function policy rules cannot be empty".to_string()),
+        }
+    }
+
+    /// Checks whether the rule covers a function.
+    fn matches(&self, name: &str, namespace: Option<&str>) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Namespace(rule) => namespace == Some(rule.as_str()),
+            Self::Function(rule) => name == rule,
+        }
+    }
+}
+
+/// The rules deciding which Dart functions scripts may call.
+///
+/// The default policy allows every function.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FunctionPolicy {
+    /// Rules a function must match one of, if not empty
+    allow: Vec<FunctionRule>,
+
+    /// Rules no function may match
+    deny: Vec<FunctionRule>,
+}
+
+impl FunctionPolicy {
+    /// Builds a policy from its JSON settings.
+    fn from_json(json: &str) -> Result<Self, String> {
+        let config: FunctionPolicyConfig = serde_json::from_str(json)
+            .map_err(|e| format!("Invalid function policy JSON: {}", e))?;
+
+        let parse_rules = |rules: Vec<String>| {
+            rules.iter().map(|rule| FunctionRule::parse(rule)).collect::<Result<Vec<_>, _>>()
+        };
+        Ok(Self {
+            allow: parse_rules(config.allow)?,
+            deny: parse_rules(config.deny)?,
+        })
+    }
+
+    /// Checks whether scripts may call a function.
+    ///
+    /// `name` is the full name the function was registered with, and
+    /// `namespace` its namespace, if any.
+    pub(crate) fn allows(&self, name: &str, namespace: Option<&str>) -> bool {
+        let allowed = self.allow.is_empty() || self.allow.iter().any(|rule| rule.matches(name, namespace));
+        allowed && !self.deny.iter().any(|rule| rule.matches(name, namespace))
+    }
+}
+
+/// Sets or removes the Dart function policy of an engine.
+///
+/// The policy JSON accepts `allow` and `deny` lists of rules (see the module
+/// documentation for the rule syntax). Pass a null policy JSON to remove the
+/// policy. Async evals already running keep the functions they started with.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. The engine pointer must be valid, and
+/// the policy_json pointer must be valid or null.
+///
+/// # Returns
+///
+/// 0 on success, -1 on error
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `policy_json` - JSON object with the policy rules, or null
+#[no_mangle]
+pub extern "C" fn rhai_set_function_policy(engine: *mut CRhaiEngine, policy_json: *const c_char) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        // Validate pointer
+        if engine.is_null() {
+            set_last_error("Engine pointer is null");
+            return -1;
+        }
+
+        let engine_wrapper = unsafe { &mut *engine };
+
+        let policy = if policy_json.is_null() {
+            FunctionPolicy::default()
+        } else {
+            let json_str = unsafe {
+                match CStr::from_ptr(policy_json).to_str() {
+                    Ok(s) => s,
+                    Err(e) => {
+                        set_last_error(&format!("Invalid UTF-8 in policy JSON: {}", e));
+                        return -1;
+                    }
+                }
+            };
+            match FunctionPolicy::from_json(json_str) {
+                Ok(policy) => policy,
+                Err(e) => {
+                    set_last_error(&e);
+                    return -1;
+                }
+            }
+        };
+
+        // Functions are added to or left out of the engine when it is built
+        engine_wrapper.function_policy = policy;
+        engine_wrapper.rebuild_engine();
+
+        0 // Success
+    }}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{rhai_engine_free, rhai_engine_new, rhai_eval};
+    use crate::functions::rhai_register_function;
+    use std::ffi::CString;
+
+    extern "C" fn answer_ok(_: i64, _: *const c_char) -> *mut c_char {
+        unsafe { libc::strdup(cr#"{"status":"success","value":"ok"}"#.as_ptr()) }
+    }
+
+    fn eval(engine: *const CRhaiEngine, script: &str) -> bool {
+        let script = CString::new(script).unwrap();
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        if rhai_eval(engine, script.as_ptr(), &mut result_ptr) == 0 {
+            drop(unsafe { CString::from_raw(result_ptr) });
+            true
+        } else {
+            false
+        }
+    }
+
+    fn set_policy(engine: *mut CRhaiEngine, json: &str) -> i32 {
+        let json = CString::new(json).unwrap();
+        rhai_set_function_policy(engine, json.as_ptr())
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        assert_eq!(FunctionRule::parse("*").unwrap(), FunctionRule::Any);
+        assert_eq!(FunctionRule::parse("db::*").unwrap(), FunctionRule::Namespace("db".to_string()));
+        assert_eq!(FunctionRule::parse("db::query").unwrap(), FunctionRule::Function("db::query".to_string()));
+        assert!(FunctionRule::parse("::*").is_err());
+        assert!(FunctionPolicy::from_json(r#"{"allow": [""]}"#).is_err());
+        assert!(FunctionPolicy::from_json(r#"{"block": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_policy_applies_per_namespace() {
+        let engine = rhai_engine_new(std::ptr::null());
+        for name in ["log", "http::get", "http::delete", "db::query"] {
+            let name = CString::new(name).unwrap();
+            assert_eq!(rhai_register_function(engine, name.as_ptr(), 101, answer_ok), 0);
+        }
+
+        assert_eq!(set_policy(engine, r#"{"allow": ["http::*", "log"], "deny": ["http::delete"]}"#), 0);
+        assert!(eval(engine, "log()"));
+        assert!(eval(engine, "http::get()"));
+        assert!(!eval(engine, "http::delete()"));
+        assert!(!eval(engine, "db::query()"));
+
+        // Functions registered later follow the policy too
+        let name = CString::new("db::insert").unwrap();
+        assert_eq!(rhai_register_function(engine, name.as_ptr(), 102, answer_ok), 0);
+        assert!(!eval(engine, "db::insert()"));
+
+        assert_eq!(set_policy(engine, r#"{"deny": ["db::*"]}"#), 0);
+        assert!(eval(engine, "http::delete()"));
+        assert!(!eval(engine, "db::query()"));
+
+        assert_eq!(rhai_set_function_policy(engine, std::ptr::null()), 0);
+        assert!(eval(engine, "db::insert()"));
+
+        rhai_engine_free(engine);
+    }
+}
@@ -4,25 +4,32 @@
 //! and managing the callback invocation bridge.
 
 use crate::types::CRhaiEngine;
+use crate::signature::{FunctionSignature, MAX_REGISTERED_VARIADIC_ARGS};
+use crate::function_policy::FunctionPolicy;
 use crate::error::{clear_last_error, set_last_error};
-use crate::{catch_panic};
-use rhai::{Dynamic, Engine};
+use crate::{catch_panic, catch_panic_ptr};
+use rhai::{Dynamic, Engine, FuncRegistration, Module, NativeCallContext, Position, RhaiFunc, Shared};
+use rhai::debugger::{BreakPoint, Debugger};
+use rhai::{ASTNode, Expr, Stmt};
+use std::any::TypeId;
 use std::ffi::{CString, CStr, c_char};
 use std::sync::{Arc, Mutex};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::atomic::{AtomicI64, Ordering};
-use tokio::sync::oneshot;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// Type for the Dart callback function pointer.
 ///
 /// This matches the NativeCallable signature on the Dart side:
 /// `Pointer<Utf8> Function(Int64 callbackId, Pointer<Utf8> argsJson)`
-type DartCallback = extern "C" fn(i64, *const c_char) -> *mut c_char;
+pub(crate) type DartCallback = extern "C" fn(i64, *const c_char) -> *mut c_char;
 
 /// Stores information about a registered Dart callback.
 #[derive(Clone)]
-struct CallbackInfo {
+pub(crate) struct CallbackInfo {
+    /// The ID of the engine the callback is registered with
+    engine_id: i64,
+
     /// The unique ID for this callback
     callback_id: i64,
 
@@ -32,56 +39,130 @@ struct CallbackInfo {
     /// Async callback timeout in seconds
     async_timeout_seconds: u64,
 
-    /// The name of the registered function
+    /// The name of the registered function, including its namespace
     function_name: String,
+
+    /// The namespace of the function, if it was registered as `namespace::name`
+    namespace: Option<String>,
+
+    /// The function's typed signature, if it was registered with one
+    signature: Option<Arc<FunctionSignature>>,
+}
+
+impl CallbackInfo {
+    /// Gets the name of the function within its namespace.
+    fn local_name(&self) -> &str {
+        match &self.namespace {
+            Some(namespace) => &self.function_name[namespace.len() + 2..],
+            None => &self.function_name,
+        }
+    }
 }
 
-/// Response structure for async callback invocations.
+/// Status envelope of a Dart function call response.
+///
+/// This struct represents the envelope form of the async call protocol (see
+/// `async_eval`), which can be:
+/// - "success": The call completed with a result
+/// - "pending": The call returned a Future and will complete later (requires future_id)
+/// - "error": The call failed with an error message
 ///
-/// This struct represents the response from a Dart callback, which can be:
-/// - "success": The operation completed synchronously with a result
-/// - "pending": The operation is async and will complete later (requires future_id)
-/// - "error": The operation failed with an error message
+/// Other keys are ignored, so embedders can add their own to the envelope.
 #[derive(Debug, Deserialize)]
 struct CallbackResponse {
-    /// Status of the callback: "success", "pending", or "error"
+    /// Status of the call: "success", "pending", or "error"
     status: String,
 
-    /// Future ID for pending async operations (only present when status is "pending")
+    /// Future ID for pending calls (only present when status is "pending")
     #[serde(default)]
     future_id: Option<i64>,
 
-    /// Result value for successful operations (JSON string or value)
+    /// Result value for successful calls
     #[serde(default)]
     value: Option<serde_json::Value>,
 
-    /// Alternative value field as JSON string (for compatibility)
+    /// Alternative value field as JSON string (for values plain JSON can't hold)
     #[serde(default)]
     value_json: Option<String>,
 
-    /// Error message for failed operations
+    /// Error message for failed calls
     #[serde(default)]
     error: Option<String>,
 }
 
+/// The outcome of a Dart function call, as reported by Dart.
+#[derive(Debug, PartialEq)]
+pub(crate) enum CallOutcome {
+    /// The call returned a JSON-encoded value
+    Value(String),
+
+    /// The call returned a Future, to be completed with `rhai_complete_future`
+    Pending(i64),
+
+    /// The call failed with an error message
+    Error(String),
+}
+
+/// Parses a status envelope answering a Dart function call.
+///
+/// Envelopes are the explicit form of the async call protocol (see
+/// `async_eval`): Dart callbacks return them, and Dart passes them to
+/// `rhai_provide_function_envelope` and `rhai_complete_future`.
+///
+/// # Returns
+///
+/// The outcome of the call, or an error message if the envelope is malformed
+pub(crate) fn parse_call_envelope(envelope_json: &str) -> Result<CallOutcome, String> {
+    let response: CallbackResponse = serde_json::from_str(envelope_json)
+        .map_err(|e| format!("Failed to parse callback response: {}", e))?;
+
+    match response.status.as_str() {
+        "success" => {
+            let value_json = match (response.value, response.value_json) {
+                (Some(value), _) => value.to_string(),
+                (None, Some(value_json)) => value_json,
+                (None, None) => "null".to_string(),
+            };
+            Ok(CallOutcome::Value(value_json))
+        }
+        "pending" => {
+            let future_id = response.future_id
+                .ok_or("Pending response missing future_id")?;
+            Ok(CallOutcome::Pending(future_id))
+        }
+        "error" => {
+            let error_msg = response.error
+                .unwrap_or_else(|| "Unknown error from Dart callback".to_string());
+            Ok(CallOutcome::Error(error_msg))
+        }
+        other => Err(format!("Unknown callback response status: {}", other)),
+    }
+}
+
+/// Reads a plain answer to a Dart function call.
+///
+/// Plain answers are passed to `rhai_provide_function_result` and are taken as
+/// the call's value unchanged, whatever keys they have. The one exception is an
+/// object whose only key is an `error` string, which is how Dart reports a
+/// failed call.
+pub(crate) fn plain_call_answer(answer_json: String) -> CallOutcome {
+    if let Ok(serde_json::Value::Object(map)) = serde_json::from_str::<serde_json::Value>(&answer_json) {
+        if let (1, Some(serde_json::Value::String(error_msg))) = (map.len(), map.get("error")) {
+            return CallOutcome::Error(error_msg.clone());
+        }
+    }
+    CallOutcome::Value(answer_json)
+}
+
 lazy_static::lazy_static! {
-    /// Global registry of callback information.
+    /// Registry of callback information.
     ///
-    /// This maps function names to their callback information.
+    /// This maps (engine ID, function name) to the callback information, so
+    /// engines that register the same function name keep separate callbacks.
+    /// Entries are removed when their engine is freed.
     /// We use Arc<Mutex<>> for thread-safe access since Rhai engine might be used
     /// from multiple threads.
-    static ref CALLBACK_REGISTRY: Arc<Mutex<HashMap<String, CallbackInfo>>> =
-        Arc::new(Mutex::new(HashMap::new()));
-
-    /// Global registry of pending async futures.
-    ///
-    /// This maps future IDs to oneshot senders that will be used to complete
-    /// async operations. When Dart completes an async operation, it calls
-    /// `rhai_complete_future` which sends the result through the channel.
-    ///
-    /// We use Arc<Mutex<>> for thread-safe access since async operations may
-    /// complete on different threads.
-    static ref PENDING_FUTURES: Arc<Mutex<HashMap<i64, oneshot::Sender<String>>>> =
+    static ref CALLBACK_REGISTRY: Arc<Mutex<HashMap<(i64, String), CallbackInfo>>> =
         Arc::new(Mutex::new(HashMap::new()));
 
     /// Global Tokio runtime for async operations.
@@ -99,11 +180,17 @@ lazy_static::lazy_static! {
     };
 }
 
+/// The Dart functions an engine was built with, by name.
+///
+/// Shared with the engine's `defer()` function and variadic dispatch hook, so
+/// evals that keep using an older build of the engine also see its functions.
+pub(crate) type EngineFunctions = Arc<Mutex<HashMap<String, CallbackInfo>>>;
+
 /// Thread-local flag to track if async functions were invoked during eval.
 ///
 /// This is used by sync `eval()` to detect when async Dart functions are called,
 /// allowing it to error immediately with a helpful message to use `evalAsync()` instead.
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 thread_local! {
     static ASYNC_FUNCTION_INVOKED: Cell<bool> = Cell::new(false);
 }
@@ -145,10 +232,81 @@ pub fn set_async_eval_mode(enabled: bool) {
 }
 
 /// Checks if we're currently in async eval mode.
-fn is_async_eval_mode() -> bool {
+pub(crate) fn is_async_eval_mode() -> bool {
     IN_ASYNC_EVAL.with(|flag| flag.get())
 }
 
+/// A Dart callback invoked by an engine hook rather than by a script function call.
+#[derive(Clone, Copy)]
+pub(crate) struct HookCallback {
+    /// The ID of the engine the hook belongs to
+    pub(crate) engine_id: i64,
+
+    /// The ID passed back to the callback
+    pub(crate) callback_id: i64,
+
+    /// The function pointer to call back into Dart for sync evals
+    pub(crate) callback_ptr: DartCallback,
+
+    /// How long async evals wait for Dart to answer, in seconds
+    pub(crate) async_timeout_seconds: u64,
+}
+
+impl HookCallback {
+    /// Invokes the hook callback and returns its JSON response.
+    ///
+    /// Sync evals call the Dart callback directly on the current thread. Async
+    /// evals post a request named `request_name` to the function request queue
+    /// and wait for Dart to answer it.
+    pub(crate) fn call(&self, request_name: &str, args_json: String) -> Result<String, String> {
+        if is_async_eval_mode() {
+            use crate::async_eval::request_dart_function_execution;
+
+            return TOKIO_RUNTIME.block_on(async {
+                request_dart_function_execution(
+                    self.engine_id,
+                    self.callback_id,
+                    request_name.to_string(),
+                    args_json,
+                    self.async_timeout_seconds,
+                ).await
+            });
+        }
+
+        call_dart_callback(self.callback_ptr, self.callback_id, args_json)
+    }
+}
+
+/// Calls a Dart callback directly on the current thread and returns its raw
+/// JSON response.
+///
+/// The response string is allocated by Dart with malloc and freed here.
+fn call_dart_callback(
+    callback_ptr: DartCallback,
+    callback_id: i64,
+    args_json: String,
+) -> Result<String, String> {
+    let args_c_string = CString::new(args_json)
+        .map_err(|e| format!("Failed to create C string: {}", e))?;
+
+    let result_ptr = callback_ptr(callback_id, args_c_string.as_ptr());
+    if result_ptr.is_null() {
+        return Err("Dart callback returned null".to_string());
+    }
+
+    let result = unsafe { CStr::from_ptr(result_ptr) }
+        .to_str()
+        .map(|s| s.to_string())
+        .map_err(|e| format!("Invalid UTF-8 in callback result: {}", e));
+
+    // Free the result string
+    unsafe {
+        libc::free(result_ptr as *mut libc::c_void);
+    }
+
+    result
+}
+
 /// Atomic counter for generating unique future IDs.
 ///
 /// This counter is incremented atomically for each new async operation
@@ -164,218 +322,625 @@ pub fn generate_future_id() -> i64 {
     NEXT_FUTURE_ID.fetch_add(1, Ordering::SeqCst)
 }
 
-/// Invokes a Dart callback asynchronously, handling both sync and async responses.
+/// Completes a pending async future with a result from Dart.
 ///
-/// This function handles three types of responses:
-/// - "success": Returns the value immediately (sync path)
-/// - "pending": Creates a oneshot channel, stores it in the registry, and awaits the result
-/// - "error": Returns the error immediately
+/// This FFI function is called by Dart when the Future of a function call that
+/// was answered with a pending status completes. It is kept for compatibility
+/// with the callback bridge: the call waits in the same registry as function
+/// requests, and `result_json` is a success or error status envelope (see the
+/// async call protocol in `async_eval`).
 ///
-/// The timeout is configurable per-engine and is passed via the async_timeout_seconds parameter.
-/// If the timeout is exceeded, the pending future is removed from the registry and an error is returned.
+/// # Safety
+///
+/// This function is safe to call from FFI when:
+/// - `future_id` is a valid future ID from a pending async operation
+/// - `result_json` is a valid null-terminated C string
 ///
 /// # Arguments
 ///
-/// * `callback_id` - The unique ID for this callback
+/// * `future_id` - The unique ID of the future to complete
+/// * `result_json` - JSON string containing the result (C string)
+///
+/// # Returns
+///
+/// 0 on success, -1 if future ID not found or on error
+#[no_mangle]
+pub extern "C" fn rhai_complete_future(
+    future_id: i64,
+    result_json: *const c_char,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        // Validate pointer
+        if result_json.is_null() {
+            set_last_error("Result JSON pointer is null");
+            return -1;
+        }
+
+        // Convert C string to Rust string
+        let result_str = unsafe {
+            match CStr::from_ptr(result_json).to_str() {
+                Ok(s) => s.to_string(),
+                Err(e) => {
+                    set_last_error(&format!("Invalid UTF-8 in result JSON: {}", e));
+                    return -1;
+                }
+            }
+        };
+
+        match crate::async_eval::complete_dart_future(future_id, &result_str) {
+            Ok(()) => 0, // Success
+            Err(e) => {
+                set_last_error(&e);
+                -1
+            }
+        }
+    }}
+}
+
+/// Registers a Dart function with the Rhai engine.
+///
+/// This function stores the callback information and registers a Rhai function
+/// that will invoke the Dart callback when called from scripts. Async calls to
+/// the function time out after the engine's `async_timeout_seconds`; see
+/// `rhai_register_function_with_timeout` to override it.
+///
+/// Registering a name again replaces its function: the previous callback and
+/// all of its overloads are removed, so scripts only reach the new callback.
+/// Use `rhai_unregister_function` to remove a function.
+///
+/// A name of the form `namespace::name`, such as `http::get`, adds the function
+/// to a static module of that namespace, so scripts call it as `http::get(...)`.
+/// Namespaced variadic functions take at most 16 arguments.
+///
+/// Functions can be registered while async evals are running. Those evals
+/// keep calling the functions they started with; later evals see the new one.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI when:
+/// - `engine` is a valid pointer created by `rhai_engine_new`
+/// - `name` is a valid null-terminated C string
+/// - `callback_ptr` is a valid function pointer matching the DartCallback signature
+/// - `callback_id` is a unique identifier for this callback
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `name` - Name of the function to register (C string)
+/// * `callback_id` - Unique ID for this callback
 /// * `callback_ptr` - Function pointer to the Dart callback
-/// * `args_json` - JSON string of arguments to pass to the callback
-/// * `async_timeout_seconds` - Timeout in seconds for async operations
 ///
 /// # Returns
 ///
-/// Result containing the JSON string response or an error
-async fn invoke_dart_callback_async(
+/// 0 on success, -1 on error (check last error)
+#[no_mangle]
+pub extern "C" fn rhai_register_function(
+    engine: *mut CRhaiEngine,
+    name: *const c_char,
     callback_id: i64,
     callback_ptr: DartCallback,
-    args_json: String,
-    async_timeout_seconds: u64,
-) -> Result<String, String> {
-    // Convert to C string
-    let args_c_string = CString::new(args_json)
-        .map_err(|e| format!("Failed to create C string: {}", e))?;
+) -> i32 {
+    rhai_register_function_with_timeout(engine, name, callback_id, callback_ptr, 0)
+}
 
-    // Call the Dart callback
-    let result_ptr = callback_ptr(callback_id, args_c_string.as_ptr());
+/// Registers a Dart function with its own async timeout.
+///
+/// Works like `rhai_register_function`, but async calls to this function time
+/// out after `timeout_seconds` instead of the engine's `async_timeout_seconds`.
+///
+/// # Safety
+///
+/// Same requirements as `rhai_register_function`.
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `name` - Name of the function to register (C string)
+/// * `callback_id` - Unique ID for this callback
+/// * `callback_ptr` - Function pointer to the Dart callback
+/// * `timeout_seconds` - Async timeout for this function, or 0 to use the engine's
+///
+/// # Returns
+///
+/// 0 on success, -1 on error (check last error)
+#[no_mangle]
+pub extern "C" fn rhai_register_function_with_timeout(
+    engine: *mut CRhaiEngine,
+    name: *const c_char,
+    callback_id: i64,
+    callback_ptr: DartCallback,
+    timeout_seconds: u64,
+) -> i32 {
+    rhai_register_function_with_signature(
+        engine,
+        name,
+        callback_id,
+        callback_ptr,
+        timeout_seconds,
+        std::ptr::null(),
+    )
+}
 
-    // Check if result is null
-    if result_ptr.is_null() {
-        return Err("Dart callback returned null".into());
-    }
+/// Registers a Dart function with a typed signature.
+///
+/// Works like `rhai_register_function_with_timeout`. With a signature (see
+/// `signature` for its JSON format), only the arities it allows are registered,
+/// and arguments and results are type-checked in Rust, failing the call at its
+/// position. Without one, the function takes 0-10 arguments of any type.
+///
+/// # Safety
+///
+/// Same requirements as `rhai_register_function`. `signature_json` must be
+/// null or a valid null-terminated C string.
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `name` - Name of the function to register (C string)
+/// * `callback_id` - Unique ID for this callback
+/// * `callback_ptr` - Function pointer to the Dart callback
+/// * `timeout_seconds` - Async timeout for this function, or 0 to use the engine's
+/// * `signature_json` - JSON signature of the function, or null for an untyped function
+///
+/// # Returns
+///
+/// 0 on success, -1 on error (check last error)
+#[no_mangle]
+pub extern "C" fn rhai_register_function_with_signature(
+    engine: *mut CRhaiEngine,
+    name: *const c_char,
+    callback_id: i64,
+    callback_ptr: DartCallback,
+    timeout_seconds: u64,
+    signature_json: *const c_char,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        let signature = match parse_signature_arg(signature_json) {
+            Ok(signature) => signature,
+            Err(e) => {
+                set_last_error(&e);
+                return -1;
+            }
+        };
 
-    // Convert result to Rust string
-    let result_json = unsafe {
-        match CStr::from_ptr(result_ptr).to_str() {
+        register_dart_function(engine, name, callback_id, callback_ptr, timeout_seconds, signature)
+    }}
+}
+
+/// Unregisters a Dart function.
+///
+/// Removes the function's callback from the registry and all of its overloads
+/// from the engine, so scripts calling it fail with "function not found".
+/// Async evals already running keep the engine they started with, and can
+/// still call the function until they finish.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI when:
+/// - `engine` is a valid pointer created by `rhai_engine_new`
+/// - `name` is a valid null-terminated C string
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `name` - Name of the function to unregister (C string)
+///
+/// # Returns
+///
+/// 0 on success, -1 on error (check last error), including when no function
+/// of that name is registered
+#[no_mangle]
+pub extern "C" fn rhai_unregister_function(engine: *mut CRhaiEngine, name: *const c_char) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        if engine.is_null() {
+            set_last_error("Engine pointer is null");
+            return -1;
+        }
+
+        if name.is_null() {
+            set_last_error("Function name pointer is null");
+            return -1;
+        }
+
+        let engine_wrapper = unsafe { &mut *engine };
+        let func_name = match unsafe { CStr::from_ptr(name) }.to_str() {
             Ok(s) => s.to_string(),
             Err(e) => {
-                // Free the string before returning error
-                let _ = CString::from_raw(result_ptr);
-                return Err(format!("Invalid UTF-8 in callback result: {}", e).into());
+                set_last_error(&format!("Invalid UTF-8 in function name: {}", e));
+                return -1;
             }
+        };
+
+        let removed = CALLBACK_REGISTRY
+            .lock()
+            .unwrap()
+            .remove(&(engine_wrapper.engine_id(), func_name.clone()));
+        if removed.is_none() {
+            set_last_error(&format!("Function '{}' is not registered", func_name));
+            return -1;
         }
-    };
 
-    // Free the result string (Dart allocated it)
-    unsafe {
-        let _ = CString::from_raw(result_ptr);
-    }
+        engine_wrapper.rebuild_engine();
+        0 // Success
+    }}
+}
 
-    // Parse the callback response
-    let response: CallbackResponse = serde_json::from_str(&result_json)
-        .map_err(|e| format!("Failed to parse callback response: {}", e))?;
+/// Unregisters every Dart function of a namespace.
+///
+/// Works like `rhai_unregister_function` for each function registered as
+/// `namespace::name`, and removes the namespace's module from the engine.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI when:
+/// - `engine` is a valid pointer created by `rhai_engine_new`
+/// - `namespace` is a valid null-terminated C string
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `namespace` - Namespace to unregister, e.g. `http` (C string)
+///
+/// # Returns
+///
+/// The number of functions unregistered, or -1 on error (check last error)
+#[no_mangle]
+pub extern "C" fn rhai_unregister_namespace(engine: *mut CRhaiEngine, namespace: *const c_char) -> i64 {
+    catch_panic! {{
+        clear_last_error();
 
-    // Handle different response statuses
-    match response.status.as_str() {
-        "success" => {
-            // Synchronous success - return the value immediately
-            if let Some(value_json) = response.value_json {
-                Ok(value_json)
-            } else if let Some(value) = response.value {
-                Ok(value.to_string())
-            } else {
-                Ok("null".to_string())
+        if engine.is_null() {
+            set_last_error("Engine pointer is null");
+            return -1;
+        }
+
+        if namespace.is_null() {
+            set_last_error("Namespace pointer is null");
+            return -1;
+        }
+
+        let engine_wrapper = unsafe { &mut *engine };
+        let namespace = match unsafe { CStr::from_ptr(namespace) }.to_str() {
+            Ok(s) => s,
+            Err(e) => {
+                set_last_error(&format!("Invalid UTF-8 in namespace: {}", e));
+                return -1;
             }
+        };
+
+        let removed = {
+            let mut registry = CALLBACK_REGISTRY.lock().unwrap();
+            let before = registry.len();
+            registry.retain(|(id, _), info| *id != engine_wrapper.engine_id() || info.namespace.as_deref() != Some(namespace));
+            before - registry.len()
+        };
+
+        if removed > 0 {
+            engine_wrapper.rebuild_engine();
         }
-        "pending" => {
-            // Asynchronous operation - mark that async was invoked
-            // This allows sync eval() to detect and error on async function calls
-            mark_async_invoked();
+        removed as i64
+    }}
+}
 
-            // Asynchronous operation - wait for completion
-            let future_id = response.future_id
-                .ok_or("Pending response missing future_id")?;
+/// A registered Dart function, as listed by `rhai_list_functions`.
+#[derive(Serialize)]
+struct FunctionListing {
+    /// Full name of the function, including its namespace
+    name: String,
 
-            // Create a oneshot channel for this async operation
-            let (tx, mut rx) = oneshot::channel::<String>();
+    /// Namespace of the function, if any
+    namespace: Option<String>,
 
-            // Store the sender in the registry
-            {
-                let mut registry = PENDING_FUTURES.lock().unwrap();
-                registry.insert(future_id, tx);
-            }
+    /// ID of the function's callback
+    callback_id: i64,
 
-            // Wait for the result with the configured timeout
-            let timeout_duration = std::time::Duration::from_secs(async_timeout_seconds);
-            match tokio::time::timeout(timeout_duration, rx).await {
-                Ok(Ok(result)) => {
-                    // Successfully received result
-                    Ok(result)
-                }
-                Ok(Err(_)) => {
-                    // Channel was closed (sender dropped)
-                    // Clean up the registry
-                    let mut registry = PENDING_FUTURES.lock().unwrap();
-                    registry.remove(&future_id);
-                    Err("Async channel closed unexpectedly".into())
-                }
-                Err(_) => {
-                    // Timeout occurred
-                    // Clean up the registry
-                    let mut registry = PENDING_FUTURES.lock().unwrap();
-                    registry.remove(&future_id);
-                    Err(format!("Async operation timed out after {} seconds",
-                        timeout_duration.as_secs()).into())
+    /// Whether the function policy lets scripts call the function
+    allowed: bool,
+
+    /// Signature of the function in signature JSON, or null if untyped
+    signature: Option<serde_json::Value>,
+}
+
+/// Lists the Dart functions registered with an engine.
+///
+/// Returns a JSON array of `{"name", "namespace", "callback_id", "allowed",
+/// "signature"}` objects sorted by name. `signature` is the function's typed
+/// signature in the JSON format described in `signature`, with every type
+/// spelled out, or null for an untyped function.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI when:
+/// - `engine` is a valid pointer created by `rhai_engine_new`
+/// - `namespace` is null or a valid null-terminated C string
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `namespace` - Namespace to list, an empty string for functions without a
+///   namespace, or null for all functions
+///
+/// # Returns
+///
+/// A JSON string that must be freed with `rhai_free_error`, or null on error
+/// (check last error)
+#[no_mangle]
+pub extern "C" fn rhai_list_functions(engine: *const CRhaiEngine, namespace: *const c_char) -> *mut c_char {
+    catch_panic_ptr! {{
+        clear_last_error();
+
+        if engine.is_null() {
+            set_last_error("Engine pointer is null");
+            return std::ptr::null_mut();
+        }
+
+        let engine_wrapper = unsafe { &*engine };
+        let namespace = if namespace.is_null() {
+            None
+        } else {
+            match unsafe { CStr::from_ptr(namespace) }.to_str() {
+                Ok(s) => Some(s),
+                Err(e) => {
+                    set_last_error(&format!("Invalid UTF-8 in namespace: {}", e));
+                    return std::ptr::null_mut();
                 }
             }
+        };
+
+        let mut listings: Vec<FunctionListing> = {
+            let registry = CALLBACK_REGISTRY.lock().unwrap();
+            registry
+                .values()
+                .filter(|info| info.engine_id == engine_wrapper.engine_id())
+                .filter(|info| match namespace {
+                    None => true,
+                    Some("") => info.namespace.is_none(),
+                    Some(namespace) => info.namespace.as_deref() == Some(namespace),
+                })
+                .map(|info| FunctionListing {
+                    name: info.function_name.clone(),
+                    namespace: info.namespace.clone(),
+                    callback_id: info.callback_id,
+                    allowed: engine_wrapper.function_policy.allows(&info.function_name, info.namespace.as_deref()),
+                    signature: info.signature.as_ref().map(|signature| signature.to_json()),
+                })
+                .collect()
+        };
+        listings.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let json = match serde_json::to_string(&listings) {
+            Ok(json) => json,
+            Err(e) => {
+                set_last_error(&format!("Failed to serialize function list: {}", e));
+                return std::ptr::null_mut();
+            }
+        };
+        match CString::new(json) {
+            Ok(c_string) => c_string.into_raw(),
+            Err(e) => {
+                set_last_error(&format!("Failed to create C string: {}", e));
+                std::ptr::null_mut()
+            }
         }
-        "error" => {
-            // Error response
-            let error_msg = response.error
-                .unwrap_or_else(|| "Unknown error from Dart callback".to_string());
-            Err(error_msg.into())
+    }}
+}
+
+/// Parses a nullable JSON signature argument.
+fn parse_signature_arg(signature_json: *const c_char) -> Result<Option<FunctionSignature>, String> {
+    if signature_json.is_null() {
+        return Ok(None);
+    }
+    unsafe { CStr::from_ptr(signature_json) }
+        .to_str()
+        .map_err(|e| format!("Invalid UTF-8 in function signature: {}", e))
+        .and_then(FunctionSignature::parse)
+        .map(Some)
+}
+
+/// Stores a Dart callback in the registry and registers its Rhai function.
+///
+/// A callback already registered under the name is replaced, and the engine
+/// is rebuilt without its overloads.
+///
+/// # Returns
+///
+/// 0 on success, -1 on error (check last error)
+fn register_dart_function(
+    engine: *mut CRhaiEngine,
+    name: *const c_char,
+    callback_id: i64,
+    callback_ptr: DartCallback,
+    timeout_seconds: u64,
+    signature: Option<FunctionSignature>,
+) -> i32 {
+    // Validate pointers
+    if engine.is_null() {
+        set_last_error("Engine pointer is null");
+        return -1;
+    }
+
+    if name.is_null() {
+        set_last_error("Function name pointer is null");
+        return -1;
+    }
+
+    // Get the engine (mutable reference needed to register functions)
+    let engine_wrapper = unsafe { &mut *engine };
+
+    // Use the engine's async timeout unless the function overrides it
+    let async_timeout_seconds = if timeout_seconds == 0 {
+        engine_wrapper.async_timeout_seconds()
+    } else {
+        timeout_seconds
+    };
+
+    // Convert function name to Rust string
+    let func_name = unsafe {
+        match CStr::from_ptr(name).to_str() {
+            Ok(s) => s.to_string(),
+            Err(e) => {
+                set_last_error(&format!("Invalid UTF-8 in function name: {}", e));
+                return -1;
+            }
         }
-        _ => {
-            Err(format!("Invalid callback status: {}", response.status).into())
+    };
+
+    let namespace = match parse_namespace(&func_name) {
+        Ok(namespace) => namespace,
+        Err(e) => {
+            set_last_error(&e);
+            return -1;
         }
+    };
+
+    // Store callback info in registry
+    let callback_info = CallbackInfo {
+        engine_id: engine_wrapper.engine_id(),
+        callback_id,
+        callback_ptr,
+        async_timeout_seconds,
+        function_name: func_name.clone(),
+        namespace,
+        signature: signature.map(Arc::new),
+    };
+
+    let replaced = CALLBACK_REGISTRY.lock().unwrap()
+        .insert((callback_info.engine_id, func_name.clone()), callback_info.clone())
+        .is_some();
+
+    // Add the function to the engine in place if nothing else holds it.
+    // Otherwise an eval is running on it (sync evals hold it too, so this may
+    // be a Dart callback of that eval): the eval keeps its engine, and
+    // a rebuilt copy with the function is used from now on. A replaced
+    // callback's overloads can only be dropped by rebuilding too. Functions
+    // the function policy does not allow are left out.
+    let allowed = engine_wrapper.function_policy.allows(&func_name, callback_info.namespace.as_deref());
+    match Arc::get_mut(&mut engine_wrapper.inner) {
+        Some(_) if !replaced && !allowed => {}
+        Some(engine) if !replaced => {
+            let mut functions = engine_wrapper.functions.lock().unwrap();
+            functions.insert(func_name, callback_info.clone());
+            match &callback_info.namespace {
+                Some(namespace) => register_namespace(engine, namespace, &functions),
+                None => register_callback(engine, callback_info),
+            }
+        }
+        _ => engine_wrapper.rebuild_engine(),
+    }
+
+    0 // Success
+}
+
+/// Gets the namespace of a function name of the form `namespace::name`.
+///
+/// # Returns
+///
+/// The namespace, None for a plain name, or an error message if the name
+/// is malformed
+fn parse_namespace(name: &str) -> Result<Option<String>, String> {
+    let Some((namespace, local_name)) = name.split_once("::") else {
+        return Ok(None);
+    };
+    let is_identifier = |part: &str| {
+        part.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            && part.chars().all(|c| c.is_alphanumeric() || c == '_')
+    };
+    if local_name.contains("::") {
+        return Err(format!("Invalid function name '{}': nested namespaces are not supported", name));
+    }
+    if !is_identifier(namespace) || local_name.is_empty() {
+        return Err(format!("Invalid function name '{}': expected namespace::name", name));
     }
+    Ok(Some(namespace.to_string()))
 }
 
-/// Completes a pending async future with a result from Dart.
-///
-/// This FFI function is called by Dart when an async operation completes.
-/// It looks up the future ID in the PENDING_FUTURES registry, sends the
-/// result through the oneshot channel, and removes the entry from the registry.
-///
-/// # Safety
-///
-/// This function is safe to call from FFI when:
-/// - `future_id` is a valid future ID from a pending async operation
-/// - `result_json` is a valid null-terminated C string
-///
-/// # Arguments
+/// Registers the Rhai function of a Dart callback.
 ///
-/// * `future_id` - The unique ID of the future to complete
-/// * `result_json` - JSON string containing the result (C string)
-///
-/// # Returns
+/// Typed functions get the arities of their signature, untyped ones multiple
+/// overloads for different parameter counts (0-10).
+fn register_callback(engine: &mut Engine, info: CallbackInfo) {
+    let name = info.function_name.clone();
+    match info.signature.clone() {
+        Some(signature) => register_typed_function(engine, &name, info, &signature),
+        None => register_function_overloads(engine, &name, info),
+    }
+}
+
+/// Registers the static module of a namespace of Dart functions.
 ///
-/// 0 on success, -1 if future ID not found or on error
-#[no_mangle]
-pub extern "C" fn rhai_complete_future(
-    future_id: i64,
-    result_json: *const c_char,
-) -> i32 {
-    catch_panic! {{
-        clear_last_error();
+/// The module holds every function of the namespace in `functions`, and
+/// replaces the namespace's previous module, if any.
+fn register_namespace(engine: &mut Engine, namespace: &str, functions: &HashMap<String, CallbackInfo>) {
+    let mut module = Module::new();
+    for info in functions.values().filter(|info| info.namespace.as_deref() == Some(namespace)) {
+        let overloads = match &info.signature {
+            Some(signature) => typed_overloads(signature),
+            None => (0..=10).map(|arity| vec![TypeId::of::<Dynamic>(); arity]).collect(),
+        };
 
-        // Validate pointer
-        if result_json.is_null() {
-            set_last_error("Result JSON pointer is null");
-            return -1;
+        for arg_types in overloads {
+            let callback = info.clone();
+            let func = move |ctx: Option<NativeCallContext>, args: &mut [&mut Dynamic]| {
+                let position = ctx.map_or(Position::NONE, |ctx| ctx.call_position());
+                invoke_typed_dart_callback(&callback, position, args.iter().map(|arg| (**arg).clone()).collect())
+            };
+            FuncRegistration::new(info.local_name()).in_internal_namespace().set_into_module_raw(
+                &mut module,
+                arg_types,
+                RhaiFunc::Method { func: Shared::new(func), has_context: true, is_pure: true, is_volatile: true },
+            );
         }
+    }
+    engine.register_static_module(namespace, module.into());
+}
 
-        // Convert C string to Rust string
-        let result_str = unsafe {
-            match CStr::from_ptr(result_json).to_str() {
-                Ok(s) => s.to_string(),
-                Err(e) => {
-                    set_last_error(&format!("Invalid UTF-8 in result JSON: {}", e));
-                    return -1;
-                }
-            }
-        };
+/// Copies the Dart functions currently registered with an engine that the
+/// function policy allows.
+///
+/// Used when the engine is rebuilt; see `CRhaiEngine::rebuild_engine`.
+pub(crate) fn snapshot_engine_functions(engine_id: i64, policy: &FunctionPolicy) -> EngineFunctions {
+    let registry = CALLBACK_REGISTRY.lock().unwrap();
+    let functions = registry
+        .values()
+        .filter(|info| info.engine_id == engine_id)
+        .filter(|info| policy.allows(&info.function_name, info.namespace.as_deref()))
+        .map(|info| (info.function_name.clone(), info.clone()))
+        .collect();
+    Arc::new(Mutex::new(functions))
+}
 
-        // Look up and remove the sender from the registry
-        let sender = {
-            let mut registry = PENDING_FUTURES.lock().unwrap();
-            registry.remove(&future_id)
-        };
+/// Registers the Rhai functions of a set of Dart functions, and a static
+/// module for each of their namespaces.
+pub(crate) fn register_engine_functions(engine: &mut Engine, functions: &EngineFunctions) {
+    let functions = functions.lock().unwrap();
 
-        match sender {
-            Some(tx) => {
-                // Send the result through the channel
-                match tx.send(result_str) {
-                    Ok(_) => 0, // Success
-                    Err(_) => {
-                        // Receiver was dropped - this is unexpected but not fatal
-                        set_last_error(&format!(
-                            "Failed to send result for future {}: receiver dropped",
-                            future_id
-                        ));
-                        -1
-                    }
-                }
-            }
-            None => {
-                // Future ID not found
-                set_last_error(&format!("Future ID {} not found in registry", future_id));
-                -1
-            }
-        }
-    }}
+    let mut callbacks: Vec<&CallbackInfo> = functions.values().filter(|info| info.namespace.is_none()).collect();
+    callbacks.sort_by(|a, b| a.function_name.cmp(&b.function_name));
+    for info in callbacks {
+        register_callback(engine, info.clone());
+    }
+
+    let namespaces: BTreeSet<&str> = functions.values().filter_map(|info| info.namespace.as_deref()).collect();
+    for namespace in namespaces {
+        register_namespace(engine, namespace, &functions);
+    }
 }
 
-/// Registers a Dart function with the Rhai engine.
+/// Registers a variadic Dart function.
 ///
-/// This function stores the callback information and registers a Rhai function
-/// that will invoke the Dart callback when called from scripts.
+/// Works like `rhai_register_function_with_timeout`, but the function takes any
+/// number of arguments of any type and forwards them all to Dart. Use `rhai_register_function_with_signature` with a `rest`
+/// parameter for a typed variadic function.
 ///
 /// # Safety
 ///
-/// This function is safe to call from FFI when:
-/// - `engine` is a valid pointer created by `rhai_engine_new`
-/// - `name` is a valid null-terminated C string
-/// - `callback_ptr` is a valid function pointer matching the DartCallback signature
-/// - `callback_id` is a unique identifier for this callback
+/// Same requirements as `rhai_register_function`.
 ///
 /// # Arguments
 ///
@@ -383,71 +948,187 @@ pub extern "C" fn rhai_complete_future(
 /// * `name` - Name of the function to register (C string)
 /// * `callback_id` - Unique ID for this callback
 /// * `callback_ptr` - Function pointer to the Dart callback
+/// * `timeout_seconds` - Async timeout for this function, or 0 to use the engine's
 ///
 /// # Returns
 ///
 /// 0 on success, -1 on error (check last error)
 #[no_mangle]
-pub extern "C" fn rhai_register_function(
+pub extern "C" fn rhai_register_variadic_function(
     engine: *mut CRhaiEngine,
     name: *const c_char,
     callback_id: i64,
     callback_ptr: DartCallback,
+    timeout_seconds: u64,
 ) -> i32 {
     catch_panic! {{
         clear_last_error();
 
-        // Validate pointers
-        if engine.is_null() {
-            set_last_error("Engine pointer is null");
-            return -1;
+        register_dart_function(
+            engine,
+            name,
+            callback_id,
+            callback_ptr,
+            timeout_seconds,
+            Some(FunctionSignature::variadic()),
+        )
+    }}
+}
+
+thread_local! {
+    /// Long calls to variadic functions being evaluated, innermost last, by
+    /// function name and number of arguments.
+    static VARIADIC_CALL_POSITIONS: RefCell<Vec<(String, usize, Position)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Adds a debugger break point on each variadic Dart function of an engine.
+///
+/// Called when an eval starts. The missing function hook isn't told where a
+/// call is, so the break points record the position of long variadic calls
+/// before their arguments are evaluated (see `record_variadic_call`).
+pub(crate) fn add_variadic_break_points(functions: &EngineFunctions, debugger: &mut Debugger) {
+    VARIADIC_CALL_POSITIONS.with(|positions| positions.borrow_mut().clear());
+    let functions = functions.lock().unwrap();
+    let variadic = functions.values().filter(|info| {
+        info.namespace.is_none() && info.signature.as_ref().is_some_and(|signature| signature.is_variadic())
+    });
+    for info in variadic {
+        debugger.break_points_mut().push(BreakPoint::AtFunctionName {
+            name: info.function_name.as_str().into(),
+            enabled: true,
+        });
+    }
+}
+
+/// Records the position of a node if it is a long call to a variadic function.
+///
+/// Called by the debugger for every node it stops at, which includes the
+/// script's first node as well as the variadic break points.
+pub(crate) fn record_variadic_call(node: &ASTNode, position: Position) {
+    let call = match node {
+        ASTNode::Expr(Expr::FnCall(call, _)) | ASTNode::Stmt(Stmt::FnCall(call, _)) => call,
+        _ => return,
+    };
+    if call.args.len() > MAX_REGISTERED_VARIADIC_ARGS {
+        let entry = (call.name.to_string(), call.args.len(), position);
+        VARIADIC_CALL_POSITIONS.with(|positions| positions.borrow_mut().push(entry));
+    }
+}
+
+/// Installs the dispatch of long calls to variadic Dart functions.
+///
+/// Variadic functions are registered for up to `MAX_REGISTERED_VARIADIC_ARGS`
+/// arguments. Rhai reports longer calls as missing functions, and this hook
+/// forwards those to the engine's variadic function of that name, if any, at
+/// the position recorded by `record_variadic_call`.
+#[allow(deprecated)]
+pub(crate) fn install_variadic_dispatch(engine: &mut Engine, functions: EngineFunctions) {
+    engine.on_missing_function(move |name, args, is_method_call, _| {
+        if is_method_call || args.len() <= MAX_REGISTERED_VARIADIC_ARGS {
+            return Ok(None);
         }
 
-        if name.is_null() {
-            set_last_error("Function name pointer is null");
-            return -1;
+        let info = functions.lock().unwrap().get(name).cloned();
+        let Some(info) = info else {
+            return Ok(None);
+        };
+        let Some(signature) = info.signature.as_ref().filter(|signature| signature.is_variadic()) else {
+            return Ok(None);
+        };
+        let position = VARIADIC_CALL_POSITIONS.with(|positions| {
+            let mut positions = positions.borrow_mut();
+            positions
+                .iter()
+                .rposition(|(call_name, arg_count, _)| call_name == name && *arg_count == args.len())
+                .map_or(Position::NONE, |index| positions.remove(index).2)
+        });
+        if args.len() < signature.required_count() {
+            return Ok(None);
         }
 
-        // Get the engine (mutable reference needed to register functions)
-        let engine_wrapper = unsafe { &mut *engine };
+        let args = args.iter().map(|arg| (**arg).clone()).collect();
+        invoke_typed_dart_callback(&info, position, args).map(Some)
+    });
+}
 
-        // Get the async timeout from the engine
-        let async_timeout_seconds = engine_wrapper.async_timeout_seconds();
+/// Removes all callbacks registered with an engine.
+///
+/// Called when the engine is freed.
+pub(crate) fn unregister_engine_callbacks(engine_id: i64) {
+    let mut registry = CALLBACK_REGISTRY.lock().unwrap();
+    registry.retain(|(id, _), _| *id != engine_id);
+}
 
-        // Convert function name to Rust string
-        let func_name = unsafe {
-            match CStr::from_ptr(name).to_str() {
-                Ok(s) => s.to_string(),
-                Err(e) => {
-                    set_last_error(&format!("Invalid UTF-8 in function name: {}", e));
-                    return -1;
-                }
-            }
-        };
+/// Gets the sizes of the callback registries, as (name, size) pairs.
+pub(crate) fn callback_registry_sizes() -> Vec<(&'static str, usize)> {
+    vec![
+        ("registered_callbacks", CALLBACK_REGISTRY.lock().unwrap().len()),
+    ]
+}
 
-        // Store callback info in registry
-        let callback_info = CallbackInfo {
-            callback_id,
-            callback_ptr,
-            async_timeout_seconds,
-            function_name: func_name.clone(),
-        };
+/// Counts the callbacks registered with an engine.
+#[cfg(test)]
+pub(crate) fn registered_callback_count(engine_id: i64) -> usize {
+    let registry = CALLBACK_REGISTRY.lock().unwrap();
+    registry.keys().filter(|(id, _)| *id == engine_id).count()
+}
 
-        {
-            let mut registry = CALLBACK_REGISTRY.lock().unwrap();
-            registry.insert(func_name.clone(), callback_info.clone());
+/// Registers a Dart function for each arity its signature allows.
+///
+/// Each arity is registered with the signature's Rhai parameter types, so
+/// overload resolution sees them. If some of them are concrete, the arity is
+/// also registered with `Dynamic` parameters, so that calls with other types
+/// reach the type check and fail with a description of the mismatch instead of
+/// "function not found".
+fn register_typed_function(
+    engine: &mut Engine,
+    name: &str,
+    info: CallbackInfo,
+    signature: &FunctionSignature,
+) {
+    for arg_types in typed_overloads(signature) {
+        let info = info.clone();
+        engine.register_raw_fn(name, arg_types, move |ctx: NativeCallContext, args: &mut [&mut Dynamic]| {
+            invoke_typed_dart_callback(&info, ctx.call_position(), args.iter().map(|arg| (**arg).clone()).collect())
+        });
+    }
+}
+
+/// Gets the parameter types a typed Dart function is registered with, one
+/// list per overload; see `register_typed_function`.
+fn typed_overloads(signature: &FunctionSignature) -> Vec<Vec<TypeId>> {
+    let mut overloads = Vec::new();
+    for arity in signature.arities() {
+        let arg_types = signature.arg_type_ids(arity);
+        let any_types = vec![TypeId::of::<Dynamic>(); arity];
+
+        if arg_types != any_types {
+            overloads.push(arg_types);
         }
+        overloads.push(any_types);
+    }
+    overloads
+}
 
-        // Register the function with Rhai engine
-        // We register multiple overloads for different parameter counts (0-10)
-        register_function_overloads(
-            Arc::get_mut(&mut engine_wrapper.inner).unwrap(),
-            &func_name,
-            callback_info,
-        );
+/// Invokes a typed Dart callback, checking its arguments and result against
+/// its signature.
+fn invoke_typed_dart_callback(
+    info: &CallbackInfo,
+    position: Position,
+    args: Vec<Dynamic>,
+) -> Result<Dynamic, Box<rhai::EvalAltResult>> {
+    let mismatch = |e: String| Box::new(rhai::EvalAltResult::ErrorRuntime(e.into(), position));
 
-        0 // Success
-    }}
+    if let Some(signature) = &info.signature {
+        signature.check_args(&info.function_name, &args).map_err(mismatch)?;
+    }
+
+    let result = invoke_dart_callback_vec_async(info, position, args)?;
+
+    if let Some(signature) = &info.signature {
+        signature.check_result(&info.function_name, &result).map_err(mismatch)?;
+    }
+    Ok(result)
 }
 
 /// Registers function overloads for different parameter counts.
@@ -461,88 +1142,88 @@ fn register_function_overloads(engine: &mut Engine, name: &str, info: CallbackIn
     // Register 0-parameter version
     {
         let info = info.clone();
-        engine.register_fn(name, move || {
-            invoke_dart_callback_vec_async(&info, vec![])
+        engine.register_fn(name, move |ctx: NativeCallContext| {
+            invoke_dart_callback_vec_async(&info, ctx.call_position(), vec![])
         });
     }
 
     // Register 1-parameter version
     {
         let info = info.clone();
-        engine.register_fn(name, move |a1: Dynamic| {
-            invoke_dart_callback_vec_async(&info, vec![a1])
+        engine.register_fn(name, move |ctx: NativeCallContext, a1: Dynamic| {
+            invoke_dart_callback_vec_async(&info, ctx.call_position(), vec![a1])
         });
     }
 
     // Register 2-parameter version
     {
         let info = info.clone();
-        engine.register_fn(name, move |a1: Dynamic, a2: Dynamic| {
-            invoke_dart_callback_vec_async(&info, vec![a1, a2])
+        engine.register_fn(name, move |ctx: NativeCallContext, a1: Dynamic, a2: Dynamic| {
+            invoke_dart_callback_vec_async(&info, ctx.call_position(), vec![a1, a2])
         });
     }
 
     // Register 3-parameter version
     {
         let info = info.clone();
-        engine.register_fn(name, move |a1: Dynamic, a2: Dynamic, a3: Dynamic| {
-            invoke_dart_callback_vec_async(&info, vec![a1, a2, a3])
+        engine.register_fn(name, move |ctx: NativeCallContext, a1: Dynamic, a2: Dynamic, a3: Dynamic| {
+            invoke_dart_callback_vec_async(&info, ctx.call_position(), vec![a1, a2, a3])
         });
     }
 
     // Register 4-parameter version
     {
         let info = info.clone();
-        engine.register_fn(name, move |a1: Dynamic, a2: Dynamic, a3: Dynamic, a4: Dynamic| {
-            invoke_dart_callback_vec_async(&info, vec![a1, a2, a3, a4])
+        engine.register_fn(name, move |ctx: NativeCallContext, a1: Dynamic, a2: Dynamic, a3: Dynamic, a4: Dynamic| {
+            invoke_dart_callback_vec_async(&info, ctx.call_position(), vec![a1, a2, a3, a4])
         });
     }
 
     // Register 5-parameter version
     {
         let info = info.clone();
-        engine.register_fn(name, move |a1: Dynamic, a2: Dynamic, a3: Dynamic, a4: Dynamic, a5: Dynamic| {
-            invoke_dart_callback_vec_async(&info, vec![a1, a2, a3, a4, a5])
+        engine.register_fn(name, move |ctx: NativeCallContext, a1: Dynamic, a2: Dynamic, a3: Dynamic, a4: Dynamic, a5: Dynamic| {
+            invoke_dart_callback_vec_async(&info, ctx.call_position(), vec![a1, a2, a3, a4, a5])
         });
     }
 
     // Register 6-parameter version
     {
         let info = info.clone();
-        engine.register_fn(name, move |a1: Dynamic, a2: Dynamic, a3: Dynamic, a4: Dynamic, a5: Dynamic, a6: Dynamic| {
-            invoke_dart_callback_vec_async(&info, vec![a1, a2, a3, a4, a5, a6])
+        engine.register_fn(name, move |ctx: NativeCallContext, a1: Dynamic, a2: Dynamic, a3: Dynamic, a4: Dynamic, a5: Dynamic, a6: Dynamic| {
+            invoke_dart_callback_vec_async(&info, ctx.call_position(), vec![a1, a2, a3, a4, a5, a6])
         });
     }
 
     // Register 7-parameter version
     {
         let info = info.clone();
-        engine.register_fn(name, move |a1: Dynamic, a2: Dynamic, a3: Dynamic, a4: Dynamic, a5: Dynamic, a6: Dynamic, a7: Dynamic| {
-            invoke_dart_callback_vec_async(&info, vec![a1, a2, a3, a4, a5, a6, a7])
+        engine.register_fn(name, move |ctx: NativeCallContext, a1: Dynamic, a2: Dynamic, a3: Dynamic, a4: Dynamic, a5: Dynamic, a6: Dynamic, a7: Dynamic| {
+            invoke_dart_callback_vec_async(&info, ctx.call_position(), vec![a1, a2, a3, a4, a5, a6, a7])
         });
     }
 
     // Register 8-parameter version
     {
         let info = info.clone();
-        engine.register_fn(name, move |a1: Dynamic, a2: Dynamic, a3: Dynamic, a4: Dynamic, a5: Dynamic, a6: Dynamic, a7: Dynamic, a8: Dynamic| {
-            invoke_dart_callback_vec_async(&info, vec![a1, a2, a3, a4, a5, a6, a7, a8])
+        engine.register_fn(name, move |ctx: NativeCallContext, a1: Dynamic, a2: Dynamic, a3: Dynamic, a4: Dynamic, a5: Dynamic, a6: Dynamic, a7: Dynamic, a8: Dynamic| {
+            invoke_dart_callback_vec_async(&info, ctx.call_position(), vec![a1, a2, a3, a4, a5, a6, a7, a8])
         });
     }
 
     // Register 9-parameter version
     {
         let info = info.clone();
-        engine.register_fn(name, move |a1: Dynamic, a2: Dynamic, a3: Dynamic, a4: Dynamic, a5: Dynamic, a6: Dynamic, a7: Dynamic, a8: Dynamic, a9: Dynamic| {
-            invoke_dart_callback_vec_async(&info, vec![a1, a2, a3, a4, a5, a6, a7, a8, a9])
+        engine.register_fn(name, move |ctx: NativeCallContext, a1: Dynamic, a2: Dynamic, a3: Dynamic, a4: Dynamic, a5: Dynamic, a6: Dynamic, a7: Dynamic, a8: Dynamic, a9: Dynamic| {
+            invoke_dart_callback_vec_async(&info, ctx.call_position(), vec![a1, a2, a3, a4, a5, a6, a7, a8, a9])
         });
     }
 
     // Register 10-parameter version
     {
         let info = info.clone();
-        engine.register_fn(name, move |a1: Dynamic, a2: Dynamic, a3: Dynamic, a4: Dynamic, a5: Dynamic, a6: Dynamic, a7: Dynamic, a8: Dynamic, a9: Dynamic, a10: Dynamic| {
-            invoke_dart_callback_vec_async(&info, vec![a1, a2, a3, a4, a5, a6, a7, a8, a9, a10])
+        engine.register_fn(name, move |ctx: NativeCallContext, a1: Dynamic, a2: Dynamic, a3: Dynamic, a4: Dynamic, a5: Dynamic, a6: Dynamic, a7: Dynamic, a8: Dynamic, a9: Dynamic, a10: Dynamic| {
+            invoke_dart_callback_vec_async(&info, ctx.call_position(), vec![a1, a2, a3, a4, a5, a6, a7, a8, a9, a10])
         });
     }
 }
@@ -556,80 +1237,30 @@ fn invoke_dart_callback_sync(
     callback_info: &CallbackInfo,
     args_json: String,
 ) -> Result<Dynamic, Box<rhai::EvalAltResult>> {
-    use serde_json;
-
-    // Convert to C string
-    let args_c_string = match CString::new(args_json) {
-        Ok(s) => s,
-        Err(e) => {
-            return Err(format!("Failed to create C string: {}", e).into());
-        }
-    };
-
     // Call the Dart callback directly (synchronous FFI call on same thread)
-    let result_ptr = (callback_info.callback_ptr)(
+    let result_json = call_dart_callback(
+        callback_info.callback_ptr,
         callback_info.callback_id,
-        args_c_string.as_ptr(),
-    );
-
-    // Check if result is null
-    if result_ptr.is_null() {
-        return Err("Dart callback returned null".into());
-    }
-
-    // Convert result to Rust string
-    let result_json = unsafe {
-        match CStr::from_ptr(result_ptr as *const c_char).to_str() {
-            Ok(s) => s.to_string(),
-            Err(e) => {
-                return Err(format!("Invalid UTF-8 in callback result: {}", e).into());
-            }
-        }
-    };
-
-    // Free the result string
-    unsafe {
-        libc::free(result_ptr as *mut libc::c_void);
-    }
-
-    // Parse the callback response
-    let response: CallbackResponse = match serde_json::from_str(&result_json) {
-        Ok(r) => r,
-        Err(e) => {
-            return Err(format!("Failed to parse callback response: {}", e).into());
-        }
-    };
-
-    // Handle response based on status
-    match response.status.as_str() {
-        "success" => {
-            // Get the result value
-            let value_json = if let Some(value) = response.value {
-                serde_json::to_string(&value).unwrap_or_else(|_| "null".to_string())
-            } else if let Some(value_json) = response.value_json {
-                value_json
-            } else {
-                "null".to_string()
-            };
+        args_json,
+    )?;
 
+    // Handle response based on its outcome
+    match parse_call_envelope(&result_json)? {
+        CallOutcome::Value(value_json) => {
             // Convert to Rhai Dynamic
             match crate::values::json_to_rhai_dynamic(&value_json) {
                 Ok(dynamic) => Ok(dynamic),
                 Err(e) => Err(format!("Failed to convert result to Rhai: {}", e).into()),
             }
         }
-        "pending" => {
+        CallOutcome::Pending(_) => {
             // Async function detected - set flag so eval() can error
             mark_async_invoked();
             Err("Async function called in sync eval - this error should be caught by eval()".into())
         }
-        "error" => {
-            let error_msg = response.error.unwrap_or_else(|| "Unknown error".to_string());
+        CallOutcome::Error(error_msg) => {
             Err(format!("Callback error: {}", error_msg).into())
         }
-        _ => {
-            Err(format!("Unknown callback status: {}", response.status).into())
-        }
     }
 }
 
@@ -639,9 +1270,11 @@ fn invoke_dart_callback_sync(
 /// event loop to make progress while waiting for async operations to complete.
 ///
 /// When in async eval mode (evalAsync), this uses the request/response pattern
-/// to avoid isolate callback issues from background threads.
+/// to avoid isolate callback issues from background threads, and records the
+/// position of the call for the eval's status.
 fn invoke_dart_callback_vec_async(
     callback_info: &CallbackInfo,
+    position: Position,
     args: Vec<Dynamic>,
 ) -> Result<Dynamic, Box<rhai::EvalAltResult>> {
     // Convert args to JSON array
@@ -655,35 +1288,23 @@ fn invoke_dart_callback_vec_async(
     // Check if we're in async eval mode
     if is_async_eval_mode() {
         // Use request/response pattern for async eval
-        use crate::async_eval::request_dart_function_execution;
+        use crate::async_eval::{record_eval_position, request_dart_function_execution};
 
+        record_eval_position(position);
         let function_name = callback_info.function_name.clone();
 
         // Use block_on to wait for the async function execution
         let result = TOKIO_RUNTIME.block_on(async {
-            request_dart_function_execution(function_name, args_json).await
+            request_dart_function_execution(
+                callback_info.engine_id,
+                callback_info.callback_id,
+                function_name,
+                args_json,
+                callback_info.async_timeout_seconds,
+            ).await
         });
 
-        match result {
-            Ok(json) => {
-                // Check if the JSON contains an error field
-                if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&json) {
-                    if let Some(error_msg) = parsed.get("error").and_then(|v| v.as_str()) {
-                        return Err(format!("Function error: {}", error_msg).into());
-                    }
-                }
-
-                // Parse the JSON result and convert to Rhai Dynamic
-                match crate::values::json_to_rhai_dynamic(&json) {
-                    Ok(dynamic) => Ok(dynamic),
-                    Err(e) => Err(format!("Failed to convert result to Rhai: {}", e).into()),
-                }
-            }
-            Err(e) => {
-                // Propagate error to Rhai
-                Err(format!("Function error: {}", e).into())
-            }
-        }
+        async_result_to_dynamic(result).map_err(|e| e.into())
     } else {
         // Sync eval mode - invoke callback directly on same thread
         // This avoids crossing thread boundaries which would cause isolate errors
@@ -691,100 +1312,163 @@ fn invoke_dart_callback_vec_async(
     }
 }
 
-/// Invokes a Dart callback with arguments as a Vec<Dynamic>.
+/// Converts the outcome of an async eval function request to a Rhai value.
 ///
-/// This is a legacy sync-only helper function kept for backward compatibility.
-/// New code should use invoke_dart_callback_vec_async instead.
-#[allow(dead_code)]
-fn invoke_dart_callback_vec(
-    callback_info: &CallbackInfo,
-    args: Vec<Dynamic>,
-) -> Result<Dynamic, Box<rhai::EvalAltResult>> {
-    // Convert args to JSON array
-    let args_json = match convert_args_to_json(&args) {
-        Ok(json) => json,
-        Err(e) => {
-            return Err(format!("Failed to convert args to JSON: {}", e).into());
-        }
-    };
+/// # Returns
+///
+/// The function's result, or an error message
+fn async_result_to_dynamic(result: Result<String, String>) -> Result<Dynamic, String> {
+    // Propagate errors to Rhai
+    let value_json = result.map_err(|e| format!("Function error: {}", e))?;
 
-    // Convert to C string
-    let args_c_string = match CString::new(args_json) {
-        Ok(s) => s,
-        Err(e) => {
-            return Err(format!("Failed to create C string: {}", e).into());
-        }
-    };
+    crate::values::json_to_rhai_dynamic(&value_json)
+        .map_err(|e| format!("Failed to convert result to Rhai: {}", e))
+}
 
-    // Call the Dart callback
-    let result_ptr = (callback_info.callback_ptr)(
-        callback_info.callback_id,
-        args_c_string.as_ptr(),
-    );
+/// A Dart function call created by `defer()`, to be run by `await_all()`.
+#[derive(Clone)]
+pub(crate) struct DeferredCall {
+    /// The callback of the function to call
+    info: CallbackInfo,
 
-    // Check if result is null
-    if result_ptr.is_null() {
-        return Err("Dart callback returned null".into());
-    }
+    /// The call arguments
+    args: Vec<Dynamic>,
+}
 
-    // Convert result to Rust string
-    let result_json = unsafe {
-        match CStr::from_ptr(result_ptr).to_str() {
-            Ok(s) => s.to_string(),
-            Err(e) => {
-                // Free the string before returning error
-                let _ = CString::from_raw(result_ptr);
-                return Err(format!("Invalid UTF-8 in callback result: {}", e).into());
+impl DeferredCall {
+    /// Checks the call's arguments against the function's signature, if any.
+    fn check_args(&self) -> Result<(), String> {
+        match &self.info.signature {
+            Some(signature) => {
+                signature.check_arg_count(&self.info.function_name, self.args.len())?;
+                signature.check_args(&self.info.function_name, &self.args)
             }
+            None => Ok(()),
         }
-    };
+    }
 
-    // Free the result string (Dart allocated it)
-    unsafe {
-        let _ = CString::from_raw(result_ptr);
+    /// Checks the call's result against the function's signature, if any.
+    fn check_result(&self, result: &Dynamic) -> Result<(), String> {
+        match &self.info.signature {
+            Some(signature) => signature.check_result(&self.info.function_name, result),
+            None => Ok(()),
+        }
     }
+}
 
-    // Parse JSON result
-    let result_value: serde_json::Value = match serde_json::from_str(&result_json) {
-        Ok(v) => v,
-        Err(e) => {
-            return Err(format!("Failed to parse callback result JSON: {}", e).into());
+/// Registers the `defer()` and `await_all()` functions on an engine.
+///
+/// `defer("name", [args])` creates a call to a registered Dart function without
+/// running it. `await_all([calls])` runs a list of deferred calls and returns an
+/// array with one map per call: `#{ ok: true, value: <result> }` or
+/// `#{ ok: false, error: "<message>" }`. In async evals all the calls are queued
+/// together, so Dart runs them concurrently; in sync evals they run in order.
+///
+/// Calls to typed functions are checked against their signatures like direct
+/// calls: mismatching arguments fail `await_all()` before any call runs, and a
+/// mismatching result fails it once the calls are done, at its position.
+pub(crate) fn install_fan_out_functions(engine: &mut Engine, functions: EngineFunctions) {
+    engine.register_type_with_name::<DeferredCall>("DeferredCall");
+
+    let defer = move |name: &str, args: rhai::Array| -> Result<DeferredCall, Box<rhai::EvalAltResult>> {
+        match functions.lock().unwrap().get(name) {
+            Some(info) => Ok(DeferredCall { info: info.clone(), args }),
+            None => Err(format!("Function '{}' is not registered", name).into()),
         }
     };
+    let defer_without_args = defer.clone();
+    engine.register_fn("defer", move |name: &str| defer_without_args(name, rhai::Array::new()));
+    engine.register_fn("defer", defer);
+
+    engine.register_fn("await_all", |ctx: NativeCallContext, calls: rhai::Array| -> Result<rhai::Array, Box<rhai::EvalAltResult>> {
+        let position = ctx.call_position();
+        let mismatch = |e: String| Box::new(rhai::EvalAltResult::ErrorRuntime(e.into(), position));
+        crate::async_eval::record_eval_position(position);
+
+        let calls = calls
+            .into_iter()
+            .map(|call| {
+                let type_name = call.type_name();
+                call.try_cast::<DeferredCall>()
+                    .ok_or_else(|| format!("await_all() expects deferred calls, got {}", type_name))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for call in &calls {
+            call.check_args().map_err(mismatch)?;
+        }
 
-    // Check if it's a success or error result
-    if let Some(success) = result_value.get("success").and_then(|v| v.as_bool()) {
-        if success {
-            // Try new format first (value_json as a JSON string)
-            if let Some(value_json) = result_value.get("value_json").and_then(|v| v.as_str()) {
-                // Parse the JSON string and convert to Rhai Dynamic
-                match crate::values::json_to_rhai_dynamic(value_json) {
-                    Ok(dynamic) => Ok(dynamic),
-                    Err(e) => Err(format!("Failed to convert result to Rhai: {}", e).into()),
-                }
-            }
-            // Fall back to old format (value as an object)
-            else if let Some(value) = result_value.get("value") {
-                match crate::values::json_to_rhai_dynamic(&value.to_string()) {
-                    Ok(dynamic) => Ok(dynamic),
-                    Err(e) => Err(format!("Failed to convert result to Rhai: {}", e).into()),
+        run_deferred_calls(&calls)
+            .into_iter()
+            .zip(&calls)
+            .map(|(outcome, call)| {
+                let mut entry = rhai::Map::new();
+                match outcome {
+                    Ok(value) => {
+                        call.check_result(&value).map_err(mismatch)?;
+                        entry.insert("ok".into(), true.into());
+                        entry.insert("value".into(), value);
+                    }
+                    Err(error) => {
+                        entry.insert("ok".into(), false.into());
+                        entry.insert("error".into(), error.into());
+                    }
                 }
-            }
-            // No value field - return unit
-            else {
-                Ok(Dynamic::UNIT)
-            }
-        } else {
-            // Error case
-            let error_msg = result_value
-                .get("error")
-                .and_then(|v| v.as_str())
-                .unwrap_or("Unknown error from Dart callback");
-            Err(error_msg.into())
-        }
-    } else {
-        Err("Invalid callback result format".into())
+                Ok(entry.into())
+            })
+            .collect()
+    });
+}
+
+/// Runs deferred calls, concurrently in async eval mode.
+///
+/// # Returns
+///
+/// The result or error message of each call, in order
+fn run_deferred_calls(calls: &[DeferredCall]) -> Vec<Result<Dynamic, String>> {
+    let args_json: Vec<Result<String, String>> = calls
+        .iter()
+        .map(|call| convert_args_to_json(&call.args)
+            .map_err(|e| format!("Failed to convert args to JSON: {}", e)))
+        .collect();
+
+    if !is_async_eval_mode() {
+        return calls
+            .iter()
+            .zip(args_json)
+            .map(|(call, args_json)| {
+                args_json.and_then(|args_json| {
+                    invoke_dart_callback_sync(&call.info, args_json).map_err(|e| e.to_string())
+                })
+            })
+            .collect();
     }
+
+    // Queue every call whose arguments converted, then wait for all of them
+    use crate::async_eval::{request_dart_function_executions, DartCall};
+
+    let requests: Vec<DartCall> = calls
+        .iter()
+        .zip(&args_json)
+        .filter_map(|(call, args_json)| args_json.as_ref().ok().map(|args_json| DartCall {
+            engine_id: call.info.engine_id,
+            callback_id: call.info.callback_id,
+            function_name: call.info.function_name.clone(),
+            args_json: args_json.clone(),
+            timeout_seconds: call.info.async_timeout_seconds,
+        }))
+        .collect();
+    let mut results = TOKIO_RUNTIME
+        .block_on(request_dart_function_executions(requests))
+        .into_iter();
+
+    args_json
+        .into_iter()
+        .map(|args_json| match args_json {
+            Ok(_) => async_result_to_dynamic(results.next().expect("one result per queued call")),
+            Err(e) => Err(e),
+        })
+        .collect()
 }
 
 /// Converts Rhai Dynamic arguments to a JSON array string.
@@ -851,63 +1535,42 @@ mod tests {
         assert_eq!(json, "[]");
     }
 
-    /// Test that timeout cleans up registry entries
-    #[tokio::test]
-    async fn test_timeout_cleanup() {
-        // Create a mock callback that returns pending status
-        extern "C" fn mock_callback(_id: i64, _args: *const c_char) -> *mut c_char {
-            let response = r#"{"status":"pending","future_id":12345}"#;
-            CString::new(response).unwrap().into_raw()
-        }
-
-        // Invoke with very short timeout
-        let result = invoke_dart_callback_async(
-            1,
-            mock_callback,
-            "[]".to_string(),
-            1, // 1 second timeout
-        ).await;
-
-        // Should timeout
-        assert!(result.is_err());
-        let err_msg = result.unwrap_err().to_string();
-        assert!(err_msg.contains("timed out") || err_msg.contains("timeout"));
-
-        // Verify the registry was cleaned up
-        let registry = PENDING_FUTURES.lock().unwrap();
-        assert!(!registry.contains_key(&12345));
-    }
-
-
-    /// Test that completing a future removes it from registry
+    /// Test that both forms of call answers are understood
     #[test]
-    fn test_future_registry_cleanup_on_completion() {
-        // Manually add a future to the registry
-        let (tx, mut rx) = oneshot::channel::<String>();
-        let future_id = 88888;
-
-        {
-            let mut registry = PENDING_FUTURES.lock().unwrap();
-            registry.insert(future_id, tx);
-            assert!(registry.contains_key(&future_id));
+    fn test_parse_call_answer_forms() {
+        assert_eq!(
+            parse_call_envelope(r#"{"status":"success","value":{"a":1}}"#).unwrap(),
+            CallOutcome::Value(r#"{"a":1}"#.to_string()),
+        );
+        assert_eq!(
+            parse_call_envelope(r#"{"status":"success","value_json":"[1,2]"}"#).unwrap(),
+            CallOutcome::Value("[1,2]".to_string()),
+        );
+        assert_eq!(
+            parse_call_envelope(r#"{"status":"pending","future_id":7}"#).unwrap(),
+            CallOutcome::Pending(7),
+        );
+        assert_eq!(
+            parse_call_envelope(r#"{"status":"error","error":"boom"}"#).unwrap(),
+            CallOutcome::Error("boom".to_string()),
+        );
+        assert_eq!(
+            parse_call_envelope(r#"{"status":"success","value":1,"trace_id":"abc"}"#).unwrap(),
+            CallOutcome::Value("1".to_string()),
+        );
+        assert!(parse_call_envelope(r#"{"status":"pending"}"#).is_err());
+        assert!(parse_call_envelope(r#"{"status":"ok","count":2}"#).is_err());
+        assert!(parse_call_envelope("42").is_err());
+
+        // Plain answers are values, including maps that look like envelopes
+        assert_eq!(plain_call_answer("42".to_string()), CallOutcome::Value("42".to_string()));
+        for map in [r#"{"status":"error","error":"not found"}"#, r#"{"status":"pending"}"#] {
+            assert_eq!(plain_call_answer(map.to_string()), CallOutcome::Value(map.to_string()));
         }
-
-        // Complete the future
-        let result_json = CString::new("\"test_result\"").unwrap();
-        let ret = rhai_complete_future(future_id, result_json.as_ptr());
-
-        // Should succeed
-        assert_eq!(ret, 0);
-
-        // Verify it was removed from registry
-        let registry = PENDING_FUTURES.lock().unwrap();
-        assert!(!registry.contains_key(&future_id));
-        
-        // Also verify the result was received
-        drop(registry);
-        let result = rx.try_recv();
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "\"test_result\"");
+        assert_eq!(
+            plain_call_answer(r#"{"error":"boom"}"#.to_string()),
+            CallOutcome::Error("boom".to_string()),
+        );
     }
 
     /// Test that completing a nonexistent future returns error
@@ -932,12 +1595,158 @@ mod tests {
         }
         
         let info = CallbackInfo {
+            engine_id: 1,
             callback_id: 123,
             callback_ptr: dummy_callback,
             async_timeout_seconds: 60,
             function_name: "test_function".to_string(),
+            namespace: None,
+            signature: None,
         };
         
         assert_eq!(info.async_timeout_seconds, 60);
     }
+
+    extern "C" fn answer_old(_: i64, _: *const c_char) -> *mut c_char {
+        unsafe { libc::strdup(cr#"{"status":"success","value":"old"}"#.as_ptr()) }
+    }
+
+    extern "C" fn answer_new(_: i64, _: *const c_char) -> *mut c_char {
+        unsafe { libc::strdup(cr#"{"status":"success","value":"new"}"#.as_ptr()) }
+    }
+
+    fn eval(engine: *const CRhaiEngine, script: &str) -> Result<String, String> {
+        use crate::error::{rhai_free_error, rhai_get_last_error};
+
+        let script = CString::new(script).unwrap();
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        if crate::engine::rhai_eval(engine, script.as_ptr(), &mut result_ptr) == 0 {
+            return Ok(unsafe { CString::from_raw(result_ptr).into_string().unwrap() });
+        }
+        let error_ptr = rhai_get_last_error();
+        let error = unsafe { CStr::from_ptr(error_ptr) }.to_str().unwrap().to_string();
+        rhai_free_error(error_ptr);
+        Err(error)
+    }
+
+    /// Test that replaced and unregistered callbacks can no longer be reached
+    #[test]
+    fn test_replace_and_unregister_function() {
+        let engine = crate::engine::rhai_engine_new(std::ptr::null());
+        let engine_id = unsafe { &*engine }.engine_id();
+        let name = CString::new("plugin").unwrap();
+        let other = CString::new("other").unwrap();
+        assert_eq!(rhai_register_function(engine, name.as_ptr(), 81, answer_old), 0);
+        assert_eq!(rhai_register_function(engine, other.as_ptr(), 82, answer_old), 0);
+        assert_eq!(eval(engine, "plugin(1, 2)").unwrap(), "\"old\"");
+
+        // A second plain registration replaces the first
+        assert_eq!(rhai_register_function(engine, name.as_ptr(), 83, answer_new), 0);
+        assert_eq!(eval(engine, "plugin()").unwrap(), "\"new\"");
+        assert_eq!(eval(engine, "plugin(1, 2)").unwrap(), "\"new\"");
+        assert_eq!(registered_callback_count(engine_id), 2);
+
+        // Replacing with a typed signature drops the old untyped overloads
+        let signature = CString::new(r#"{"params": [{"name": "x", "type": "int"}]}"#).unwrap();
+        assert_eq!(rhai_register_function_with_signature(engine, name.as_ptr(), 84, answer_new, 0, signature.as_ptr()), 0);
+        assert_eq!(eval(engine, "plugin(1)").unwrap(), "\"new\"");
+        assert!(eval(engine, "plugin(1, 2)").unwrap_err().contains("not found"));
+        assert_eq!(eval(engine, "other()").unwrap(), "\"old\"");
+        assert_eq!(registered_callback_count(engine_id), 2);
+
+        assert_eq!(rhai_unregister_function(engine, name.as_ptr()), 0);
+        assert!(eval(engine, "plugin(1)").unwrap_err().contains("not found"));
+        assert!(eval(engine, r#"defer("plugin", [1])"#).unwrap_err().contains("not registered"));
+        assert_eq!(registered_callback_count(engine_id), 1);
+        assert_eq!(rhai_unregister_function(engine, name.as_ptr()), -1);
+
+        crate::engine::rhai_engine_free(engine);
+    }
+
+    fn list_functions(engine: *const CRhaiEngine, namespace: Option<&str>) -> serde_json::Value {
+        let namespace = namespace.map(|namespace| CString::new(namespace).unwrap());
+        let json_ptr = rhai_list_functions(engine, namespace.as_ref().map_or(std::ptr::null(), |n| n.as_ptr()));
+        let json = unsafe { CString::from_raw(json_ptr) }.into_string().unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    static MUTATING_ENGINE: std::sync::atomic::AtomicPtr<CRhaiEngine> =
+        std::sync::atomic::AtomicPtr::new(std::ptr::null_mut());
+
+    /// Registers, replaces and unregisters functions while the eval calling it runs.
+    extern "C" fn mutate_functions(_: i64, _: *const c_char) -> *mut c_char {
+        let engine = MUTATING_ENGINE.load(Ordering::SeqCst);
+        let added = CString::new("added").unwrap();
+        let mutate = CString::new("mutate").unwrap();
+        assert_eq!(rhai_register_function(engine, added.as_ptr(), 87, answer_old), 0);
+        assert_eq!(rhai_register_function(engine, added.as_ptr(), 88, answer_new), 0);
+        // Fails on a second call in the same eval, which still reaches this callback
+        rhai_unregister_function(engine, mutate.as_ptr());
+        unsafe { libc::strdup(cr#"{"status":"success","value":"mutated"}"#.as_ptr()) }
+    }
+
+    /// Test that a sync eval keeps its engine while its callbacks change the functions
+    #[test]
+    fn test_register_during_sync_eval() {
+        let engine = crate::engine::rhai_engine_new(std::ptr::null());
+        MUTATING_ENGINE.store(engine, Ordering::SeqCst);
+        let name = CString::new("mutate").unwrap();
+        assert_eq!(rhai_register_function(engine, name.as_ptr(), 86, mutate_functions), 0);
+
+        // The running eval keeps the functions it started with
+        assert!(eval(engine, "mutate(); added()").unwrap_err().contains("added"));
+        assert_eq!(rhai_register_function(engine, name.as_ptr(), 86, mutate_functions), 0);
+        assert_eq!(eval(engine, "mutate() + mutate()").unwrap(), "\"mutatedmutated\"");
+
+        // Later evals see the changes
+        assert_eq!(eval(engine, "added()").unwrap(), "\"new\"");
+        assert!(eval(engine, "mutate()").unwrap_err().contains("not found"));
+
+        crate::engine::rhai_engine_free(engine);
+    }
+
+    /// Test that namespaced functions are reachable, listed and removed per namespace
+    #[test]
+    fn test_namespaced_functions() {
+        let engine = crate::engine::rhai_engine_new(std::ptr::null());
+        let register = |name: &str, signature: Option<&str>| {
+            let name = CString::new(name).unwrap();
+            let signature = signature.map(|signature| CString::new(signature).unwrap());
+            let signature_ptr = signature.as_ref().map_or(std::ptr::null(), |s| s.as_ptr());
+            rhai_register_function_with_signature(engine, name.as_ptr(), 85, answer_new, 0, signature_ptr)
+        };
+        assert_eq!(register("get", None), 0);
+        assert_eq!(register("http::get", Some(r#"{"params": [{"name": "url", "type": "string"}]}"#)), 0);
+        assert_eq!(register("http::post", None), 0);
+        assert_eq!(register("db::query", None), 0);
+        assert_eq!(register("a::b::c", None), -1);
+        assert_eq!(register("http::", None), -1);
+
+        assert_eq!(eval(engine, r#"http::get("/") + get()"#).unwrap(), "\"newnew\"");
+        let err = eval(engine, "http::get(1)").unwrap_err();
+        assert!(err.contains("Function 'http::get' parameter 1 (url): expected string, got int"), "{}", err);
+        assert!(eval(engine, "post()").unwrap_err().contains("not found"));
+
+        let listed = list_functions(engine, Some("http"));
+        assert_eq!(listed.as_array().unwrap().len(), 2);
+        assert_eq!(listed[0]["name"], "http::get");
+        assert_eq!(listed[0]["namespace"], "http");
+        assert_eq!(listed[0]["signature"]["params"][0]["type"], "string");
+        assert_eq!(listed[1]["signature"], serde_json::Value::Null);
+        assert_eq!(list_functions(engine, Some(""))[0]["name"], "get");
+        assert_eq!(list_functions(engine, None).as_array().unwrap().len(), 4);
+
+        let name = CString::new("http::get").unwrap();
+        assert_eq!(rhai_unregister_function(engine, name.as_ptr()), 0);
+        assert!(eval(engine, r#"http::get("/")"#).is_err());
+        assert_eq!(eval(engine, "http::post()").unwrap(), "\"new\"");
+
+        let namespace = CString::new("http").unwrap();
+        assert_eq!(rhai_unregister_namespace(engine, namespace.as_ptr()), 1);
+        assert!(eval(engine, "http::post()").is_err());
+        assert_eq!(eval(engine, "db::query() + get()").unwrap(), "\"newnew\"");
+        assert_eq!(list_functions(engine, None).as_array().unwrap().len(), 2);
+
+        crate::engine::rhai_engine_free(engine);
+    }
 }
@@ -33,6 +33,18 @@
 //! - `engine`: Engine lifecycle management
 //! - `values`: Type conversion between Rhai and Dart
 //! - `functions`: Function registration and callback management
+//! - `signature`: Typed signatures for registered Dart functions
+//! - `debugger`: Debugger hooks that follow scripts statement by statement
+//! - `function_policy`: Rules for the Dart functions scripts may call
+//! - `async_eval`: Background script evaluation with Dart request/response
+//! - `scope`: Named variable scopes (execution contexts) shared across one engine
+//! - `type_locks`: Type locks that keep scope variables from changing type
+//! - `watch`: Change notifications for watched scope variables
+//! - `resolver`: Lazy variable resolution through a Dart resolver callback
+//! - `definitions`: Policy for the variables scripts may define
+//! - `notify`: Push notifications for queued function requests and completed evals
+//! - `eval_pool`: Bounded worker pool for async evals
+//! - `retention`: Expiry of abandoned async eval state and registry diagnostics
 
 // Re-export macros at crate root for easier use
 #[macro_use]
@@ -43,7 +55,18 @@ pub mod types;
 pub mod engine;
 pub mod values;
 pub mod functions;
+pub mod signature;
+pub mod debugger;
+pub mod function_policy;
 pub mod async_eval;
+pub mod scope;
+pub mod type_locks;
+pub mod watch;
+pub mod resolver;
+pub mod definitions;
+pub mod notify;
+pub mod eval_pool;
+pub mod retention;
 
 #[cfg(test)]
 mod tests {
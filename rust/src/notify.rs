@@ -0,0 +1,373 @@
+//! Push notifications for queued function requests and completed evals
+//!
+//! Instead of polling `rhai_get_pending_function_request` and
+//! `rhai_eval_async_poll` in a loop, Dart can register a listener that is told
+//! when there is something to fetch:
+//!
+//! - A native port, posted to with `Dart_PostCObject`. Call
+//!   `rhai_init_dart_api_dl` with `NativeApi.initializeApiDLData` first, then
+//!   `rhai_set_notify_port` with the port of a `ReceivePort`.
+//! - A notify callback, for embedders without the Dart API DL. It is invoked on
+//!   the background thread that queued the request or finished the eval, so it
+//!   must be safe to call from any thread (e.g. a `NativeCallable.listener`).
+//!
+//! Each notification carries an event kind and an ID: `[kind, id]` as a list of
+//! two ints on the port, or the two callback arguments. The ID is the request's
+//! exec ID for `NOTIFY_FUNCTION_REQUEST`, and the eval ID for `NOTIFY_EVAL_COMPLETED`
+//! and `NOTIFY_EVAL_EVENT`.
+//! Notifications only signal that there is something to fetch; Dart still
+//! retrieves the request or result through the usual FFI functions.
+
+use crate::error::clear_last_error;
+use crate::catch_panic;
+use std::ffi::{c_char, c_void, CStr};
+use std::sync::Mutex;
+
+/// Event kind: a function request was queued. The ID is its exec ID.
+pub const NOTIFY_FUNCTION_REQUEST: i32 = 1;
+
+/// Event kind: an async eval completed. The ID is its eval ID.
+pub const NOTIFY_EVAL_COMPLETED: i32 = 2;
+
+/// Event kind: an async eval emitted a value. The ID is its eval ID.
+pub const NOTIFY_EVAL_EVENT: i32 = 3;
+
+/// Type for the notify callback: `Void Function(Int32 kind, Int64 id)`.
+pub(crate) type NotifyCallback = extern "C" fn(i32, i64);
+
+/// Type of `Dart_PostCObject` from the Dart API DL.
+type PostCObjectFn = extern "C" fn(i64, *mut DartCObject) -> bool;
+
+/// Major version of the Dart API DL this module was written against.
+const DART_API_DL_MAJOR_VERSION: i32 = 2;
+
+/// `Dart_CObject_kInt64`
+const DART_COBJECT_INT64: i32 = 3;
+
+/// `Dart_CObject_kArray`
+const DART_COBJECT_ARRAY: i32 = 6;
+
+/// The value of a `Dart_CObject`, limited to the members this module posts.
+///
+/// Padded to the size of the largest member of the C union.
+#[repr(C)]
+#[derive(Clone, Copy)]
+union DartCObjectValue {
+    as_int64: i64,
+    as_array: DartCObjectArray,
+    _padding: [u64; 5],
+}
+
+/// The `as_array` member of a `Dart_CObject` value.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DartCObjectArray {
+    length: isize,
+    values: *mut *mut DartCObject,
+}
+
+/// A `Dart_CObject`, as posted with `Dart_PostCObject`.
+#[repr(C)]
+struct DartCObject {
+    kind: i32,
+    value: DartCObjectValue,
+}
+
+/// An entry of the Dart API DL function table.
+#[repr(C)]
+struct DartApiEntry {
+    name: *const c_char,
+    function: *const c_void,
+}
+
+/// The data passed by `NativeApi.initializeApiDLData`.
+#[repr(C)]
+struct DartApi {
+    major: i32,
+    minor: i32,
+    functions: *const DartApiEntry,
+}
+
+/// The registered listener.
+#[derive(Default)]
+struct Notifier {
+    /// `Dart_PostCObject`, once the Dart API DL is initialized
+    post_c_object: Option<PostCObjectFn>,
+
+    /// The native port to post notifications to
+    port: Option<i64>,
+
+    /// The notify callback, used when no port is set
+    callback: Option<NotifyCallback>,
+}
+
+lazy_static::lazy_static! {
+    /// The process-wide notification listener.
+    static ref NOTIFIER: Mutex<Notifier> = Mutex::new(Notifier::default());
+}
+
+/// Tells the registered listener about an event.
+///
+/// Does nothing if no listener is registered. Failures to post are ignored:
+/// Dart can still poll.
+pub(crate) fn notify(kind: i32, id: i64) {
+    let (post_c_object, port, callback) = {
+        let notifier = NOTIFIER.lock().unwrap();
+        (notifier.post_c_object, notifier.port, notifier.callback)
+    };
+
+    match (post_c_object, port, callback) {
+        (Some(post_c_object), Some(port), _) => post_event(post_c_object, port, kind, id),
+        (_, None, Some(callback)) => callback(kind, id),
+        _ => {}
+    }
+}
+
+/// Posts an event to a native port as a list of two ints.
+fn post_event(post_c_object: PostCObjectFn, port: i64, kind: i32, id: i64) {
+    let mut kind_object = DartCObject {
+        kind: DART_COBJECT_INT64,
+        value: DartCObjectValue { as_int64: kind as i64 },
+    };
+    let mut id_object = DartCObject {
+        kind: DART_COBJECT_INT64,
+        value: DartCObjectValue { as_int64: id },
+    };
+    let mut values = [&mut kind_object as *mut DartCObject, &mut id_object as *mut DartCObject];
+    let mut message = DartCObject {
+        kind: DART_COBJECT_ARRAY,
+        value: DartCObjectValue {
+            as_array: DartCObjectArray {
+                length: values.len() as isize,
+                values: values.as_mut_ptr(),
+            },
+        },
+    };
+
+    // The message is copied by Dart before this returns
+    post_c_object(port, &mut message);
+}
+
+/// Initializes the Dart API DL functions used for port notifications.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. `data` must be the pointer returned
+/// by `NativeApi.initializeApiDLData`.
+///
+/// # Returns
+///
+/// 0 on success, -1 on error
+///
+/// # Arguments
+///
+/// * `data` - The Dart API DL data
+#[no_mangle]
+pub extern "C" fn rhai_init_dart_api_dl(data: *mut c_void) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        if data.is_null() {
+            set_last_error("Dart API data pointer is null");
+            return -1;
+        }
+
+        let api = unsafe { &*(data as *const DartApi) };
+        if api.major != DART_API_DL_MAJOR_VERSION {
+            set_last_error(&format!(
+                "Unsupported Dart API DL version {}.{} (expected major version {})",
+                api.major, api.minor, DART_API_DL_MAJOR_VERSION
+            ));
+            return -1;
+        }
+
+        // Find Dart_PostCObject in the null-terminated function table
+        let mut entry = api.functions;
+        let mut post_c_object = None;
+        while !entry.is_null() && !unsafe { &*entry }.name.is_null() {
+            let current = unsafe { &*entry };
+            if unsafe { CStr::from_ptr(current.name) }.to_bytes() == b"Dart_PostCObject" {
+                post_c_object = Some(unsafe {
+                    std::mem::transmute::<*const c_void, PostCObjectFn>(current.function)
+                });
+                break;
+            }
+            entry = unsafe { entry.add(1) };
+        }
+
+        match post_c_object {
+            Some(post_c_object) => {
+                NOTIFIER.lock().unwrap().post_c_object = Some(post_c_object);
+                0 // Success
+            }
+            None => {
+                set_last_error("Dart_PostCObject not found in the Dart API DL");
+                -1
+            }
+        }
+    }}
+}
+
+/// Sets or removes the native port notifications are posted to.
+///
+/// Requires `rhai_init_dart_api_dl`. While a port is set, the notify callback
+/// is not used.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI.
+///
+/// # Returns
+///
+/// 0 on success, -1 on error
+///
+/// # Arguments
+///
+/// * `port` - The native port of a `ReceivePort`, or 0 to remove it
+#[no_mangle]
+pub extern "C" fn rhai_set_notify_port(port: i64) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        let mut notifier = NOTIFIER.lock().unwrap();
+        if port != 0 && notifier.post_c_object.is_none() {
+            set_last_error("Dart API DL is not initialized; call rhai_init_dart_api_dl first");
+            return -1;
+        }
+
+        notifier.port = if port == 0 { None } else { Some(port) };
+        0 // Success
+    }}
+}
+
+/// Sets or removes the notify callback.
+///
+/// The callback is invoked from background threads, so it must be safe to call
+/// from any thread. It is not used while a native port is set.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. `callback` must be null or stay
+/// valid while it is set.
+///
+/// # Returns
+///
+/// 0 on success, -1 on error
+///
+/// # Arguments
+///
+/// * `callback` - Function pointer to the notify callback, or null to remove it
+#[no_mangle]
+pub extern "C" fn rhai_set_notify_callback(callback: Option<NotifyCallback>) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        NOTIFIER.lock().unwrap().callback = callback;
+        0 // Success
+    }}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::MutexGuard;
+
+    lazy_static::lazy_static! {
+        static ref POSTED: Mutex<Vec<(i64, Vec<i64>)>> = Mutex::new(Vec::new());
+        static ref CALLED: Mutex<Vec<(i32, i64)>> = Mutex::new(Vec::new());
+
+        /// Serializes the tests that change the process-wide listener
+        static ref LISTENER_LOCK: Mutex<()> = Mutex::new(());
+    }
+
+    extern "C" fn fake_post_c_object(port: i64, message: *mut DartCObject) -> bool {
+        let message = unsafe { &*message };
+        assert_eq!(message.kind, DART_COBJECT_ARRAY);
+        let array = unsafe { message.value.as_array };
+        let values = (0..array.length)
+            .map(|i| {
+                let value = unsafe { &**array.values.offset(i) };
+                assert_eq!(value.kind, DART_COBJECT_INT64);
+                unsafe { value.value.as_int64 }
+            })
+            .collect();
+        POSTED.lock().unwrap().push((port, values));
+        true
+    }
+
+    extern "C" fn record_event(kind: i32, id: i64) {
+        CALLED.lock().unwrap().push((kind, id));
+    }
+
+    fn init_fake_api() -> MutexGuard<'static, ()> {
+        let guard = LISTENER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let name = c"Dart_PostCObject";
+        let functions = [
+            DartApiEntry { name: c"Dart_PostInteger".as_ptr(), function: std::ptr::null() },
+            DartApiEntry { name: name.as_ptr(), function: fake_post_c_object as *const c_void },
+            DartApiEntry { name: std::ptr::null(), function: std::ptr::null() },
+        ];
+        let mut api = DartApi { major: 2, minor: 3, functions: functions.as_ptr() };
+        assert_eq!(rhai_init_dart_api_dl(&mut api as *mut DartApi as *mut c_void), 0);
+        guard
+    }
+
+    #[test]
+    fn test_port_notifications() {
+        let _guard = init_fake_api();
+        assert_eq!(rhai_set_notify_port(9001), 0);
+        assert_eq!(rhai_set_notify_callback(Some(record_event)), 0);
+
+        notify(NOTIFY_EVAL_COMPLETED, 42);
+
+        assert!(POSTED.lock().unwrap().contains(&(9001, vec![2, 42])));
+        assert!(!CALLED.lock().unwrap().contains(&(NOTIFY_EVAL_COMPLETED, 42)));
+
+        assert_eq!(rhai_set_notify_port(0), 0);
+        assert_eq!(rhai_set_notify_callback(None), 0);
+    }
+
+    #[test]
+    fn test_callback_fallback() {
+        let _guard = LISTENER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        assert_eq!(rhai_set_notify_callback(Some(record_event)), 0);
+
+        notify(NOTIFY_FUNCTION_REQUEST, 7);
+        assert!(CALLED.lock().unwrap().contains(&(NOTIFY_FUNCTION_REQUEST, 7)));
+
+        assert_eq!(rhai_set_notify_callback(None), 0);
+    }
+
+    #[test]
+    fn test_async_eval_completion_is_notified() {
+        use crate::async_eval::rhai_eval_async_start;
+        use crate::engine::{rhai_engine_free, rhai_engine_new};
+
+        let _guard = LISTENER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        assert_eq!(rhai_set_notify_callback(Some(record_event)), 0);
+
+        let engine = rhai_engine_new(std::ptr::null());
+        let script = std::ffi::CString::new("40 + 2").unwrap();
+        let mut eval_id = 0_i64;
+        assert_eq!(rhai_eval_async_start(engine, script.as_ptr(), &mut eval_id), 0);
+
+        let mut notified = false;
+        for _ in 0..500 {
+            if CALLED.lock().unwrap().contains(&(NOTIFY_EVAL_COMPLETED, eval_id)) {
+                notified = true;
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(notified);
+
+        assert_eq!(rhai_set_notify_callback(None), 0);
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_rejects_unsupported_api_version() {
+        let mut api = DartApi { major: 1, minor: 0, functions: std::ptr::null() };
+        assert_eq!(rhai_init_dart_api_dl(&mut api as *mut DartApi as *mut c_void), -1);
+    }
+}
@@ -0,0 +1,290 @@
+//! Lazy variable resolution through a Dart resolver callback
+//!
+//! An engine can have a variable resolver: when a script reads a name that is
+//! not in scope, the engine asks Dart for its value instead of failing right away.
+//! This uses Rhai's variable resolution hook, which is installed on every engine
+//! at creation and reads the resolver from a shared slot, so a resolver can be
+//! set or removed at any time.
+//!
+//! The resolver receives `{"callback_id": <id>, "name": "<variable>"}` and must
+//! answer with one of:
+//!
+//! - `{"status": "found", "value": <json value>}`
+//! - `{"status": "not_found"}`, which leaves the usual "variable not found" error
+//! - `{"status": "error", "error": "<message>"}`, which fails the eval
+//!
+//! Sync evals invoke the resolver callback directly. Async evals post a request
+//! named `__rhai_resolve_var__` to the function request queue, which the
+//! pollers filtered by engine or eval return, and Dart answers it with
+//! `rhai_provide_function_result`.
+//!
+//! Answers are cached for the rest of the eval, so each name is resolved at most
+//! once per eval. Resolved variables are read-only.
+
+use crate::error::clear_last_error;
+use crate::functions::{DartCallback, HookCallback};
+use crate::types::CRhaiEngine;
+use crate::catch_panic;
+use rhai::{Dynamic, Engine};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Function name of the resolver requests posted by async evals.
+pub const RESOLVE_VARIABLE_FUNCTION: &str = "__rhai_resolve_var__";
+
+/// The resolver slot of an engine, shared with its resolution hook.
+pub(crate) type SharedResolver = Arc<Mutex<Option<HookCallback>>>;
+
+/// Response from a variable resolver.
+#[derive(Debug, Deserialize)]
+struct ResolverResponse {
+    /// Status of the lookup: "found", "not_found", or "error"
+    #[serde(default)]
+    status: Option<String>,
+
+    /// The variable's value (only present when status is "found")
+    #[serde(default)]
+    value: Option<serde_json::Value>,
+
+    /// Error message (present when status is "error", or when an async
+    /// request failed on the Dart side)
+    #[serde(default)]
+    error: Option<String>,
+}
+
+thread_local! {
+    /// Resolver answers for the eval running on this thread.
+    ///
+    /// `None` values record names the resolver did not find.
+    static RESOLVED_VARIABLES: RefCell<HashMap<String, Option<Dynamic>>> = RefCell::new(HashMap::new());
+}
+
+/// Keeps the resolver cache of one eval.
+///
+/// Starts with an empty cache and restores the previous one when dropped, so an
+/// eval started from inside a Dart callback does not share answers with the
+/// eval that is waiting on that callback.
+pub(crate) struct ResolverCacheGuard {
+    previous: HashMap<String, Option<Dynamic>>,
+}
+
+impl ResolverCacheGuard {
+    /// Starts a fresh resolver cache for an eval on this thread.
+    pub(crate) fn begin() -> Self {
+        let previous = RESOLVED_VARIABLES.with(|cache| std::mem::take(&mut *cache.borrow_mut()));
+        Self { previous }
+    }
+}
+
+impl Drop for ResolverCacheGuard {
+    fn drop(&mut self) {
+        let previous = std::mem::take(&mut self.previous);
+        RESOLVED_VARIABLES.with(|cache| *cache.borrow_mut() = previous);
+    }
+}
+
+/// Installs the variable resolution hook on an engine.
+///
+/// The hook only consults the resolver for names that are not in scope, and
+/// caches its answers for the current eval.
+pub(crate) fn install_var_resolver(engine: &mut Engine, slot: SharedResolver) {
+    #[allow(deprecated)]
+    engine.on_var(move |name, index, context| {
+        // Names with a known scope position, or found by a scope search, are
+        // resolved as usual
+        if index > 0 || context.scope().contains(name) {
+            return Ok(None);
+        }
+
+        let resolver = match *slot.lock().unwrap() {
+            Some(resolver) => resolver,
+            None => return Ok(None),
+        };
+
+        if let Some(cached) = RESOLVED_VARIABLES.with(|cache| cache.borrow().get(name).cloned()) {
+            return Ok(cached);
+        }
+
+        let resolved = resolve(&resolver, name)
+            .map_err(|e| format!("Variable resolver failed for '{}': {}", name, e))?;
+
+        RESOLVED_VARIABLES.with(|cache| cache.borrow_mut().insert(name.to_string(), resolved.clone()));
+        Ok(resolved)
+    });
+}
+
+/// Asks Dart for the value of a variable.
+///
+/// # Returns
+///
+/// The value, None if the resolver does not know the name, or an error message
+fn resolve(resolver: &HookCallback, name: &str) -> Result<Option<Dynamic>, String> {
+    let args_json = serde_json::to_string(&serde_json::json!({
+        "callback_id": resolver.callback_id,
+        "name": name,
+    }))
+    .map_err(|e| format!("Failed to serialize resolver request: {}", e))?;
+
+    let response_json = resolver.call(RESOLVE_VARIABLE_FUNCTION, args_json)?;
+    parse_resolver_response(&response_json)
+}
+
+/// Parses a resolver response into a value.
+fn parse_resolver_response(response_json: &str) -> Result<Option<Dynamic>, String> {
+    let response: ResolverResponse = serde_json::from_str(response_json)
+        .map_err(|e| format!("Failed to parse resolver response: {}", e))?;
+
+    match (response.status.as_deref(), response.error) {
+        (Some("found"), _) => {
+            let value = response.value.unwrap_or(serde_json::Value::Null);
+            crate::values::json_value_to_dynamic(&value)
+                .map(Some)
+                .map_err(|e| format!("Failed to convert resolved value to Rhai: {}", e))
+        }
+        (Some("not_found"), _) => Ok(None),
+        (Some("error"), error) | (None, error @ Some(_)) => {
+            Err(error.unwrap_or_else(|| "Unknown resolver error".to_string()))
+        }
+        (Some(status), _) => Err(format!("Invalid resolver status: {}", status)),
+        (None, None) => Err("Resolver response is missing a status".to_string()),
+    }
+}
+
+/// Sets or removes the variable resolver of an engine.
+///
+/// When a script reads a name that is not in scope, the engine asks the
+/// resolver for it (see the module documentation for the request and response
+/// formats). Answers are cached for the rest of the eval. Pass a null
+/// callback pointer to remove the resolver.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. The engine pointer must be valid, and
+/// `callback_ptr` must be null or stay valid while it is set as the resolver.
+///
+/// # Returns
+///
+/// 0 on success, -1 on error
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `callback_id` - ID passed back to the resolver with each request
+/// * `callback_ptr` - Function pointer to the Dart resolver, or null
+#[no_mangle]
+pub extern "C" fn rhai_set_var_resolver(
+    engine: *const CRhaiEngine,
+    callback_id: i64,
+    callback_ptr: Option<DartCallback>,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        // Validate pointer
+        if engine.is_null() {
+            set_last_error("Engine pointer is null");
+            return -1;
+        }
+
+        let engine_wrapper = unsafe { &*engine };
+        *engine_wrapper.var_resolver.lock().unwrap() = callback_ptr.map(|callback_ptr| HookCallback {
+            engine_id: engine_wrapper.engine_id(),
+            callback_id,
+            callback_ptr,
+            async_timeout_seconds: engine_wrapper.async_timeout_seconds(),
+        });
+
+        0 // Success
+    }}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{rhai_engine_free, rhai_engine_new, rhai_eval, rhai_set_var};
+    use crate::error::{rhai_free_error, rhai_get_last_error};
+    use std::ffi::{c_char, CStr, CString};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static RESOLVER_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    extern "C" fn customer_resolver(_callback_id: i64, args_json: *const c_char) -> *mut c_char {
+        let args: serde_json::Value =
+            serde_json::from_str(unsafe { CStr::from_ptr(args_json) }.to_str().unwrap()).unwrap();
+        if args["name"] == "customer_tier" {
+            RESOLVER_CALLS.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let response = match args["name"].as_str().unwrap() {
+            "customer_tier" => r#"{"status": "found", "value": "gold"}"#,
+            "customer_credit" => r#"{"status": "error", "error": "credit service unavailable"}"#,
+            _ => r#"{"status": "not_found"}"#,
+        };
+
+        // Dart allocates results with malloc
+        let c_response = CString::new(response).unwrap();
+        unsafe { libc::strdup(c_response.as_ptr()) }
+    }
+
+    fn eval(engine: *const CRhaiEngine, script: &str) -> Result<String, String> {
+        let script = CString::new(script).unwrap();
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        if rhai_eval(engine, script.as_ptr(), &mut result_ptr) == 0 {
+            Ok(unsafe { CString::from_raw(result_ptr).into_string().unwrap() })
+        } else {
+            let error_ptr = rhai_get_last_error();
+            let error = unsafe { CStr::from_ptr(error_ptr).to_str().unwrap().to_string() };
+            rhai_free_error(error_ptr);
+            Err(error)
+        }
+    }
+
+    #[test]
+    fn test_resolver_values_are_cached_per_eval() {
+        let engine = rhai_engine_new(std::ptr::null());
+        assert_eq!(rhai_set_var_resolver(engine, 1, Some(customer_resolver)), 0);
+
+        let before = RESOLVER_CALLS.load(Ordering::SeqCst);
+        assert_eq!(eval(engine, "customer_tier + \"/\" + customer_tier").unwrap(), "\"gold/gold\"");
+        assert_eq!(RESOLVER_CALLS.load(Ordering::SeqCst) - before, 1);
+
+        // A new eval asks again
+        assert_eq!(eval(engine, "customer_tier").unwrap(), "\"gold\"");
+        assert_eq!(RESOLVER_CALLS.load(Ordering::SeqCst) - before, 2);
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_resolver_not_found_and_error() {
+        let engine = rhai_engine_new(std::ptr::null());
+        assert_eq!(rhai_set_var_resolver(engine, 1, Some(customer_resolver)), 0);
+
+        let err = eval(engine, "customer_unknown").unwrap_err();
+        assert!(err.contains("'customer_unknown' not found"), "{}", err);
+
+        let err = eval(engine, "customer_credit").unwrap_err();
+        assert!(err.contains("credit service unavailable"), "{}", err);
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_scope_variables_take_precedence() {
+        let engine = rhai_engine_new(std::ptr::null());
+        assert_eq!(rhai_set_var_resolver(engine, 1, Some(customer_resolver)), 0);
+
+        let name = CString::new("customer_tier").unwrap();
+        let value = CString::new("\"silver\"").unwrap();
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), std::ptr::null()), 0);
+        assert_eq!(eval(engine, "customer_tier").unwrap(), "\"silver\"");
+
+        // Removing the resolver restores the usual behavior
+        assert_eq!(rhai_set_var_resolver(engine, 1, None), 0);
+        assert!(eval(engine, "customer_plan").is_err());
+
+        rhai_engine_free(engine);
+    }
+}
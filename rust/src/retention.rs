@@ -0,0 +1,186 @@
+//! Expiry of abandoned async eval state and registry diagnostics
+//!
+//! If Dart stops polling an eval (for example because the isolate died), its
+//! result, emitted values, and unanswered requests would otherwise stay in the
+//! global registries forever. A retention policy bounds how long they are kept:
+//!
+//! - `completed_result_ttl_seconds`: how long completed eval results and their
+//!   undrained emitted values are kept for Dart to poll.
+//! - `stale_request_ttl_seconds`: how long function requests and response
+//!   channels are kept without an answer. Calls still waiting fail with a
+//!   cancellation error when their channel expires, so this should be longer
+//!   than any function timeout.
+//!
+//! Expired state is collected at most once per `GC_INTERVAL` when an async eval
+//! starts, or on demand with `rhai_collect_garbage`. Response channels whose
+//! waiting side is gone are always collected. `rhai_registry_stats` reports the registry sizes.
+
+use crate::error::clear_last_error;
+use crate::{catch_panic, catch_panic_ptr};
+use std::ffi::{CString, c_char};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Default time completed eval results are kept for Dart to poll.
+pub const DEFAULT_COMPLETED_RESULT_TTL_SECONDS: u64 = 600;
+
+/// Default time function requests are kept without an answer.
+pub const DEFAULT_STALE_REQUEST_TTL_SECONDS: u64 = 600;
+
+/// Minimum time between automatic collections.
+const GC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long abandoned async eval state is kept.
+struct RetentionPolicy {
+    /// How long completed results are kept (zero keeps them until polled)
+    completed_result_ttl: Duration,
+
+    /// How long unanswered requests are kept (zero keeps them until answered)
+    stale_request_ttl: Duration,
+
+    /// When expired state was last collected
+    last_collected: Option<Instant>,
+}
+
+lazy_static::lazy_static! {
+    /// The process-wide retention policy.
+    static ref RETENTION_POLICY: Mutex<RetentionPolicy> = Mutex::new(RetentionPolicy {
+        completed_result_ttl: Duration::from_secs(DEFAULT_COMPLETED_RESULT_TTL_SECONDS),
+        stale_request_ttl: Duration::from_secs(DEFAULT_STALE_REQUEST_TTL_SECONDS),
+        last_collected: None,
+    });
+}
+
+/// Collects expired state.
+///
+/// # Returns
+///
+/// The number of removed entries
+fn collect_garbage() -> usize {
+    let (completed_result_ttl, stale_request_ttl) = {
+        let mut policy = RETENTION_POLICY.lock().unwrap();
+        policy.last_collected = Some(Instant::now());
+        (policy.completed_result_ttl, policy.stale_request_ttl)
+    };
+
+    crate::async_eval::expire_async_state(completed_result_ttl, stale_request_ttl)
+}
+
+/// Collects expired state, unless that was done less than `GC_INTERVAL` ago.
+pub(crate) fn collect_garbage_if_due() {
+    let due = RETENTION_POLICY.lock().unwrap().last_collected
+        .is_none_or(|at| at.elapsed() >= GC_INTERVAL);
+    if due {
+        collect_garbage();
+    }
+}
+
+/// Sets how long abandoned async eval state is kept.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI.
+///
+/// # Returns
+///
+/// 0 on success, -1 on error
+///
+/// # Arguments
+///
+/// * `completed_result_ttl_seconds` - How long completed results are kept for
+///   Dart to poll, or 0 to keep them until polled
+/// * `stale_request_ttl_seconds` - How long function requests are kept without
+///   an answer, or 0 to keep them until answered
+#[no_mangle]
+pub extern "C" fn rhai_set_retention_policy(
+    completed_result_ttl_seconds: u64,
+    stale_request_ttl_seconds: u64,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        let mut policy = RETENTION_POLICY.lock().unwrap();
+        policy.completed_result_ttl = Duration::from_secs(completed_result_ttl_seconds);
+        policy.stale_request_ttl = Duration::from_secs(stale_request_ttl_seconds);
+
+        0 // Success
+    }}
+}
+
+/// Collects expired async eval state now.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI.
+///
+/// # Returns
+///
+/// The number of removed entries, or -1 on error
+#[no_mangle]
+pub extern "C" fn rhai_collect_garbage() -> i64 {
+    catch_panic! {{
+        clear_last_error();
+        collect_garbage() as i64
+    }}
+}
+
+/// Reports the sizes of the global registries.
+///
+/// Returns a JSON object mapping each registry to its number of entries:
+/// `async_eval_results`, `async_eval_events`, `pending_function_requests`,
+/// `function_response_channels`, and `registered_callbacks`.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. The returned string must be freed
+/// with `rhai_free_error()`.
+///
+/// # Returns
+///
+/// The JSON string, or null on error
+#[no_mangle]
+pub extern "C" fn rhai_registry_stats() -> *mut c_char {
+    catch_panic_ptr! {{
+        clear_last_error();
+
+        let stats: serde_json::Map<String, serde_json::Value> = crate::async_eval::async_registry_sizes()
+            .into_iter()
+            .chain(crate::functions::callback_registry_sizes())
+            .map(|(name, size)| (name.to_string(), size.into()))
+            .collect();
+
+        match CString::new(serde_json::Value::Object(stats).to_string()) {
+            Ok(c_string) => c_string.into_raw(),
+            Err(e) => {
+                set_last_error(&format!("Failed to create C string: {}", e));
+                std::ptr::null_mut()
+            }
+        }
+    }}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::rhai_free_error;
+    use std::ffi::CStr;
+
+    #[test]
+    fn test_registry_stats_report_every_registry() {
+        let stats_ptr = rhai_registry_stats();
+        assert!(!stats_ptr.is_null());
+        let stats: serde_json::Value =
+            serde_json::from_str(unsafe { CStr::from_ptr(stats_ptr) }.to_str().unwrap()).unwrap();
+        rhai_free_error(stats_ptr);
+
+        for name in [
+            "async_eval_results",
+            "async_eval_events",
+            "pending_function_requests",
+            "function_response_channels",
+            "registered_callbacks",
+        ] {
+            assert!(stats[name].is_u64(), "missing {}", name);
+        }
+    }
+}
@@ -0,0 +1,1408 @@
+//! Scope handle FFI
+//!
+//! This module provides FFI functions for named variable scopes (execution contexts).
+//! A scope handle owns its own variables and constants, and can be evaluated against
+//! any engine. This lets many per-user contexts reuse one configured engine and its
+//! registered functions without sharing variables.
+
+use crate::types::{CRhaiEngine, CRhaiScope, ScopeState};
+use crate::error::{clear_last_error, set_last_error};
+use crate::engine::eval_to_json;
+use crate::type_locks::{check_type_lock, check_type_locks, push_with_lock, type_lock_from_c};
+use crate::watch::deliver_watch_notifications;
+use crate::values::{
+    dynamic_to_json_value, dynamic_values_equal, json_to_rhai_dynamic, json_value_to_dynamic,
+    rhai_dynamic_to_json,
+};
+use crate::{catch_panic, catch_panic_ptr};
+use rhai::{Dynamic, Position, Scope};
+use serde::{Deserialize, Serialize};
+use std::ffi::{CString, CStr, c_char};
+use std::sync::atomic::{AtomicI64, Ordering};
+
+/// Atomic counter for generating unique savepoint IDs.
+static NEXT_SAVEPOINT_ID: AtomicI64 = AtomicI64::new(1);
+
+/// Creates a new, empty scope handle.
+///
+/// # Returns
+///
+/// A pointer to a newly created scope handle, or null on error.
+/// The returned pointer must be freed using `rhai_scope_free()`.
+#[no_mangle]
+pub extern "C" fn rhai_scope_new() -> *mut CRhaiScope {
+    catch_panic_ptr! {{
+        clear_last_error();
+
+        Box::into_raw(Box::new(CRhaiScope::new()))
+    }}
+}
+
+/// Frees a scope handle.
+///
+/// Async evals that were started with this scope handle keep running on
+/// their own clone of the scope and are not affected.
+///
+/// # Safety
+///
+/// The scope pointer must have been created by `rhai_scope_new()` and
+/// must not have been freed previously. Passing a null pointer is safe
+/// and will be a no-op.
+///
+/// # Arguments
+///
+/// * `scope` - Pointer to the scope handle to free
+#[no_mangle]
+pub extern "C" fn rhai_scope_free(scope: *mut CRhaiScope) {
+    let _result = catch_panic! {{
+        if !scope.is_null() {
+            unsafe {
+                // Reclaim ownership and drop
+                let _ = Box::from_raw(scope);
+            }
+        }
+        0 // Success
+    }};
+}
+
+/// Sets a variable or constant on a scope handle from its JSON-encoded value.
+///
+/// Shared by `rhai_scope_set_var` and `rhai_scope_set_constant`. A null
+/// `type_lock` keeps any existing lock.
+fn set_scope_value(
+    scope: *mut CRhaiScope,
+    name: *const c_char,
+    value_json: *const c_char,
+    constant: bool,
+    type_lock: *const c_char,
+) -> i32 {
+    // Validate pointers
+    if scope.is_null() {
+        set_last_error("Scope pointer is null");
+        return -1;
+    }
+
+    if name.is_null() {
+        set_last_error("Variable name pointer is null");
+        return -1;
+    }
+
+    if value_json.is_null() {
+        set_last_error("Value JSON pointer is null");
+        return -1;
+    }
+
+    // Get the scope handle
+    let scope_handle = unsafe { &*scope };
+
+    // Convert variable name to Rust string
+    let var_name = unsafe {
+        match CStr::from_ptr(name).to_str() {
+            Ok(s) => s.to_string(),
+            Err(e) => {
+                set_last_error(&format!("Invalid UTF-8 in variable name: {}", e));
+                return -1;
+            }
+        }
+    };
+
+    // Convert JSON value to Rust string
+    let json_str = unsafe {
+        match CStr::from_ptr(value_json).to_str() {
+            Ok(s) => s,
+            Err(e) => {
+                set_last_error(&format!("Invalid UTF-8 in value JSON: {}", e));
+                return -1;
+            }
+        }
+    };
+
+    // Convert JSON to Rhai Dynamic value
+    let dynamic_value = match json_to_rhai_dynamic(json_str) {
+        Ok(v) => v,
+        Err(e) => {
+            set_last_error(&format!("Failed to parse value JSON: {}", e));
+            return -1;
+        }
+    };
+
+    let lock = match type_lock_from_c(type_lock) {
+        Ok(lock) => lock,
+        Err(e) => {
+            set_last_error(&e);
+            return -1;
+        }
+    };
+
+    let mut scope = scope_handle.scope();
+    if let Err(e) = push_with_lock(&mut scope, var_name, dynamic_value, constant, lock) {
+        set_last_error(&e);
+        return -1;
+    }
+
+    0 // Success
+}
+
+/// Sets a mutable variable on a scope handle, optionally locking its type.
+///
+/// See `rhai_set_var` for the supported lock modes and how locks are enforced.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. The scope, name, and value_json pointers must be valid.
+/// The type_lock pointer may be null, in which case the variable keeps any existing lock.
+///
+/// # Returns
+///
+/// 0 on success, -1 on error (including when the value does not satisfy the lock).
+/// On error, use `rhai_get_last_error()` to retrieve the error message.
+///
+/// # Arguments
+///
+/// * `scope` - Pointer to the scope handle
+/// * `name` - Pointer to a null-terminated C string containing the variable name
+/// * `value_json` - Pointer to a null-terminated C string containing the JSON-encoded value
+/// * `type_lock` - Pointer to a null-terminated C string containing the lock mode, or null
+#[no_mangle]
+pub extern "C" fn rhai_scope_set_var(
+    scope: *mut CRhaiScope,
+    name: *const c_char,
+    value_json: *const c_char,
+    type_lock: *const c_char,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+        set_scope_value(scope, name, value_json, false, type_lock)
+    }}
+}
+
+/// Sets an immutable constant on a scope handle.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. The scope, name, and value_json pointers must be valid.
+///
+/// # Returns
+///
+/// 0 on success, -1 on error.
+/// On error, use `rhai_get_last_error()` to retrieve the error message.
+///
+/// # Arguments
+///
+/// * `scope` - Pointer to the scope handle
+/// * `name` - Pointer to a null-terminated C string containing the constant name
+/// * `value_json` - Pointer to a null-terminated C string containing the JSON-encoded value
+#[no_mangle]
+pub extern "C" fn rhai_scope_set_constant(
+    scope: *mut CRhaiScope,
+    name: *const c_char,
+    value_json: *const c_char,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+        set_scope_value(scope, name, value_json, true, std::ptr::null())
+    }}
+}
+
+/// Gets the value of a variable or constant on a scope handle as JSON.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. The scope, name, and result_out pointers must be valid.
+///
+/// # Returns
+///
+/// 0 on success (with the JSON value stored via result_out), -1 on error
+/// (including when the variable does not exist).
+///
+/// # Arguments
+///
+/// * `scope` - Pointer to the scope handle
+/// * `name` - Pointer to a null-terminated C string containing the variable name
+/// * `result_out` - Pointer to store the JSON value (must be freed with rhai_free_error)
+#[no_mangle]
+pub extern "C" fn rhai_scope_get_var(
+    scope: *const CRhaiScope,
+    name: *const c_char,
+    result_out: *mut *mut c_char,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        // Validate pointers
+        if scope.is_null() {
+            set_last_error("Scope pointer is null");
+            return -1;
+        }
+
+        if name.is_null() {
+            set_last_error("Variable name pointer is null");
+            return -1;
+        }
+
+        if result_out.is_null() {
+            set_last_error("Result output pointer is null");
+            return -1;
+        }
+
+        let scope_handle = unsafe { &*scope };
+
+        // Convert variable name to Rust string
+        let var_name = unsafe {
+            match CStr::from_ptr(name).to_str() {
+                Ok(s) => s,
+                Err(e) => {
+                    set_last_error(&format!("Invalid UTF-8 in variable name: {}", e));
+                    return -1;
+                }
+            }
+        };
+
+        // Look up the variable and convert it to JSON
+        let json = {
+            let scope = scope_handle.scope();
+            match scope.get(var_name) {
+                Some(value) => match rhai_dynamic_to_json(value) {
+                    Ok(json) => json,
+                    Err(e) => {
+                        set_last_error(&format!("Failed to convert variable to JSON: {}", e));
+                        return -1;
+                    }
+                },
+                None => {
+                    set_last_error(&format!("Variable '{}' not found in scope", var_name));
+                    return -1;
+                }
+            }
+        };
+
+        match CString::new(json) {
+            Ok(c_string) => {
+                unsafe {
+                    *result_out = c_string.into_raw();
+                }
+                0 // Success
+            }
+            Err(e) => {
+                set_last_error(&format!("Failed to create C string: {}", e));
+                -1
+            }
+        }
+    }}
+}
+
+/// Clears all variables, constants and type locks from a scope handle.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. The scope pointer must be valid.
+///
+/// # Returns
+///
+/// 0 on success, -1 on error.
+///
+/// # Arguments
+///
+/// * `scope` - Pointer to the scope handle
+#[no_mangle]
+pub extern "C" fn rhai_scope_clear(scope: *mut CRhaiScope) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        // Validate pointer
+        if scope.is_null() {
+            set_last_error("Scope pointer is null");
+            return -1;
+        }
+
+        let scope_handle = unsafe { &*scope };
+        let mut state = scope_handle.scope();
+        state.clear();
+        state.type_locks.clear();
+
+        0 // Success
+    }}
+}
+
+/// Evaluates a Rhai script against a scope handle and returns the result as JSON.
+///
+/// The engine supplies configuration and registered functions, while variables
+/// are read from and written to the given scope handle. The engine's own scope
+/// is not visible to the script.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. The engine, scope, and script pointers must be valid.
+///
+/// # Returns
+///
+/// 0 on success (with result stored via result_out), -1 on error.
+/// On error, use `rhai_get_last_error()` to retrieve the error message.
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `scope` - Pointer to the scope handle
+/// * `script` - Pointer to a null-terminated C string containing the script
+/// * `result_out` - Pointer to store the result JSON string (must be freed with rhai_free_error)
+#[no_mangle]
+pub extern "C" fn rhai_eval_in_scope(
+    engine: *const CRhaiEngine,
+    scope: *mut CRhaiScope,
+    script: *const c_char,
+    result_out: *mut *mut c_char,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        // Validate pointers
+        if engine.is_null() {
+            set_last_error("Engine pointer is null");
+            return -1;
+        }
+
+        if scope.is_null() {
+            set_last_error("Scope pointer is null");
+            return -1;
+        }
+
+        if script.is_null() {
+            set_last_error("Script pointer is null");
+            return -1;
+        }
+
+        if result_out.is_null() {
+            set_last_error("Result output pointer is null");
+            return -1;
+        }
+
+        let engine_wrapper = unsafe { &*engine };
+        let scope_handle = unsafe { &*scope };
+
+        // Convert C string to Rust string
+        let script_str = unsafe {
+            match CStr::from_ptr(script).to_str() {
+                Ok(s) => s,
+                Err(e) => {
+                    set_last_error(&format!("Invalid UTF-8 in script: {}", e));
+                    return -1;
+                }
+            }
+        };
+
+        // Hold the engine for the whole eval, as in rhai_eval
+        let rhai_engine = engine_wrapper.inner.clone();
+        let result = eval_to_json(&rhai_engine, &mut scope_handle.scope(), script_str);
+        deliver_watch_notifications();
+
+        match result {
+            Ok(json) => {
+                match CString::new(json) {
+                    Ok(c_string) => {
+                        unsafe {
+                            *result_out = c_string.into_raw();
+                        }
+                        0 // Success
+                    }
+                    Err(e) => {
+                        set_last_error(&format!("Failed to create C string: {}", e));
+                        -1
+                    }
+                }
+            }
+            Err(error_msg) => {
+                set_last_error(&error_msg);
+                -1
+            }
+        }
+    }}
+}
+
+/// Saves the current variables of a scope so they can be restored later.
+///
+/// Savepoints nest: rolling back to or releasing a savepoint also discards every
+/// savepoint created after it. This supports multi-step workflows where several
+/// evals must succeed together, e.g. savepoint, eval, eval, then release or rollback.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. The engine and savepoint_id_out pointers
+/// must be valid, and the scope pointer must be valid or null.
+///
+/// # Returns
+///
+/// 0 on success (with the savepoint ID stored via savepoint_id_out), -1 on error
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `scope` - Pointer to a scope handle, or null to use the engine's scope
+/// * `savepoint_id_out` - Pointer to store the new savepoint ID
+#[no_mangle]
+pub extern "C" fn rhai_scope_savepoint(
+    engine: *const CRhaiEngine,
+    scope: *const CRhaiScope,
+    savepoint_id_out: *mut i64,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        // Validate pointers
+        if engine.is_null() {
+            set_last_error("Engine pointer is null");
+            return -1;
+        }
+
+        if savepoint_id_out.is_null() {
+            set_last_error("Savepoint ID output pointer is null");
+            return -1;
+        }
+
+        let target = unsafe { &*engine }.scope_target(scope);
+        let mut state = target.lock().unwrap();
+
+        let savepoint_id = NEXT_SAVEPOINT_ID.fetch_add(1, Ordering::SeqCst);
+        let saved = state.vars.clone();
+        state.savepoints.push((savepoint_id, saved));
+
+        unsafe {
+            *savepoint_id_out = savepoint_id;
+        }
+
+        0 // Success
+    }}
+}
+
+/// Restores a scope to the state captured by a savepoint.
+///
+/// The savepoint itself is kept, so the scope can be rolled back to it again.
+/// Savepoints created after it are discarded.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. The engine pointer must be valid, and
+/// the scope pointer must be valid or null.
+///
+/// # Returns
+///
+/// 0 on success, -1 if the savepoint does not exist for this scope
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `scope` - Pointer to a scope handle, or null to use the engine's scope
+/// * `savepoint_id` - The savepoint ID returned by `rhai_scope_savepoint()`
+#[no_mangle]
+pub extern "C" fn rhai_scope_rollback(
+    engine: *const CRhaiEngine,
+    scope: *const CRhaiScope,
+    savepoint_id: i64,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        // Validate pointer
+        if engine.is_null() {
+            set_last_error("Engine pointer is null");
+            return -1;
+        }
+
+        let target = unsafe { &*engine }.scope_target(scope);
+        let mut state = target.lock().unwrap();
+
+        match state.savepoints.iter().position(|(id, _)| *id == savepoint_id) {
+            Some(index) => {
+                state.savepoints.truncate(index + 1);
+                let saved = state.savepoints[index].1.clone();
+                state.vars = saved;
+                0 // Success
+            }
+            None => {
+                set_last_error(&format!("Savepoint ID {} not found", savepoint_id));
+                -1
+            }
+        }
+    }}
+}
+
+/// Releases a savepoint without changing the scope's variables.
+///
+/// Savepoints created after it are released too.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. The engine pointer must be valid, and
+/// the scope pointer must be valid or null.
+///
+/// # Returns
+///
+/// 0 on success, -1 if the savepoint does not exist for this scope
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `scope` - Pointer to a scope handle, or null to use the engine's scope
+/// * `savepoint_id` - The savepoint ID returned by `rhai_scope_savepoint()`
+#[no_mangle]
+pub extern "C" fn rhai_scope_release(
+    engine: *const CRhaiEngine,
+    scope: *const CRhaiScope,
+    savepoint_id: i64,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        // Validate pointer
+        if engine.is_null() {
+            set_last_error("Engine pointer is null");
+            return -1;
+        }
+
+        let target = unsafe { &*engine }.scope_target(scope);
+        let mut state = target.lock().unwrap();
+
+        match state.savepoints.iter().position(|(id, _)| *id == savepoint_id) {
+            Some(index) => {
+                state.savepoints.truncate(index);
+                0 // Success
+            }
+            None => {
+                set_last_error(&format!("Savepoint ID {} not found", savepoint_id));
+                -1
+            }
+        }
+    }}
+}
+
+/// Current version of the scope export document format.
+pub const SCOPE_EXPORT_VERSION: u32 = 1;
+
+/// A serialized scope, as produced by `rhai_scope_export`.
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "variables": [{ "name": "total", "constant": false, "value": 42 }],
+///   "errors": [{ "name": "callback", "error": "Unsupported Rhai type: Fn" }]
+/// }
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScopeExport {
+    /// Format version, currently always `SCOPE_EXPORT_VERSION`
+    pub version: u32,
+
+    /// The exported variables, in scope order
+    pub variables: Vec<ExportedVariable>,
+
+    /// Variables that could not be exported, with the reason
+    #[serde(default)]
+    pub errors: Vec<ExportError>,
+}
+
+/// A single variable in a scope export.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedVariable {
+    /// Variable name
+    pub name: String,
+
+    /// Whether the variable is a constant
+    #[serde(default)]
+    pub constant: bool,
+
+    /// The variable's value, in the same JSON encoding as eval results
+    pub value: serde_json::Value,
+}
+
+/// A variable that was left out of a scope export.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportError {
+    /// Variable name
+    pub name: String,
+
+    /// Why the variable could not be exported
+    pub error: String,
+}
+
+/// Builds the export document for a scope.
+///
+/// Shadowed entries are skipped, so each name appears once with the value a
+/// script would see. Values that cannot be represented as JSON are listed in
+/// `errors` instead of failing the whole export.
+pub(crate) fn export_scope(scope: &Scope<'static>) -> ScopeExport {
+    let entries: Vec<(&str, bool, Dynamic)> = scope.iter().collect();
+    let mut variables = Vec::new();
+    let mut errors = Vec::new();
+
+    for (index, (name, constant, value)) in entries.iter().enumerate() {
+        // A later entry with the same name shadows this one
+        if entries[index + 1..].iter().any(|(later, _, _)| later == name) {
+            continue;
+        }
+
+        match dynamic_to_json_value(value) {
+            Ok(json) => variables.push(ExportedVariable {
+                name: name.to_string(),
+                constant: *constant,
+                value: json,
+            }),
+            Err(e) => errors.push(ExportError {
+                name: name.to_string(),
+                error: e,
+            }),
+        }
+    }
+
+    ScopeExport {
+        version: SCOPE_EXPORT_VERSION,
+        variables,
+        errors,
+    }
+}
+
+/// Rebuilds a scope from an export document.
+///
+/// # Returns
+///
+/// The restored scope, or an error if the document is malformed, has an
+/// unsupported version, or contains a value that cannot be converted
+pub(crate) fn import_scope(json: &str) -> Result<Scope<'static>, String> {
+    let export: ScopeExport = serde_json::from_str(json)
+        .map_err(|e| format!("Failed to parse scope export: {}", e))?;
+
+    if export.version != SCOPE_EXPORT_VERSION {
+        return Err(format!(
+            "Unsupported scope export version: {} (expected {})",
+            export.version, SCOPE_EXPORT_VERSION
+        ));
+    }
+
+    let mut scope = Scope::new();
+    for variable in export.variables {
+        let value = json_value_to_dynamic(&variable.value)
+            .map_err(|e| format!("Failed to restore variable '{}': {}", variable.name, e))?;
+
+        if variable.constant {
+            scope.push_constant_dynamic(variable.name, value);
+        } else {
+            scope.push_dynamic(variable.name, value);
+        }
+    }
+
+    Ok(scope)
+}
+
+/// Serializes all variables of a scope to a versioned JSON document.
+///
+/// The document lists each variable with its name, constness, and value (see
+/// `ScopeExport`). Variables whose values cannot be represented as JSON are
+/// reported in the document's `errors` list rather than failing the export.
+/// The result can be passed to `rhai_scope_import` to restore the scope, e.g.
+/// when resuming a session after an app restart.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. The engine and result_out pointers must
+/// be valid, and the scope pointer must be valid or null.
+///
+/// # Returns
+///
+/// 0 on success (with the JSON document stored via result_out), -1 on error
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `scope` - Pointer to a scope handle, or null to use the engine's scope
+/// * `result_out` - Pointer to store the JSON document (must be freed with rhai_free_error)
+#[no_mangle]
+pub extern "C" fn rhai_scope_export(
+    engine: *const CRhaiEngine,
+    scope: *const CRhaiScope,
+    result_out: *mut *mut c_char,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        // Validate pointers
+        if engine.is_null() {
+            set_last_error("Engine pointer is null");
+            return -1;
+        }
+
+        if result_out.is_null() {
+            set_last_error("Result output pointer is null");
+            return -1;
+        }
+
+        let target = unsafe { &*engine }.scope_target(scope);
+        let export = export_scope(&target.lock().unwrap().vars);
+
+        let json = match serde_json::to_string(&export) {
+            Ok(json) => json,
+            Err(e) => {
+                set_last_error(&format!("Failed to serialize scope export: {}", e));
+                return -1;
+            }
+        };
+
+        match CString::new(json) {
+            Ok(c_string) => {
+                unsafe {
+                    *result_out = c_string.into_raw();
+                }
+                0 // Success
+            }
+            Err(e) => {
+                set_last_error(&format!("Failed to create C string: {}", e));
+                -1
+            }
+        }
+    }}
+}
+
+/// Restores the variables of a scope from a document produced by `rhai_scope_export`.
+///
+/// The scope's current variables are replaced by the imported ones. The import is
+/// all-or-nothing: if the document is invalid, or a value breaks one of the scope's
+/// type locks, the scope is left unchanged. Savepoints and type locks are not part
+/// of the export and are kept as they are.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. The engine and json pointers must be
+/// valid, and the scope pointer must be valid or null.
+///
+/// # Returns
+///
+/// 0 on success, -1 on error
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `scope` - Pointer to a scope handle, or null to use the engine's scope
+/// * `json` - Pointer to a null-terminated C string containing the export document
+#[no_mangle]
+pub extern "C" fn rhai_scope_import(
+    engine: *const CRhaiEngine,
+    scope: *const CRhaiScope,
+    json: *const c_char,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        // Validate pointers
+        if engine.is_null() {
+            set_last_error("Engine pointer is null");
+            return -1;
+        }
+
+        if json.is_null() {
+            set_last_error("JSON pointer is null");
+            return -1;
+        }
+
+        let json_str = unsafe {
+            match CStr::from_ptr(json).to_str() {
+                Ok(s) => s,
+                Err(e) => {
+                    set_last_error(&format!("Invalid UTF-8 in scope JSON: {}", e));
+                    return -1;
+                }
+            }
+        };
+
+        let restored = match import_scope(json_str) {
+            Ok(scope) => scope,
+            Err(e) => {
+                set_last_error(&e);
+                return -1;
+            }
+        };
+
+        let target = unsafe { &*engine }.scope_target(scope);
+        let mut state = target.lock().unwrap();
+
+        // Imported values must satisfy the scope's type locks like any other value
+        if let Err(e) = check_type_locks(&state.type_locks, &restored, Position::NONE) {
+            set_last_error(&e);
+            return -1;
+        }
+
+        state.vars = restored;
+
+        0 // Success
+    }}
+}
+
+/// The variables a script added, removed, or modified in its scope.
+///
+/// Values are the new values, in the same JSON encoding as eval results.
+/// Values that cannot be represented as JSON are reported as `null`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ScopeChanges {
+    /// Variables that did not exist before the eval, with their values
+    pub added: serde_json::Map<String, serde_json::Value>,
+
+    /// Names of variables that no longer exist after the eval
+    pub removed: Vec<String>,
+
+    /// Variables whose value changed, with their new values
+    pub modified: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ScopeChanges {
+    /// Returns true if nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// Compares two states of a scope and lists the differences.
+///
+/// Only the visible entry of each name is compared, so shadowing a variable
+/// with `let` is reported as a modification of that name.
+pub(crate) fn diff_scopes(before: &Scope<'static>, after: &Scope<'static>) -> ScopeChanges {
+    let mut changes = ScopeChanges::default();
+    let to_json = |value: &Dynamic| dynamic_to_json_value(value).unwrap_or(serde_json::Value::Null);
+
+    for (name, _, value) in after.iter() {
+        // Only the last entry of each name is visible
+        if changes.added.contains_key(name) || changes.modified.contains_key(name) {
+            continue;
+        }
+        let visible = after.get(name).unwrap_or(&value);
+
+        match before.get(name) {
+            None => {
+                changes.added.insert(name.to_string(), to_json(visible));
+            }
+            Some(before_value) if !dynamic_values_equal(before_value, visible) => {
+                changes.modified.insert(name.to_string(), to_json(visible));
+            }
+            Some(_) => {}
+        }
+    }
+
+    for (name, _, _) in before.iter() {
+        if !after.contains(name) && !changes.removed.iter().any(|n| n == name) {
+            changes.removed.push(name.to_string());
+        }
+    }
+
+    changes
+}
+
+/// Wraps an eval result together with its change set.
+///
+/// # Returns
+///
+/// `{"value": <result>, "changes": <change set>}` as a JSON string
+pub(crate) fn result_with_changes(result_json: &str, changes: &ScopeChanges) -> Result<String, String> {
+    let value: serde_json::Value = serde_json::from_str(result_json)
+        .map_err(|e| format!("Failed to parse result JSON: {}", e))?;
+
+    serde_json::to_string(&serde_json::json!({
+        "value": value,
+        "changes": changes,
+    }))
+    .map_err(|e| format!("Failed to serialize change set: {}", e))
+}
+
+/// How to resolve a conflict when writing an async eval's scope back.
+///
+/// A conflict occurs when the async script changed a variable, and the target
+/// scope's value of that variable also changed after the async eval started
+/// (for example through a sync eval or `rhai_set_var`). Changing a constant of
+/// the target scope, or declaring a constant over one of its variables, is a
+/// conflict the script never wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MergeConflictPolicy {
+    /// The async script's value overwrites the current value
+    ScriptWins,
+    /// The current value is kept, and the script's value is discarded
+    KeepCurrent,
+    /// Nothing is written back, and the eval fails with an error naming the conflicts
+    Fail,
+}
+
+impl MergeConflictPolicy {
+    /// Converts the `merge_conflict` field of `CRhaiEvalOptions`.
+    pub(crate) fn from_c(value: u8) -> Result<Self, String> {
+        match value {
+            0 => Ok(Self::ScriptWins),
+            1 => Ok(Self::KeepCurrent),
+            2 => Ok(Self::Fail),
+            _ => Err(format!("Invalid merge conflict policy: {}", value)),
+        }
+    }
+}
+
+/// Writes the changes an async script made to its scope back to a target scope.
+///
+/// Only variables whose value in `result` differs from `snapshot` (the scope as it
+/// was when the eval started) are written, so variables the script did not touch
+/// never overwrite newer values in `target`. Constants already in `target` are
+/// never overwritten, and a script constant never shadows a variable of
+/// `target`; either counts as a conflict under every policy.
+///
+/// Written values must satisfy the type locks `target` has now, which may
+/// differ from those it had when the eval started.
+///
+/// Conflicts (with `MergeConflictPolicy::Fail`) and type lock violations are
+/// detected before anything is written, so the target is left untouched on error.
+///
+/// # Returns
+///
+/// Ok on success, or an error naming the conflicting or violating variable
+pub(crate) fn merge_scope_changes(
+    snapshot: &Scope<'static>,
+    result: &Scope<'static>,
+    target: &mut ScopeState,
+    policy: MergeConflictPolicy,
+) -> Result<(), String> {
+    let mut writes: Vec<(String, bool, Dynamic)> = Vec::new();
+    let mut conflicts: Vec<String> = Vec::new();
+
+    // Only the visible (innermost) entry of each name matters
+    let visible = result.clone_visible();
+    for (name, is_constant, value) in visible.iter() {
+        let before = snapshot.get(name);
+
+        let changed = match before {
+            Some(before_value) => !dynamic_values_equal(before_value, &value),
+            None => true,
+        };
+        if !changed {
+            continue;
+        }
+
+        // Constants may not replace or be replaced by variables of the target
+        let constant_clash = match target.is_constant(name) {
+            Some(target_constant) => target_constant || is_constant,
+            None => false,
+        };
+
+        let current = target.get(name);
+        let conflict = constant_clash
+            || match (before, current) {
+                (Some(before_value), Some(current_value)) => !dynamic_values_equal(before_value, current_value),
+                (None, Some(_)) | (Some(_), None) => true,
+                (None, None) => false,
+            };
+
+        if conflict {
+            match policy {
+                MergeConflictPolicy::ScriptWins if !constant_clash => {}
+                _ => {
+                    conflicts.push(name.to_string());
+                    continue;
+                }
+            }
+        }
+
+        writes.push((name.to_string(), is_constant, value));
+    }
+
+    if policy == MergeConflictPolicy::Fail && !conflicts.is_empty() {
+        return Err(format!(
+            "Scope write-back conflict: variables changed by another eval: {}",
+            conflicts.join(", ")
+        ));
+    }
+
+    for (name, _, value) in &writes {
+        check_type_lock(&target.type_locks, name, value)?;
+    }
+
+    for (name, is_constant, value) in writes {
+        if is_constant {
+            target.vars.push_constant_dynamic(name, value);
+        } else if let Some(slot) = target.vars.get_mut(&name) {
+            *slot = value;
+        } else {
+            target.vars.push_dynamic(name, value);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{rhai_engine_new, rhai_engine_free, rhai_set_var};
+    use crate::type_locks::TypeLock;
+
+    fn eval_in(engine: *const CRhaiEngine, scope: *mut CRhaiScope, script: &str) -> Result<String, String> {
+        let script = CString::new(script).unwrap();
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        let ret = rhai_eval_in_scope(engine, scope, script.as_ptr(), &mut result_ptr);
+        if ret == 0 {
+            unsafe { Ok(CString::from_raw(result_ptr).into_string().unwrap()) }
+        } else {
+            let error_ptr = crate::error::rhai_get_last_error();
+            unsafe { Err(CString::from_raw(error_ptr).into_string().unwrap()) }
+        }
+    }
+
+    fn get_var(scope: *const CRhaiScope, name: &str) -> Option<String> {
+        let name = CString::new(name).unwrap();
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        if rhai_scope_get_var(scope, name.as_ptr(), &mut result_ptr) == 0 {
+            unsafe { Some(CString::from_raw(result_ptr).into_string().unwrap()) }
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn test_scope_new_and_free() {
+        let scope = rhai_scope_new();
+        assert!(!scope.is_null());
+        rhai_scope_free(scope);
+
+        // Freeing null should not crash
+        rhai_scope_free(std::ptr::null_mut());
+    }
+
+    #[test]
+    fn test_scope_set_and_get_var() {
+        let scope = rhai_scope_new();
+        let name = CString::new("user").unwrap();
+        let value = CString::new(r#"{"name": "Alice"}"#).unwrap();
+
+        assert_eq!(rhai_scope_set_var(scope, name.as_ptr(), value.as_ptr(), std::ptr::null()), 0);
+        assert_eq!(get_var(scope, "user").unwrap(), r#"{"name":"Alice"}"#);
+        assert!(get_var(scope, "missing").is_none());
+
+        assert_eq!(rhai_scope_clear(scope), 0);
+        assert!(get_var(scope, "user").is_none());
+
+        rhai_scope_free(scope);
+    }
+
+    #[test]
+    fn test_scopes_do_not_share_variables() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let scope_a = rhai_scope_new();
+        let scope_b = rhai_scope_new();
+
+        assert_eq!(eval_in(engine, scope_a, "let counter = 1; counter").unwrap(), "1");
+        assert_eq!(eval_in(engine, scope_b, "let counter = 100; counter").unwrap(), "100");
+        assert_eq!(eval_in(engine, scope_a, "counter += 1; counter").unwrap(), "2");
+
+        assert_eq!(get_var(scope_a, "counter").unwrap(), "2");
+        assert_eq!(get_var(scope_b, "counter").unwrap(), "100");
+
+        rhai_scope_free(scope_a);
+        rhai_scope_free(scope_b);
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_engine_scope_not_visible_in_scope_handle() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let scope = rhai_scope_new();
+
+        let name = CString::new("secret").unwrap();
+        let value = CString::new("42").unwrap();
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), std::ptr::null()), 0);
+
+        let err = eval_in(engine, scope, "secret").unwrap_err();
+        assert!(err.contains("Variable 'secret' not found"));
+
+        rhai_scope_free(scope);
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_scope_constant_is_immutable() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let scope = rhai_scope_new();
+
+        let name = CString::new("LIMIT").unwrap();
+        let value = CString::new("10").unwrap();
+        assert_eq!(rhai_scope_set_constant(scope, name.as_ptr(), value.as_ptr()), 0);
+
+        assert_eq!(eval_in(engine, scope, "LIMIT * 2").unwrap(), "20");
+        assert!(eval_in(engine, scope, "LIMIT = 5;").is_err());
+
+        rhai_scope_free(scope);
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_merge_writes_only_changed_variables() {
+        let mut snapshot = Scope::new();
+        snapshot.push("a", 1_i64);
+        snapshot.push("b", 2_i64);
+
+        let mut result = snapshot.clone();
+        *result.get_mut("a").unwrap() = Dynamic::from(10_i64);
+        result.push("c", 3_i64);
+
+        // `b` was changed by someone else meanwhile, but the script did not touch it
+        let mut target = ScopeState { vars: snapshot.clone(), ..Default::default() };
+        *target.get_mut("b").unwrap() = Dynamic::from(20_i64);
+
+        merge_scope_changes(&snapshot, &result, &mut target, MergeConflictPolicy::Fail).unwrap();
+        assert_eq!(target.get_value::<i64>("a"), Some(10));
+        assert_eq!(target.get_value::<i64>("b"), Some(20));
+        assert_eq!(target.get_value::<i64>("c"), Some(3));
+    }
+
+    #[test]
+    fn test_merge_conflict_policies() {
+        let mut snapshot = Scope::new();
+        snapshot.push("x", 1_i64);
+
+        let mut result = snapshot.clone();
+        *result.get_mut("x").unwrap() = Dynamic::from(2_i64);
+
+        let mut current = snapshot.clone();
+        *current.get_mut("x").unwrap() = Dynamic::from(3_i64);
+
+        let mut target = ScopeState { vars: current.clone(), ..Default::default() };
+        merge_scope_changes(&snapshot, &result, &mut target, MergeConflictPolicy::ScriptWins).unwrap();
+        assert_eq!(target.get_value::<i64>("x"), Some(2));
+
+        let mut target = ScopeState { vars: current.clone(), ..Default::default() };
+        merge_scope_changes(&snapshot, &result, &mut target, MergeConflictPolicy::KeepCurrent).unwrap();
+        assert_eq!(target.get_value::<i64>("x"), Some(3));
+
+        let mut target = ScopeState { vars: current.clone(), ..Default::default() };
+        let err = merge_scope_changes(&snapshot, &result, &mut target, MergeConflictPolicy::Fail).unwrap_err();
+        assert!(err.contains("x"));
+        assert_eq!(target.get_value::<i64>("x"), Some(3));
+    }
+
+    #[test]
+    fn test_merge_never_overwrites_target_constant() {
+        let snapshot = Scope::new();
+        let mut result = Scope::new();
+        result.push("LIMIT", 5_i64);
+
+        let mut target = ScopeState::default();
+        target.push_constant("LIMIT", 10_i64);
+
+        merge_scope_changes(&snapshot, &result, &mut target, MergeConflictPolicy::ScriptWins).unwrap();
+        assert_eq!(target.get_value::<i64>("LIMIT"), Some(10));
+        assert_eq!(target.len(), 1);
+    }
+
+    #[test]
+    fn test_merge_never_shadows_target_variable_with_constant() {
+        let mut snapshot = Scope::new();
+        snapshot.push("limit", 1_i64);
+
+        // The script redeclares `limit` as a constant
+        let mut result = snapshot.clone();
+        result.push_constant("limit", 5_i64);
+
+        for policy in [MergeConflictPolicy::ScriptWins, MergeConflictPolicy::KeepCurrent] {
+            let mut target = ScopeState { vars: snapshot.clone(), ..Default::default() };
+            merge_scope_changes(&snapshot, &result, &mut target, policy).unwrap();
+            assert_eq!(target.get_value::<i64>("limit"), Some(1));
+            assert_eq!(target.is_constant("limit"), Some(false));
+            assert_eq!(target.len(), 1);
+        }
+
+        let mut target = ScopeState { vars: snapshot.clone(), ..Default::default() };
+        let err = merge_scope_changes(&snapshot, &result, &mut target, MergeConflictPolicy::Fail).unwrap_err();
+        assert!(err.contains("limit"), "{}", err);
+    }
+
+    #[test]
+    fn test_merge_checks_current_target_type_locks() {
+        let mut snapshot = Scope::new();
+        snapshot.push("a", 1_i64);
+        snapshot.push("b", 2_i64);
+
+        let mut result = snapshot.clone();
+        *result.get_mut("a").unwrap() = Dynamic::from(10_i64);
+        *result.get_mut("b").unwrap() = Dynamic::from("text");
+
+        // `b` was locked after the eval started
+        let mut target = ScopeState { vars: snapshot.clone(), ..Default::default() };
+        target.type_locks.insert("b".to_string(), TypeLock::Int);
+
+        let err = merge_scope_changes(&snapshot, &result, &mut target, MergeConflictPolicy::ScriptWins).unwrap_err();
+        assert!(err.contains("'b' is locked to int, but was assigned string"), "{}", err);
+        assert_eq!(target.get_value::<i64>("a"), Some(1));
+        assert_eq!(target.get_value::<i64>("b"), Some(2));
+    }
+
+    #[test]
+    fn test_savepoint_rollback_and_release() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let scope = rhai_scope_new();
+
+        assert_eq!(eval_in(engine, scope, "let step = 1;").unwrap(), "null");
+
+        let mut first = 0_i64;
+        assert_eq!(rhai_scope_savepoint(engine, scope, &mut first), 0);
+        assert_eq!(eval_in(engine, scope, "step = 2; let extra = true;").unwrap(), "null");
+
+        let mut second = 0_i64;
+        assert_eq!(rhai_scope_savepoint(engine, scope, &mut second), 0);
+        assert_eq!(eval_in(engine, scope, "step = 3;").unwrap(), "null");
+
+        // Rolling back to the first savepoint discards the second one
+        assert_eq!(rhai_scope_rollback(engine, scope, first), 0);
+        assert_eq!(get_var(scope, "step").unwrap(), "1");
+        assert!(get_var(scope, "extra").is_none());
+        assert_eq!(rhai_scope_rollback(engine, scope, second), -1);
+
+        // The first savepoint can be rolled back to again, until released
+        assert_eq!(eval_in(engine, scope, "step = 4;").unwrap(), "null");
+        assert_eq!(rhai_scope_rollback(engine, scope, first), 0);
+        assert_eq!(get_var(scope, "step").unwrap(), "1");
+
+        assert_eq!(rhai_scope_release(engine, scope, first), 0);
+        assert_eq!(rhai_scope_rollback(engine, scope, first), -1);
+
+        rhai_scope_free(scope);
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_savepoints_are_per_scope() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let scope = rhai_scope_new();
+
+        let mut savepoint = 0_i64;
+        assert_eq!(rhai_scope_savepoint(engine, scope, &mut savepoint), 0);
+
+        // The engine's own scope does not know the handle's savepoint
+        assert_eq!(rhai_scope_rollback(engine, std::ptr::null(), savepoint), -1);
+        assert_eq!(rhai_scope_rollback(engine, scope, savepoint), 0);
+
+        rhai_scope_free(scope);
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_scope_export_import_roundtrip() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let scope = rhai_scope_new();
+
+        let name = CString::new("RATE").unwrap();
+        let value = CString::new("1.5").unwrap();
+        assert_eq!(rhai_scope_set_constant(scope, name.as_ptr(), value.as_ptr()), 0);
+        assert_eq!(eval_in(engine, scope, "let cart = #{ items: [1, 2] }; let n = 1; let n = 2;").unwrap(), "null");
+
+        let mut export_ptr: *mut c_char = std::ptr::null_mut();
+        assert_eq!(rhai_scope_export(engine, scope, &mut export_ptr), 0);
+        let export_json = unsafe { CString::from_raw(export_ptr) };
+
+        let export: ScopeExport = serde_json::from_str(export_json.to_str().unwrap()).unwrap();
+        assert_eq!(export.version, SCOPE_EXPORT_VERSION);
+        assert_eq!(export.variables.len(), 3);
+        assert!(export.variables[0].constant);
+        assert_eq!(export.variables[2].name, "n");
+        assert_eq!(export.variables[2].value, serde_json::json!(2));
+        assert!(export.errors.is_empty());
+
+        let restored = rhai_scope_new();
+        assert_eq!(rhai_scope_import(engine, restored, export_json.as_ptr()), 0);
+        assert_eq!(eval_in(engine, restored, "cart.items[1] + n").unwrap(), "4");
+        assert!(eval_in(engine, restored, "RATE = 2.0;").is_err());
+
+        rhai_scope_free(restored);
+        rhai_scope_free(scope);
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_scope_export_reports_unsupported_values() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let scope = rhai_scope_new();
+
+        assert_eq!(eval_in(engine, scope, "let f = Fn(\"to_json\"); let ok = 1;").unwrap(), "null");
+
+        let export = export_scope(&unsafe { &*scope }.scope());
+        assert_eq!(export.variables.len(), 1);
+        assert_eq!(export.variables[0].name, "ok");
+        assert_eq!(export.errors.len(), 1);
+        assert_eq!(export.errors[0].name, "f");
+
+        rhai_scope_free(scope);
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_scope_import_rejects_bad_documents() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let scope = rhai_scope_new();
+        let name = CString::new("keep").unwrap();
+        let value = CString::new("1").unwrap();
+        assert_eq!(rhai_scope_set_var(scope, name.as_ptr(), value.as_ptr(), std::ptr::null()), 0);
+
+        let wrong_version = CString::new(r#"{"version": 99, "variables": []}"#).unwrap();
+        assert_eq!(rhai_scope_import(engine, scope, wrong_version.as_ptr()), -1);
+
+        let malformed = CString::new("not json").unwrap();
+        assert_eq!(rhai_scope_import(engine, scope, malformed.as_ptr()), -1);
+
+        assert_eq!(get_var(scope, "keep").unwrap(), "1");
+
+        rhai_scope_free(scope);
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_diff_scopes() {
+        let mut before = Scope::new();
+        before.push("same", 1_i64);
+        before.push("changed", 1_i64);
+        before.push("gone", 1_i64);
+
+        let mut after = Scope::new();
+        after.push("same", 1_i64);
+        after.push("changed", 2_i64);
+        after.push("new", "hello".to_string());
+        after.push("new", "shadowed".to_string());
+
+        let changes = diff_scopes(&before, &after);
+        assert_eq!(changes.added.len(), 1);
+        assert_eq!(changes.added["new"], serde_json::json!("shadowed"));
+        assert_eq!(changes.modified.len(), 1);
+        assert_eq!(changes.modified["changed"], serde_json::json!(2));
+        assert_eq!(changes.removed, vec!["gone".to_string()]);
+
+        assert!(diff_scopes(&before, &before.clone()).is_empty());
+    }
+
+    #[test]
+    fn test_eval_async_in_scope() {
+        use crate::async_eval::{rhai_eval_async_start_in_scope, rhai_eval_async_poll};
+
+        let engine = rhai_engine_new(std::ptr::null());
+        let scope = rhai_scope_new();
+
+        let name = CString::new("base").unwrap();
+        let value = CString::new("40").unwrap();
+        assert_eq!(rhai_scope_set_var(scope, name.as_ptr(), value.as_ptr(), std::ptr::null()), 0);
+
+        let script = CString::new("base + 2").unwrap();
+        let mut eval_id = 0_i64;
+        assert_eq!(rhai_eval_async_start_in_scope(engine, scope, script.as_ptr(), &mut eval_id), 0);
+
+        let mut status = 0_i32;
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        for _ in 0..500 {
+            assert_eq!(rhai_eval_async_poll(eval_id, &mut status, &mut result_ptr), 0);
+            if status != 0 {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        assert_eq!(status, 1);
+        unsafe {
+            assert_eq!(CString::from_raw(result_ptr).into_string().unwrap(), "42");
+        }
+
+        rhai_scope_free(scope);
+        rhai_engine_free(engine);
+    }
+}
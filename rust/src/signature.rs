@@ -0,0 +1,473 @@
+//! Typed signatures for registered Dart functions
+//!
+//! A signature declares the parameter types of a Dart function, which trailing
+//! parameters are optional, and the type of its result. It is passed as JSON:
+//!
+//! ```json
+//! {
+//!   "params": [
+//!     {"name": "url", "type": "string"},
+//!     {"name": "retries", "type": "int", "optional": true}
+//!   ],
+//!   "returns": "map"
+//! }
+//! ```
+//!
+//! A `rest` parameter, e.g. `"rest": {"name": "parts", "type": "string"}`, makes
+//! the function variadic: it takes any number of further arguments of the rest
+//! type.
+//!
+//! Types use the type lock vocabulary (`int`, `float`, `number`, `string`,
+//! `bool`, `array`, `map`, or a JSON schema object), plus `any` for values of
+//! any type. A parameter without a `type`, or a signature without `returns`,
+//! accepts anything.
+//!
+//! Only the arities the signature allows are registered, and parameters with a
+//! concrete Rhai type are registered with it, so Rhai's overload resolution
+//! sees the real types. Arguments are checked before Dart is called, and the
+//! result after it returns; mismatches fail the call at its position, naming
+//! the parameter and the expected type.
+
+use crate::type_locks::TypeLock;
+use rhai::Dynamic;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::any::TypeId;
+use std::ops::RangeInclusive;
+
+/// Number of arguments variadic functions are registered for, one overload per arity.
+///
+/// Rhai only matches `Dynamic` parameters against the first 16 arguments of a
+/// call, so wider overloads could never be found; longer calls to variadic
+/// functions are dispatched by `install_variadic_dispatch` instead.
+pub(crate) const MAX_REGISTERED_VARIADIC_ARGS: usize = 16;
+
+/// A parameter of a typed Dart function.
+#[derive(Debug, Clone)]
+pub(crate) struct ParamSpec {
+    /// Name of the parameter, for error messages
+    name: Option<String>,
+
+    /// Type the argument must have, or None for any type
+    ty: Option<TypeLock>,
+
+    /// Whether the argument may be left out
+    optional: bool,
+}
+
+/// The signature of a typed Dart function.
+#[derive(Debug, Clone)]
+pub(crate) struct FunctionSignature {
+    /// The parameters, required ones first
+    params: Vec<ParamSpec>,
+
+    /// The parameter further arguments are checked against, if variadic
+    rest: Option<ParamSpec>,
+
+    /// Type the result must have, or None for any type
+    returns: Option<TypeLock>,
+}
+
+/// A parameter as described in signature JSON.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ParamJson {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default, rename = "type")]
+    ty: Option<JsonValue>,
+    #[serde(default)]
+    optional: bool,
+}
+
+/// A signature as described in signature JSON.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SignatureJson {
+    #[serde(default)]
+    params: Vec<ParamJson>,
+    #[serde(default)]
+    rest: Option<ParamJson>,
+    #[serde(default)]
+    returns: Option<JsonValue>,
+}
+
+/// Parses a signature type: a type lock mode, `any`, or a JSON schema object.
+fn parse_type(ty: Option<JsonValue>) -> Result<Option<TypeLock>, String> {
+    match ty {
+        None => Ok(None),
+        Some(JsonValue::String(mode)) if mode.trim() == "any" => Ok(None),
+        Some(JsonValue::String(mode)) => TypeLock::parse(&mode).map(Some),
+        Some(schema @ JsonValue::Object(_)) => Ok(Some(TypeLock::Schema(schema))),
+        Some(other) => Err(format!("Invalid signature type: {}", other)),
+    }
+}
+
+/// Describes a signature type as in signature JSON.
+fn type_json(ty: &Option<TypeLock>) -> JsonValue {
+    match ty {
+        None => JsonValue::from("any"),
+        Some(TypeLock::Schema(schema)) => schema.clone(),
+        Some(ty) => JsonValue::from(ty.describe()),
+    }
+}
+
+impl ParamSpec {
+    /// Describes the parameter as in signature JSON.
+    fn to_json(&self) -> JsonValue {
+        serde_json::json!({
+            "name": self.name,
+            "type": type_json(&self.ty),
+            "optional": self.optional,
+        })
+    }
+}
+
+impl FunctionSignature {
+    /// Parses a signature from JSON.
+    ///
+    /// # Returns
+    ///
+    /// The signature, or an error message if it is malformed
+    pub(crate) fn parse(json: &str) -> Result<Self, String> {
+        let parsed: SignatureJson = serde_json::from_str(json)
+            .map_err(|e| format!("Invalid function signature: {}", e))?;
+
+        let mut params = Vec::with_capacity(parsed.params.len());
+        for param in parsed.params {
+            if !param.optional && params.last().is_some_and(|last: &ParamSpec| last.optional) {
+                return Err("Required parameters must come before optional ones".to_string());
+            }
+            params.push(ParamSpec {
+                name: param.name,
+                ty: parse_type(param.ty)?,
+                optional: param.optional,
+            });
+        }
+
+        let rest = match parsed.rest {
+            Some(rest) => Some(ParamSpec {
+                name: rest.name,
+                ty: parse_type(rest.ty)?,
+                optional: true,
+            }),
+            None => None,
+        };
+        Ok(Self {
+            params,
+            rest,
+            returns: parse_type(parsed.returns)?,
+        })
+    }
+
+    /// Describes the signature as signature JSON, with every type spelled out.
+    pub(crate) fn to_json(&self) -> JsonValue {
+        serde_json::json!({
+            "params": self.params.iter().map(ParamSpec::to_json).collect::<Vec<_>>(),
+            "rest": self.rest.as_ref().map(ParamSpec::to_json),
+            "returns": type_json(&self.returns),
+        })
+    }
+
+    /// Creates the signature of an untyped variadic function.
+    pub(crate) fn variadic() -> Self {
+        Self {
+            params: Vec::new(),
+            rest: Some(ParamSpec { name: None, ty: None, optional: true }),
+            returns: None,
+        }
+    }
+
+    /// Gets the numbers of arguments the function is registered for.
+    ///
+    /// Variadic functions are registered up to `MAX_REGISTERED_VARIADIC_ARGS`.
+    pub(crate) fn arities(&self) -> RangeInclusive<usize> {
+        let required = self.required_count();
+        if self.rest.is_some() {
+            required..=self.params.len().max(MAX_REGISTERED_VARIADIC_ARGS)
+        } else {
+            required..=self.params.len()
+        }
+    }
+
+    /// Gets the number of required parameters.
+    pub(crate) fn required_count(&self) -> usize {
+        self.params.iter().filter(|param| !param.optional).count()
+    }
+
+    /// Checks whether the function takes any number of further arguments.
+    pub(crate) fn is_variadic(&self) -> bool {
+        self.rest.is_some()
+    }
+
+    /// Gets the parameter an argument is checked against.
+    fn param_at(&self, index: usize) -> Option<&ParamSpec> {
+        self.params.get(index).or(self.rest.as_ref())
+    }
+
+    /// Gets the Rhai parameter types for a call with `arity` arguments.
+    ///
+    /// Types Rhai can't express as one Rust type (`number`, schemas, `any`)
+    /// are registered as `Dynamic` and checked when called.
+    pub(crate) fn arg_type_ids(&self, arity: usize) -> Vec<TypeId> {
+        (0..arity)
+            .filter_map(|index| self.param_at(index))
+            .map(|param| match param.ty {
+                Some(TypeLock::Int) => TypeId::of::<rhai::INT>(),
+                Some(TypeLock::Float) => TypeId::of::<rhai::FLOAT>(),
+                Some(TypeLock::String) => TypeId::of::<rhai::ImmutableString>(),
+                Some(TypeLock::Bool) => TypeId::of::<bool>(),
+                Some(TypeLock::Array) => TypeId::of::<rhai::Array>(),
+                Some(TypeLock::Map) => TypeId::of::<rhai::Map>(),
+                _ => TypeId::of::<Dynamic>(),
+            })
+            .collect()
+    }
+
+    /// Checks the number of arguments of a call.
+    ///
+    /// Direct calls with other numbers of arguments don't find the function;
+    /// calls made without Rhai's function lookup, such as deferred calls, are
+    /// checked with this.
+    ///
+    /// # Returns
+    ///
+    /// Ok, or a message describing the mismatch
+    pub(crate) fn check_arg_count(&self, function_name: &str, count: usize) -> Result<(), String> {
+        let required = self.required_count();
+        if count < required {
+            return Err(format!("Function '{}' takes at least {} arguments, got {}", function_name, required, count));
+        }
+        if self.rest.is_none() && count > self.params.len() {
+            return Err(format!(
+                "Function '{}' takes at most {} arguments, got {}",
+                function_name, self.params.len(), count
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks the arguments of a call.
+    ///
+    /// # Returns
+    ///
+    /// Ok, or a message naming the first mismatching parameter
+    pub(crate) fn check_args(&self, function_name: &str, args: &[Dynamic]) -> Result<(), String> {
+        for (index, arg) in args.iter().enumerate() {
+            let Some(param) = self.param_at(index) else {
+                break;
+            };
+            if let Some(ty) = &param.ty {
+                ty.check(arg).map_err(|e| {
+                    let param_name = match &param.name {
+                        Some(name) => format!("parameter {} ({})", index + 1, name),
+                        None => format!("parameter {}", index + 1),
+                    };
+                    format!("Function '{}' {}: {}", function_name, param_name, e)
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the result of a call.
+    ///
+    /// # Returns
+    ///
+    /// Ok, or a message describing the mismatch
+    pub(crate) fn check_result(&self, function_name: &str, result: &Dynamic) -> Result<(), String> {
+        match &self.returns {
+            Some(ty) => ty.check(result)
+                .map_err(|e| format!("Function '{}' result: {}", function_name, e)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{rhai_engine_free, rhai_engine_new, rhai_eval};
+    use crate::error::{rhai_free_error, rhai_get_last_error};
+    use crate::functions::rhai_register_function_with_signature;
+    use std::ffi::{c_char, CStr, CString};
+
+    #[test]
+    fn test_parse_signature() {
+        let signature = FunctionSignature::parse(r#"{
+            "params": [
+                {"name": "url", "type": "string"},
+                {"name": "retries", "type": "int", "optional": true},
+                {"name": "body", "optional": true}
+            ],
+            "returns": "map"
+        }"#).unwrap();
+        assert_eq!(signature.arities(), 1..=3);
+        assert_eq!(signature.arg_type_ids(2), vec![
+            TypeId::of::<rhai::ImmutableString>(),
+            TypeId::of::<rhai::INT>(),
+        ]);
+
+        // Exported signatures parse back to the same signature
+        let exported = signature.to_json();
+        assert_eq!(exported["params"][2]["type"], "any");
+        assert_eq!(exported["returns"], "map");
+        assert_eq!(FunctionSignature::parse(&exported.to_string()).unwrap().to_json(), exported);
+
+        let err = FunctionSignature::parse(r#"{"params": [{"optional": true}, {"type": "int"}]}"#).unwrap_err();
+        assert!(err.contains("before optional"), "{}", err);
+        assert!(FunctionSignature::parse(r#"{"params": [{"type": "decimal"}]}"#).is_err());
+        assert!(FunctionSignature::parse(r#"{"parameters": []}"#).is_err());
+    }
+
+    extern "C" fn answer_hello(_: i64, _: *const c_char) -> *mut c_char {
+        let response = CString::new(r#"{"status":"success","value":"hello"}"#).unwrap();
+        unsafe { libc::strdup(response.as_ptr()) }
+    }
+
+    fn eval(engine: *const crate::types::CRhaiEngine, script: &str) -> Result<String, String> {
+        let script = CString::new(script).unwrap();
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        if rhai_eval(engine, script.as_ptr(), &mut result_ptr) == 0 {
+            return Ok(unsafe { CString::from_raw(result_ptr).into_string().unwrap() });
+        }
+        let error_ptr = rhai_get_last_error();
+        let error = unsafe { CStr::from_ptr(error_ptr) }.to_str().unwrap().to_string();
+        rhai_free_error(error_ptr);
+        Err(error)
+    }
+
+    #[test]
+    fn test_typed_function_checks_arguments_and_result() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("greet").unwrap();
+        let signature = CString::new(r#"{
+            "params": [{"name": "who", "type": "string"}, {"name": "times", "type": "int", "optional": true}],
+            "returns": "string"
+        }"#).unwrap();
+        assert_eq!(rhai_register_function_with_signature(engine, name.as_ptr(), 71, answer_hello, 0, signature.as_ptr()), 0);
+
+        let name = CString::new("count").unwrap();
+        let signature = CString::new(r#"{"params": [], "returns": "int"}"#).unwrap();
+        assert_eq!(rhai_register_function_with_signature(engine, name.as_ptr(), 72, answer_hello, 0, signature.as_ptr()), 0);
+
+        assert_eq!(eval(engine, r#"greet("a")"#).unwrap(), "\"hello\"");
+        assert_eq!(eval(engine, r#"greet("a", 2)"#).unwrap(), "\"hello\"");
+
+        // Mismatches fail before Dart is called, at the call's position
+        let err = eval(engine, "let x = 1;\ngreet(42)").unwrap_err();
+        assert!(err.contains("line 2"), "{}", err);
+        assert!(err.contains("Function 'greet' parameter 1 (who): expected string, got int"), "{}", err);
+
+        let err = eval(engine, r#"greet("a", "b")"#).unwrap_err();
+        assert!(err.contains("parameter 2 (times): expected int, got string"), "{}", err);
+
+        // Arities outside the signature are not registered
+        assert!(eval(engine, "greet()").unwrap_err().contains("not found"));
+        assert!(eval(engine, r#"greet("a", 1, 2)"#).unwrap_err().contains("not found"));
+
+        let err = eval(engine, "count()").unwrap_err();
+        assert!(err.contains("Function 'count' result: expected int, got string"), "{}", err);
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_deferred_calls_are_checked() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("greet").unwrap();
+        let signature = CString::new(r#"{
+            "params": [{"name": "who", "type": "string"}, {"name": "times", "type": "int", "optional": true}],
+            "returns": "string"
+        }"#).unwrap();
+        assert_eq!(rhai_register_function_with_signature(engine, name.as_ptr(), 76, answer_hello, 0, signature.as_ptr()), 0);
+
+        let name = CString::new("count").unwrap();
+        let signature = CString::new(r#"{"params": [], "returns": "int"}"#).unwrap();
+        assert_eq!(rhai_register_function_with_signature(engine, name.as_ptr(), 77, answer_hello, 0, signature.as_ptr()), 0);
+
+        assert_eq!(
+            eval(engine, r#"await_all([defer("greet", ["a"]), defer("greet", ["a", 2])]).map(|r| r.value)"#).unwrap(),
+            r#"["hello","hello"]"#,
+        );
+
+        // Mismatches fail await_all() at its position
+        let err = eval(engine, "let calls = [defer(\"greet\", [42])];\nawait_all(calls)").unwrap_err();
+        assert!(err.contains("line 2"), "{}", err);
+        assert!(err.contains("Function 'greet' parameter 1 (who): expected string, got int"), "{}", err);
+
+        let err = eval(engine, r#"await_all([defer("greet")])"#).unwrap_err();
+        assert!(err.contains("Function 'greet' takes at least 1 arguments, got 0"), "{}", err);
+        let err = eval(engine, r#"await_all([defer("greet", ["a", 1, 2])])"#).unwrap_err();
+        assert!(err.contains("Function 'greet' takes at most 2 arguments, got 3"), "{}", err);
+
+        let err = eval(engine, "let calls = [defer(\"count\")];\nawait_all(calls)").unwrap_err();
+        assert!(err.contains("line 2"), "{}", err);
+        assert!(err.contains("Function 'count' result: expected int, got string"), "{}", err);
+
+        rhai_engine_free(engine);
+    }
+
+    /// Answers with the arguments it was called with.
+    extern "C" fn echo_args(_: i64, args_json: *const c_char) -> *mut c_char {
+        let args_json = unsafe { CStr::from_ptr(args_json) }.to_str().unwrap();
+        let response = serde_json::json!({ "status": "success", "value_json": args_json }).to_string();
+        let response = CString::new(response).unwrap();
+        unsafe { libc::strdup(response.as_ptr()) }
+    }
+
+    #[test]
+    fn test_variadic_function_takes_any_number_of_arguments() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("concat").unwrap();
+        assert_eq!(crate::functions::rhai_register_variadic_function(engine, name.as_ptr(), 74, echo_args, 0), 0);
+
+        for count in [0, 10, 11, 50] {
+            let args: Vec<String> = (1..=count).map(|n| n.to_string()).collect();
+            let script = format!("concat({})", args.join(", "));
+            assert_eq!(eval(engine, &script).unwrap(), format!("[{}]", args.join(",")));
+        }
+
+        // Long calls with mixed types reach Dart as well
+        let args: Vec<String> = (1..=50).map(|n| if n % 2 == 0 { format!("\"{}\"", n) } else { n.to_string() }).collect();
+        let script = format!("concat({})", args.join(", "));
+        assert_eq!(eval(engine, &script).unwrap(), format!("[{}]", args.join(",")));
+
+        // Rest parameters are type-checked like the others
+        let name = CString::new("join").unwrap();
+        let signature = CString::new(r#"{
+            "params": [{"name": "separator", "type": "string"}],
+            "rest": {"name": "parts", "type": "string"}
+        }"#).unwrap();
+        assert_eq!(rhai_register_function_with_signature(engine, name.as_ptr(), 75, echo_args, 0, signature.as_ptr()), 0);
+        assert_eq!(eval(engine, r#"join("-", "a", "b")"#).unwrap(), r#"["-","a","b"]"#);
+        let err = eval(engine, r#"join("-", "a", 3)"#).unwrap_err();
+        assert!(err.contains("parameter 3 (parts): expected string, got int"), "{}", err);
+
+        let parts = vec!["\"a\""; 20].join(", ");
+        let err = eval(engine, &format!(r#"join("-", {}, 3)"#, parts)).unwrap_err();
+        assert!(err.contains("parameter 22 (parts): expected string, got int"), "{}", err);
+
+        // Long calls report their own position, also when nested
+        let script = format!("let x = 1;\njoin(\"-\", {p},\n    join(\"-\", {p}).len().to_string(), 3)", p = parts);
+        let err = eval(engine, &script).unwrap_err();
+        assert!(err.contains("line 2"), "{}", err);
+        assert!(err.contains("parameter 23 (parts): expected string, got int"), "{}", err);
+        let script = format!("join(\"-\", {p},\n    join(\"-\", {p}, 3))", p = parts);
+        let err = eval(engine, &script).unwrap_err();
+        assert!(err.contains("line 2"), "{}", err);
+        assert!(eval(engine, "join()").unwrap_err().contains("not found"));
+
+        rhai_engine_free(engine);
+    }
+
+    #[test]
+    fn test_invalid_signature_is_rejected() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("broken").unwrap();
+        let signature = CString::new(r#"{"params": [{"type": "decimal"}]}"#).unwrap();
+        assert_eq!(rhai_register_function_with_signature(engine, name.as_ptr(), 73, answer_hello, 0, signature.as_ptr()), -1);
+        assert!(eval(engine, "broken(1)").unwrap_err().contains("not found"));
+        rhai_engine_free(engine);
+    }
+}
@@ -0,0 +1,509 @@
+//! Type locks for scope variables
+//!
+//! This module lets Dart pin the type of a scope variable. A locked variable
+//! must keep its type across evals: if a script assigns a value of another type,
+//! the eval fails with a runtime error naming the variable and both types, and
+//! the scope is restored to its state before the eval.
+//!
+//! Rhai has no assignment hook, so while an eval runs, the debugger checks the
+//! locks before each top-level statement (see the `debugger` module) and
+//! reports a violation at the statement that made it. Locks are checked again
+//! when the eval finishes, which covers its last statement, before its result
+//! or any scope write-back is reported. Schema locks need the value converted
+//! to JSON, so they are only checked then.
+
+use crate::engine::format_rhai_error;
+use crate::types::ScopeState;
+use crate::values::dynamic_to_json_value;
+use rhai::{Dynamic, EvalAltResult, Position, Scope};
+use serde_json::Value as JsonValue;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr};
+
+/// Type locks of a scope, keyed by variable name.
+pub(crate) type TypeLocks = HashMap<String, TypeLock>;
+
+/// The type a locked variable must keep.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeLock {
+    /// Integer (`i64`)
+    Int,
+    /// Floating-point number (`f64`)
+    Float,
+    /// Integer or floating-point number
+    Number,
+    /// String
+    String,
+    /// Boolean
+    Bool,
+    /// Array
+    Array,
+    /// Object map
+    Map,
+    /// A JSON schema the value's JSON representation must match.
+    ///
+    /// Supported keywords: `type`, `enum`, `properties`, `required`, `items`.
+    Schema(JsonValue),
+}
+
+impl TypeLock {
+    /// Parses a type lock mode.
+    ///
+    /// Accepts `int`, `float`, `number`, `string`, `bool`, `array`, `map`,
+    /// or an inline JSON schema object (a string starting with `{`).
+    pub fn parse(mode: &str) -> Result<Self, String> {
+        let mode = mode.trim();
+        match mode {
+            "int" => Ok(Self::Int),
+            "float" => Ok(Self::Float),
+            "number" => Ok(Self::Number),
+            "string" => Ok(Self::String),
+            "bool" => Ok(Self::Bool),
+            "array" => Ok(Self::Array),
+            "map" => Ok(Self::Map),
+            _ if mode.starts_with('{') => {
+                let schema: JsonValue = serde_json::from_str(mode)
+                    .map_err(|e| format!("Invalid JSON schema in type lock: {}", e))?;
+                Ok(Self::Schema(schema))
+            }
+            _ => Err(format!("Unknown type lock mode: {}", mode)),
+        }
+    }
+
+    /// Returns the name of the locked type, for error messages.
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Int => "int".to_string(),
+            Self::Float => "float".to_string(),
+            Self::Number => "number".to_string(),
+            Self::String => "string".to_string(),
+            Self::Bool => "bool".to_string(),
+            Self::Array => "array".to_string(),
+            Self::Map => "map".to_string(),
+            Self::Schema(schema) => match schema.get("type") {
+                Some(JsonValue::String(t)) => format!("schema ({})", t),
+                _ => "schema".to_string(),
+            },
+        }
+    }
+
+    /// Checks a value against this lock.
+    ///
+    /// # Returns
+    ///
+    /// Ok if the value satisfies the lock, or a description of the mismatch
+    pub fn check(&self, value: &Dynamic) -> Result<(), String> {
+        let matches = match self {
+            Self::Int => value.is_int(),
+            Self::Float => value.is_float(),
+            Self::Number => value.is_int() || value.is_float(),
+            Self::String => value.is_string(),
+            Self::Bool => value.is_bool(),
+            Self::Array => value.is_array(),
+            Self::Map => value.is_map(),
+            Self::Schema(schema) => {
+                let json = dynamic_to_json_value(value)?;
+                return validate_schema(schema, &json, "$");
+            }
+        };
+
+        if matches {
+            Ok(())
+        } else {
+            Err(format!("expected {}, got {}", self.describe(), value_type_name(value)))
+        }
+    }
+}
+
+/// Returns the type name of a value in type lock vocabulary.
+pub fn value_type_name(value: &Dynamic) -> String {
+    if value.is_unit() {
+        "null".to_string()
+    } else if value.is_int() {
+        "int".to_string()
+    } else if value.is_float() {
+        "float".to_string()
+    } else if value.is_string() {
+        "string".to_string()
+    } else if value.is_bool() {
+        "bool".to_string()
+    } else if value.is_array() {
+        "array".to_string()
+    } else if value.is_map() {
+        "map".to_string()
+    } else {
+        value.type_name().to_string()
+    }
+}
+
+/// Finds the first locked variable in a scope that breaks its lock.
+///
+/// Variables that are locked but not (or no longer) in the scope are ignored.
+///
+/// # Returns
+///
+/// A description of the violation naming the variable, or None if all locks hold
+fn type_lock_violation(locks: &TypeLocks, scope: &Scope<'_>) -> Option<String> {
+    sorted_locks(locks)
+        .into_iter()
+        .find_map(|(name, lock)| scope.get(name).and_then(|value| lock_violation(name, lock, value)))
+}
+
+/// Returns the locks sorted by variable name, so the reported violation is
+/// deterministic.
+fn sorted_locks(locks: &TypeLocks) -> Vec<(&String, &TypeLock)> {
+    let mut sorted: Vec<_> = locks.iter().collect();
+    sorted.sort_by(|(a, _), (b, _)| a.cmp(b));
+    sorted
+}
+
+/// Checks the value of one locked variable.
+///
+/// # Returns
+///
+/// A description of the violation, or None if the lock holds
+fn lock_violation(name: &str, lock: &TypeLock, value: &Dynamic) -> Option<String> {
+    let reason = lock.check(value).err()?;
+
+    Some(match lock {
+        TypeLock::Schema(_) => format!(
+            "Type lock violation: variable '{}' no longer matches its locked {}: {}",
+            name,
+            lock.describe(),
+            reason
+        ),
+        _ => format!(
+            "Type lock violation: variable '{}' is locked to {}, but was assigned {}",
+            name,
+            lock.describe(),
+            value_type_name(value)
+        ),
+    })
+}
+
+/// Checks every locked variable in a scope against its lock.
+///
+/// Variables that are locked but not (or no longer) in the scope are ignored.
+/// The position is the statement to blame for a violation, if known.
+///
+/// # Returns
+///
+/// Ok if all locks hold, or a runtime error naming the first violating variable
+pub(crate) fn check_type_locks(locks: &TypeLocks, scope: &Scope<'_>, position: Position) -> Result<(), String> {
+    match type_lock_violation(locks, scope) {
+        Some(violation) => Err(format_rhai_error(&EvalAltResult::ErrorRuntime(violation.into(), position))),
+        None => Ok(()),
+    }
+}
+
+/// Checks a value about to be stored in a variable against the variable's lock.
+///
+/// # Returns
+///
+/// Ok if the variable is not locked or the value satisfies its lock, or a
+/// runtime error naming the variable
+pub(crate) fn check_type_lock(locks: &TypeLocks, name: &str, value: &Dynamic) -> Result<(), String> {
+    match locks.get(name).and_then(|lock| lock_violation(name, lock, value)) {
+        Some(violation) => Err(format_rhai_error(&EvalAltResult::ErrorRuntime(violation.into(), Position::NONE))),
+        None => Ok(()),
+    }
+}
+
+/// Type locks of the eval running on this thread.
+struct LiveTypeLocks {
+    /// Locks checked before each statement, sorted by name, without schema locks
+    locks: Vec<(String, TypeLock)>,
+
+    /// Position of the last top-level statement the eval started
+    last_statement: Position,
+}
+
+thread_local! {
+    /// Type locks checked while the eval on this thread runs, if it has any.
+    static LIVE_TYPE_LOCKS: RefCell<Option<LiveTypeLocks>> = const { RefCell::new(None) };
+}
+
+/// Keeps the live type locks of one eval.
+///
+/// Restores the previous ones when dropped, so an eval started from inside a
+/// Dart callback does not check the locks of the eval waiting on that callback.
+pub(crate) struct LiveTypeLockGuard {
+    previous: Option<LiveTypeLocks>,
+}
+
+impl LiveTypeLockGuard {
+    /// Starts checking the given locks while an eval runs on this thread.
+    pub(crate) fn begin(locks: &TypeLocks) -> Self {
+        let live = (!locks.is_empty()).then(|| LiveTypeLocks {
+            locks: sorted_locks(locks)
+                .into_iter()
+                .filter(|(_, lock)| !matches!(lock, TypeLock::Schema(_)))
+                .map(|(name, lock)| (name.clone(), lock.clone()))
+                .collect(),
+            last_statement: Position::NONE,
+        });
+        let previous = LIVE_TYPE_LOCKS.with(|slot| slot.replace(live));
+        Self { previous }
+    }
+
+    /// Stops checking the locks once the eval is over.
+    ///
+    /// # Returns
+    ///
+    /// The position of the last top-level statement the eval started, to blame
+    /// for a violation found when it finished
+    pub(crate) fn finish(self) -> Position {
+        LIVE_TYPE_LOCKS.with(|slot| slot.borrow().as_ref().map_or(Position::NONE, |live| live.last_statement))
+    }
+}
+
+impl Drop for LiveTypeLockGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        LIVE_TYPE_LOCKS.with(|slot| *slot.borrow_mut() = previous);
+    }
+}
+
+/// Checks whether the eval running on this thread has live type locks.
+pub(crate) fn has_live_type_locks() -> bool {
+    LIVE_TYPE_LOCKS.with(|slot| slot.borrow().is_some())
+}
+
+/// Checks the live type locks before a top-level statement runs.
+///
+/// The scope then holds the effects of every statement before this one, so a
+/// violation is reported at the previous statement. Schema locks are left to
+/// the check when the eval finishes.
+///
+/// # Returns
+///
+/// Ok if all locks hold, or a runtime error naming the first violating variable
+pub(crate) fn check_live_type_locks(scope: &Scope<'_>, statement: Position) -> Result<(), Box<EvalAltResult>> {
+    LIVE_TYPE_LOCKS.with(|slot| {
+        let mut slot = slot.borrow_mut();
+        let Some(live) = slot.as_mut() else {
+            return Ok(());
+        };
+
+        let violation = live
+            .locks
+            .iter()
+            .find_map(|(name, lock)| scope.get(name).and_then(|value| lock_violation(name, lock, value)));
+        if let Some(violation) = violation {
+            return Err(EvalAltResult::ErrorRuntime(violation.into(), live.last_statement).into());
+        }
+        // Statements without a position, such as the empty one after a
+        // trailing `;`, have nothing to blame
+        if !statement.is_none() {
+            live.last_statement = statement;
+        }
+        Ok(())
+    })
+}
+
+/// Parses an optional type lock mode passed over FFI.
+///
+/// # Returns
+///
+/// None if the pointer is null, otherwise the parsed lock or an error message
+pub(crate) fn type_lock_from_c(lock: *const c_char) -> Result<Option<TypeLock>, String> {
+    if lock.is_null() {
+        return Ok(None);
+    }
+
+    let mode = unsafe { CStr::from_ptr(lock) }
+        .to_str()
+        .map_err(|e| format!("Invalid UTF-8 in type lock: {}", e))?;
+
+    TypeLock::parse(mode).map(Some)
+}
+
+/// Stores a variable or constant in a scope, enforcing its type lock.
+///
+/// The value is checked against the given lock, or against the lock already
+/// held by the variable if none is given. A given lock replaces any existing one.
+pub(crate) fn push_with_lock(
+    state: &mut ScopeState,
+    name: String,
+    value: Dynamic,
+    constant: bool,
+    lock: Option<TypeLock>,
+) -> Result<(), String> {
+    if let Some(effective) = lock.as_ref().or_else(|| state.type_locks.get(&name)) {
+        effective.check(&value).map_err(|reason| {
+            format!("Value for variable '{}' does not satisfy its type lock: {}", name, reason)
+        })?;
+    }
+
+    if constant {
+        state.vars.push_constant(name.clone(), value);
+    } else {
+        state.vars.push(name.clone(), value);
+    }
+
+    if let Some(lock) = lock {
+        state.type_locks.insert(name, lock);
+    }
+
+    Ok(())
+}
+
+/// Validates a JSON value against a (subset of) JSON schema.
+fn validate_schema(schema: &JsonValue, value: &JsonValue, path: &str) -> Result<(), String> {
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            JsonValue::String(t) => vec![t.as_str()],
+            JsonValue::Array(types) => types.iter().filter_map(|t| t.as_str()).collect(),
+            _ => return Err(format!("{}: schema 'type' must be a string or an array", path)),
+        };
+
+        if !allowed.iter().any(|t| json_type_matches(t, value)) {
+            return Err(format!(
+                "{}: expected {}, got {}",
+                path,
+                allowed.join(" or "),
+                json_type_name(value)
+            ));
+        }
+    }
+
+    if let Some(JsonValue::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            return Err(format!("{}: value is not one of the allowed values", path));
+        }
+    }
+
+    if let JsonValue::Object(map) = value {
+        if let Some(JsonValue::Array(required)) = schema.get("required") {
+            for key in required.iter().filter_map(|k| k.as_str()) {
+                if !map.contains_key(key) {
+                    return Err(format!("{}: missing required property '{}'", path, key));
+                }
+            }
+        }
+
+        if let Some(JsonValue::Object(properties)) = schema.get("properties") {
+            for (key, property_schema) in properties {
+                if let Some(property_value) = map.get(key) {
+                    validate_schema(property_schema, property_value, &format!("{}.{}", path, key))?;
+                }
+            }
+        }
+    }
+
+    if let (JsonValue::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (index, item) in items.iter().enumerate() {
+            validate_schema(item_schema, item, &format!("{}[{}]", path, index))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks a JSON value against a JSON schema type name.
+fn json_type_matches(expected: &str, value: &JsonValue) -> bool {
+    match expected {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => false,
+    }
+}
+
+/// Returns the JSON schema type name of a JSON value.
+fn json_type_name(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Null => "null",
+        JsonValue::Bool(_) => "boolean",
+        JsonValue::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        JsonValue::Number(_) => "number",
+        JsonValue::String(_) => "string",
+        JsonValue::Array(_) => "array",
+        JsonValue::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::values::json_to_rhai_dynamic;
+
+    #[test]
+    fn test_parse_modes() {
+        assert_eq!(TypeLock::parse("int").unwrap(), TypeLock::Int);
+        assert_eq!(TypeLock::parse(" map ").unwrap(), TypeLock::Map);
+        assert!(matches!(TypeLock::parse(r#"{"type": "object"}"#).unwrap(), TypeLock::Schema(_)));
+        assert!(TypeLock::parse("decimal").is_err());
+        assert!(TypeLock::parse("{not json").is_err());
+    }
+
+    #[test]
+    fn test_basic_locks() {
+        let int_value = Dynamic::from(1_i64);
+        let float_value = Dynamic::from(1.5_f64);
+        let string_value = Dynamic::from("x".to_string());
+
+        assert!(TypeLock::Int.check(&int_value).is_ok());
+        assert!(TypeLock::Int.check(&float_value).is_err());
+        assert!(TypeLock::Number.check(&int_value).is_ok());
+        assert!(TypeLock::Number.check(&float_value).is_ok());
+        assert!(TypeLock::Number.check(&string_value).is_err());
+        assert!(TypeLock::String.check(&string_value).is_ok());
+    }
+
+    #[test]
+    fn test_schema_lock() {
+        let lock = TypeLock::parse(r#"{
+            "type": "object",
+            "required": ["id"],
+            "properties": {
+                "id": { "type": "integer" },
+                "tags": { "type": "array", "items": { "type": "string" } }
+            }
+        }"#).unwrap();
+
+        let valid = json_to_rhai_dynamic(r#"{"id": 1, "tags": ["a"]}"#).unwrap();
+        assert!(lock.check(&valid).is_ok());
+
+        let missing = json_to_rhai_dynamic(r#"{"tags": []}"#).unwrap();
+        assert!(lock.check(&missing).unwrap_err().contains("missing required property 'id'"));
+
+        let bad_item = json_to_rhai_dynamic(r#"{"id": 1, "tags": ["a", 2]}"#).unwrap();
+        assert!(lock.check(&bad_item).unwrap_err().contains("$.tags[1]"));
+    }
+
+    #[test]
+    fn test_check_type_locks_message() {
+        let mut locks = TypeLocks::new();
+        locks.insert("total".to_string(), TypeLock::Int);
+
+        let mut scope = Scope::new();
+        scope.push("total", "oops".to_string());
+
+        let err = check_type_locks(&locks, &scope, Position::NONE).unwrap_err();
+        assert!(err.starts_with("Runtime error: Type lock violation"));
+        assert!(err.contains("'total'"));
+        assert!(err.contains("locked to int"));
+        assert!(err.contains("assigned string"));
+    }
+
+    #[test]
+    fn test_push_with_lock_keeps_existing_lock() {
+        let mut state = ScopeState::default();
+        push_with_lock(&mut state, "total".to_string(), Dynamic::from(1_i64), false, Some(TypeLock::Int)).unwrap();
+
+        // A later unlocked set must still respect the lock
+        let err = push_with_lock(&mut state, "total".to_string(), Dynamic::from("x".to_string()), false, None)
+            .unwrap_err();
+        assert!(err.contains("expected int, got string"));
+
+        // Mismatched initial values are rejected
+        assert!(push_with_lock(&mut state, "flag".to_string(), Dynamic::from(1_i64), false, Some(TypeLock::Bool)).is_err());
+        assert!(!state.vars.contains("flag"));
+    }
+}
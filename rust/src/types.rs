@@ -4,8 +4,63 @@
 //! All structs use #[repr(C)] to ensure consistent memory layout.
 
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicI64, Ordering};
 use rhai::{Engine, Scope};
 use std::ffi::c_char;
+use std::ops::{Deref, DerefMut};
+use crate::type_locks::TypeLocks;
+use crate::watch::ScopeWatch;
+use crate::resolver::{install_var_resolver, SharedResolver};
+use crate::definitions::{install_definition_policy, SharedDefinitionPolicy};
+use crate::async_eval::{install_cancellation_check, install_emit_function};
+use crate::debugger::install_debugger;
+use crate::functions::{
+    install_fan_out_functions, install_variadic_dispatch, register_engine_functions, snapshot_engine_functions,
+    EngineFunctions,
+};
+use crate::engine::EngineConfig;
+use crate::function_policy::FunctionPolicy;
+
+/// A variable scope shared between the FFI handle that owns it and any
+/// background evaluations that need to read from or write back to it.
+pub(crate) type SharedScope = Arc<Mutex<ScopeState>>;
+
+/// The variables of a scope together with its savepoints, type locks and watchers.
+///
+/// Dereferences to the underlying Rhai `Scope`, so it can be used wherever a
+/// scope is expected. The other state is kept next to the variables so that all
+/// of it is always updated under the same lock.
+#[derive(Default)]
+pub(crate) struct ScopeState {
+    /// The variables and constants visible to scripts
+    pub(crate) vars: Scope<'static>,
+
+    /// Saved copies of `vars`, oldest first, keyed by savepoint ID
+    pub(crate) savepoints: Vec<(i64, Scope<'static>)>,
+
+    /// Types that locked variables must keep across evals
+    pub(crate) type_locks: TypeLocks,
+
+    /// Watched variable names and the callback notified when they change
+    pub(crate) watch: Option<ScopeWatch>,
+}
+
+impl Deref for ScopeState {
+    type Target = Scope<'static>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.vars
+    }
+}
+
+impl DerefMut for ScopeState {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.vars
+    }
+}
+
+/// Atomic counter for generating unique engine IDs.
+static NEXT_ENGINE_ID: AtomicI64 = AtomicI64::new(1);
 
 /// Opaque handle for a Rhai engine instance.
 ///
@@ -18,33 +73,104 @@ use std::ffi::c_char;
 /// directly constructed or accessed from Rust code outside this crate.
 #[repr(C)]
 pub struct CRhaiEngine {
+    /// Unique ID of this engine, used to key its registered callbacks
+    pub(crate) engine_id: i64,
+
     /// The wrapped Rhai engine
+    /// Async evals hold clones of it, so it is replaced rather than mutated
+    /// while they run; see `rebuild_engine`
     pub(crate) inner: Arc<Engine>,
 
+    /// The Dart functions registered with `inner`
+    pub(crate) functions: EngineFunctions,
+
+    /// Rules for the Dart functions scripts may call
+    /// Applied when functions are added to the engine
+    pub(crate) function_policy: FunctionPolicy,
+
+    /// The configuration the engine was created with, kept to rebuild it
+    pub(crate) config: EngineConfig,
+
     /// Async callback timeout in seconds
     /// This is stored per-engine to allow different engines to have different timeouts
     pub(crate) async_timeout_seconds: u64,
 
     /// Variable scope for storing variables set from Dart
-    /// Wrapped in Mutex for thread-safe access from async eval
-    pub(crate) scope: Mutex<Scope<'static>>,
+    /// Wrapped in Arc<Mutex> for thread-safe access from async eval
+    pub(crate) scope: SharedScope,
+
+    /// Dart callback asked for variables that are not in scope
+    /// Shared with the engine's variable resolution hook
+    pub(crate) var_resolver: SharedResolver,
+
+    /// Rules for variables scripts may define
+    /// Shared with the engine's variable definition hook
+    pub(crate) definition_policy: SharedDefinitionPolicy,
 }
 
 impl CRhaiEngine {
-    /// Creates a new CRhaiEngine wrapping the given engine
-    pub(crate) fn new(engine: Engine, async_timeout_seconds: u64) -> Self {
+    /// Creates a new CRhaiEngine with an engine built from the given configuration
+    ///
+    /// Installs the variable resolution and definition hooks, which stay
+    /// inactive until a resolver is set with `rhai_set_var_resolver` or a policy
+    /// with `rhai_set_definition_policy`, the hook that aborts cancelled
+    /// async evals, the dispatch of long variadic calls, and the `emit()`,
+    /// `defer()` and `await_all()` functions.
+    pub(crate) fn new(config: EngineConfig) -> Self {
+        let engine_id = NEXT_ENGINE_ID.fetch_add(1, Ordering::SeqCst);
+        let var_resolver = SharedResolver::default();
+        let definition_policy = SharedDefinitionPolicy::default();
+        let functions = EngineFunctions::default();
+        let engine = build_engine(&config, &functions, &var_resolver, &definition_policy);
+
         Self {
+            engine_id,
             inner: Arc::new(engine),
-            async_timeout_seconds,
-            scope: Mutex::new(Scope::new()),
+            functions,
+            function_policy: FunctionPolicy::default(),
+            async_timeout_seconds: config.async_timeout_seconds(),
+            config,
+            scope: Arc::new(Mutex::new(ScopeState::default())),
+            var_resolver,
+            definition_policy,
         }
     }
 
+    /// Replaces the engine with a fresh one that has the Dart functions
+    /// currently in the callback registry that the function policy allows.
+    ///
+    /// Rhai cannot remove registered functions, so this is how functions are
+    /// unregistered or replaced, and how they are added while async evals hold
+    /// the engine. Async evals already running keep the engine they started
+    /// with, and with it the functions they could call when they started.
+    pub(crate) fn rebuild_engine(&mut self) {
+        self.functions = snapshot_engine_functions(self.engine_id, &self.function_policy);
+        let engine = build_engine(&self.config, &self.functions, &self.var_resolver, &self.definition_policy);
+        self.inner = Arc::new(engine);
+    }
+
     /// Gets a mutable reference to the scope
-    pub(crate) fn scope(&self) -> std::sync::MutexGuard<'_, Scope<'static>> {
+    pub(crate) fn scope(&self) -> std::sync::MutexGuard<'_, ScopeState> {
         self.scope.lock().unwrap()
     }
 
+    /// Resolves the scope an eval should use: the given scope handle, or the
+    /// engine's own scope when the handle pointer is null.
+    ///
+    /// The handle pointer must be null or valid.
+    pub(crate) fn scope_target(&self, scope: *const CRhaiScope) -> SharedScope {
+        if scope.is_null() {
+            self.scope.clone()
+        } else {
+            unsafe { &*scope }.inner.clone()
+        }
+    }
+
+    /// Gets the unique ID of this engine
+    pub(crate) fn engine_id(&self) -> i64 {
+        self.engine_id
+    }
+
     /// Gets a reference to the inner engine
     pub(crate) fn engine(&self) -> &Engine {
         &self.inner
@@ -56,6 +182,59 @@ impl CRhaiEngine {
     }
 }
 
+/// Builds a configured engine with the crate's hooks, built-in functions and
+/// the given Dart functions.
+fn build_engine(
+    config: &EngineConfig,
+    functions: &EngineFunctions,
+    var_resolver: &SharedResolver,
+    definition_policy: &SharedDefinitionPolicy,
+) -> Engine {
+    let mut engine = Engine::new();
+    config.apply_to_engine(&mut engine);
+
+    install_var_resolver(&mut engine, var_resolver.clone());
+    install_definition_policy(&mut engine, definition_policy.clone());
+    install_cancellation_check(&mut engine);
+    install_emit_function(&mut engine);
+    install_fan_out_functions(&mut engine, functions.clone());
+    install_variadic_dispatch(&mut engine, functions.clone());
+    install_debugger(&mut engine, functions.clone());
+    register_engine_functions(&mut engine, functions);
+    engine
+}
+
+/// Opaque handle for a named variable scope (execution context).
+///
+/// A scope handle holds its own set of variables and constants, independent of
+/// the engine's built-in scope. Many scope handles can be evaluated against the
+/// same engine, so per-user contexts can share one configured engine (and its
+/// registered functions) without sharing variables.
+///
+/// # Safety
+///
+/// This type is only accessed via FFI functions and should never be
+/// directly constructed or accessed from Rust code outside this crate.
+#[repr(C)]
+pub struct CRhaiScope {
+    /// The wrapped variable scope
+    pub(crate) inner: SharedScope,
+}
+
+impl CRhaiScope {
+    /// Creates a new, empty CRhaiScope
+    pub(crate) fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(ScopeState::default())),
+        }
+    }
+
+    /// Gets a mutable reference to the scope
+    pub(crate) fn scope(&self) -> std::sync::MutexGuard<'_, ScopeState> {
+        self.inner.lock().unwrap()
+    }
+}
+
 /// Configuration for creating a Rhai engine.
 ///
 /// This struct is passed across the FFI boundary to configure engine creation.
@@ -112,6 +291,32 @@ impl CRhaiConfig {
     }
 }
 
+/// Per-call options for evaluating a script.
+///
+/// This struct is passed across the FFI boundary to the `*_with_options` eval
+/// entry points. A null options pointer means "all defaults" (all fields 0).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CRhaiEvalOptions {
+    /// Whether to write the final scope of a successful async eval back to the
+    /// scope it was started from (bool as u8 for C compatibility)
+    pub merge_scope: u8,
+
+    /// How to resolve write-back conflicts, when a variable was changed both by
+    /// the async script and by another eval since the async eval started:
+    /// 0 = script value wins, 1 = keep the current value, 2 = fail the eval
+    pub merge_conflict: u8,
+
+    /// Whether to restore the scope to its state before the eval if the script
+    /// errors or times out (bool as u8 for C compatibility)
+    pub transactional: u8,
+
+    /// Whether to report which scope variables the script changed. When set,
+    /// the result JSON is `{"value": <result>, "changes": <change set>}`
+    /// (bool as u8 for C compatibility)
+    pub track_changes: u8,
+}
+
 /// Represents a Rhai value for passing across the FFI boundary.
 ///
 /// Uses JSON serialization for complex types to avoid FFI alignment issues.
@@ -167,13 +372,30 @@ mod tests {
         assert_eq!(config.disable_file_io, 1);
     }
 
+    #[test]
+    fn test_eval_options_defaults() {
+        let options = CRhaiEvalOptions::default();
+        assert_eq!(options.merge_scope, 0);
+        assert_eq!(options.merge_conflict, 0);
+        assert_eq!(options.transactional, 0);
+        assert_eq!(options.track_changes, 0);
+    }
+
     #[test]
     fn test_engine_wrapper() {
-        let engine = Engine::new();
-        let wrapper = CRhaiEngine::new(engine, 30);
+        let wrapper = CRhaiEngine::new(EngineConfig::secure_defaults());
         assert!(!Arc::as_ptr(&wrapper.inner).is_null());
         assert_eq!(wrapper.async_timeout_seconds(), 30);
         // Verify scope is initialized and accessible
         assert!(wrapper.scope().is_empty());
     }
+
+    #[test]
+    fn test_scope_handle_wrapper() {
+        let handle = CRhaiScope::new();
+        assert!(handle.scope().is_empty());
+
+        handle.scope().push("x", 42_i64);
+        assert_eq!(handle.scope().get_value::<i64>("x"), Some(42));
+    }
 }
@@ -38,8 +38,9 @@ pub fn rhai_dynamic_to_json(dynamic: &Dynamic) -> Result<String, String> {
 
 /// Converts a Rhai Dynamic to a serde_json::Value recursively.
 ///
-/// This is an internal helper function used by `rhai_dynamic_to_json`.
-fn dynamic_to_json_value(dynamic: &Dynamic) -> Result<JsonValue, String> {
+/// This is the helper behind `rhai_dynamic_to_json`, also used where a value
+/// is embedded in a larger JSON document.
+pub(crate) fn dynamic_to_json_value(dynamic: &Dynamic) -> Result<JsonValue, String> {
     // Handle unit type (void/null)
     if dynamic.is_unit() {
         return Ok(JsonValue::Null);
@@ -105,6 +106,18 @@ fn dynamic_to_json_value(dynamic: &Dynamic) -> Result<JsonValue, String> {
     Err(format!("Unsupported Rhai type: {}", dynamic.type_name()))
 }
 
+/// Checks whether two Rhai Dynamic values hold the same value.
+///
+/// Rhai's `Dynamic` does not implement `PartialEq`, so values are compared by
+/// their JSON representation. Values that cannot be represented as JSON are
+/// compared by type name and display string instead.
+pub fn dynamic_values_equal(a: &Dynamic, b: &Dynamic) -> bool {
+    match (dynamic_to_json_value(a), dynamic_to_json_value(b)) {
+        (Ok(a_json), Ok(b_json)) => a_json == b_json,
+        _ => a.type_name() == b.type_name() && a.to_string() == b.to_string(),
+    }
+}
+
 /// Converts a JSON string to a Rhai Dynamic value.
 ///
 /// This function parses a JSON string and converts it to the appropriate Rhai type:
@@ -138,8 +151,9 @@ pub fn json_to_rhai_dynamic(json: &str) -> Result<Dynamic, String> {
 
 /// Converts a serde_json::Value to a Rhai Dynamic recursively.
 ///
-/// This is an internal helper function used by `json_to_rhai_dynamic`.
-fn json_value_to_dynamic(value: &JsonValue) -> Result<Dynamic, String> {
+/// This is the helper behind `json_to_rhai_dynamic`, also used where a value
+/// is read out of a larger JSON document.
+pub(crate) fn json_value_to_dynamic(value: &JsonValue) -> Result<Dynamic, String> {
     match value {
         JsonValue::Null => Ok(Dynamic::UNIT),
 
@@ -426,6 +440,17 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_dynamic_values_equal() {
+        assert!(dynamic_values_equal(&Dynamic::from(1_i64), &Dynamic::from(1_i64)));
+        assert!(!dynamic_values_equal(&Dynamic::from(1_i64), &Dynamic::from(2_i64)));
+        assert!(!dynamic_values_equal(&Dynamic::from(1_i64), &Dynamic::from("1".to_string())));
+
+        let a = json_to_rhai_dynamic(r#"{"x": [1, 2], "y": "z"}"#).unwrap();
+        let b = json_to_rhai_dynamic(r#"{"y": "z", "x": [1, 2]}"#).unwrap();
+        assert!(dynamic_values_equal(&a, &b));
+    }
+
     #[test]
     fn test_invalid_json() {
         let result = json_to_rhai_dynamic("invalid json {");
@@ -0,0 +1,247 @@
+//! Change notifications for watched scope variables
+//!
+//! Dart can subscribe to a set of variable names on a scope. After each
+//! successful eval against that scope, the watched variables the script added,
+//! removed, or modified are reported in a single batched notification:
+//!
+//! - Sync evals invoke the watch callback directly, once the scope lock is released.
+//! - Async evals post a request named `__rhai_watch__` to the function request
+//!   queue. Only the pollers filtered by engine or eval return them, not the
+//!   legacy `rhai_get_pending_function_request`. Notification requests do not
+//!   expect a result, so Dart must not call `rhai_provide_function_result` for them.
+//!
+//! The notification payload is `{"callback_id": <id>, "changes": <change set>}`,
+//! where the change set has the same shape as the one reported by `track_changes`.
+
+use crate::error::clear_last_error;
+use crate::functions::DartCallback;
+use crate::scope::{diff_scopes, ScopeChanges};
+use crate::types::{CRhaiEngine, CRhaiScope};
+use crate::catch_panic;
+use rhai::Scope;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::ffi::{CStr, CString, c_char};
+
+/// Function name of the notification requests posted by async evals.
+pub const WATCH_NOTIFICATION_FUNCTION: &str = "__rhai_watch__";
+
+/// The watched variable names of a scope and the callback to notify.
+#[derive(Clone)]
+pub(crate) struct ScopeWatch {
+    /// Names of the watched variables
+    names: HashSet<String>,
+
+    /// The ID passed back to the watch callback
+    callback_id: i64,
+
+    /// The function pointer to call back into Dart for sync evals
+    callback_ptr: DartCallback,
+}
+
+impl ScopeWatch {
+    /// Gets the ID passed back to the watch callback
+    pub(crate) fn callback_id(&self) -> i64 {
+        self.callback_id
+    }
+
+    /// Lists the changes to watched variables between two states of a scope.
+    ///
+    /// # Returns
+    ///
+    /// The filtered change set, or None if no watched variable changed
+    pub(crate) fn changes(&self, before: &Scope<'static>, after: &Scope<'static>) -> Option<ScopeChanges> {
+        let mut changes = diff_scopes(before, after);
+        changes.added.retain(|name, _| self.names.contains(name));
+        changes.removed.retain(|name| self.names.contains(name));
+        changes.modified.retain(|name, _| self.names.contains(name));
+
+        if changes.is_empty() {
+            None
+        } else {
+            Some(changes)
+        }
+    }
+
+    /// Builds the notification payload for a change set.
+    pub(crate) fn payload(&self, changes: &ScopeChanges) -> Result<String, String> {
+        serde_json::to_string(&serde_json::json!({
+            "callback_id": self.callback_id,
+            "changes": changes,
+        }))
+        .map_err(|e| format!("Failed to serialize watch notification: {}", e))
+    }
+
+    /// Invokes the watch callback with a change set.
+    ///
+    /// Errors are ignored: a failed notification must not fail the eval.
+    fn notify(&self, changes: &ScopeChanges) {
+        let payload = match self.payload(changes).and_then(|p| CString::new(p).map_err(|e| e.to_string())) {
+            Ok(p) => p,
+            Err(_) => return,
+        };
+
+        let result_ptr = (self.callback_ptr)(self.callback_id, payload.as_ptr());
+
+        // Free the (ignored) result string
+        if !result_ptr.is_null() {
+            unsafe {
+                libc::free(result_ptr as *mut libc::c_void);
+            }
+        }
+    }
+}
+
+thread_local! {
+    /// Notifications collected by sync evals on this thread, waiting for the
+    /// scope lock to be released before the callback is invoked.
+    static PENDING_NOTIFICATIONS: RefCell<Vec<(ScopeWatch, ScopeChanges)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Queues a notification for the watched variables a sync eval changed.
+///
+/// Does nothing if no watched variable changed.
+pub(crate) fn queue_watch_notification(watch: &ScopeWatch, before: &Scope<'static>, after: &Scope<'static>) {
+    if let Some(changes) = watch.changes(before, after) {
+        PENDING_NOTIFICATIONS.with(|pending| pending.borrow_mut().push((watch.clone(), changes)));
+    }
+}
+
+/// Invokes the watch callbacks for all notifications queued on this thread.
+///
+/// Must be called after the scope lock is released, so the callback can read
+/// the scope or start another eval.
+pub(crate) fn deliver_watch_notifications() {
+    let pending = PENDING_NOTIFICATIONS.with(|pending| std::mem::take(&mut *pending.borrow_mut()));
+    for (watch, changes) in pending {
+        watch.notify(&changes);
+    }
+}
+
+/// Subscribes to changes of scope variables.
+///
+/// Replaces the set of watched variable names of the scope. After each
+/// successful eval against the scope, the watched variables the script changed
+/// are reported in one batched notification, through `callback_ptr` for sync
+/// evals and through the function request queue (as `__rhai_watch__`) for async
+/// evals. An empty name list removes the subscription.
+///
+/// # Safety
+///
+/// This function is safe to call from FFI. The engine and names_json pointers
+/// must be valid, the scope pointer must be valid or null, and `callback_ptr`
+/// must stay valid while the subscription exists.
+///
+/// # Returns
+///
+/// 0 on success, -1 on error
+///
+/// # Arguments
+///
+/// * `engine` - Pointer to the Rhai engine
+/// * `scope` - Pointer to a scope handle, or null to use the engine's scope
+/// * `names_json` - JSON array of the variable names to watch
+/// * `callback_id` - ID passed back to the callback with each notification
+/// * `callback_ptr` - Function pointer to the Dart callback
+#[no_mangle]
+pub extern "C" fn rhai_scope_watch(
+    engine: *const CRhaiEngine,
+    scope: *const CRhaiScope,
+    names_json: *const c_char,
+    callback_id: i64,
+    callback_ptr: DartCallback,
+) -> i32 {
+    catch_panic! {{
+        clear_last_error();
+
+        // Validate pointers
+        if engine.is_null() {
+            set_last_error("Engine pointer is null");
+            return -1;
+        }
+
+        if names_json.is_null() {
+            set_last_error("Names JSON pointer is null");
+            return -1;
+        }
+
+        let names_str = unsafe {
+            match CStr::from_ptr(names_json).to_str() {
+                Ok(s) => s,
+                Err(e) => {
+                    set_last_error(&format!("Invalid UTF-8 in names JSON: {}", e));
+                    return -1;
+                }
+            }
+        };
+
+        let names: HashSet<String> = match serde_json::from_str(names_str) {
+            Ok(names) => names,
+            Err(e) => {
+                set_last_error(&format!("Names JSON must be an array of strings: {}", e));
+                return -1;
+            }
+        };
+
+        let target = unsafe { &*engine }.scope_target(scope);
+        target.lock().unwrap().watch = if names.is_empty() {
+            None
+        } else {
+            Some(ScopeWatch { names, callback_id, callback_ptr })
+        };
+
+        0 // Success
+    }}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{rhai_engine_free, rhai_engine_new, rhai_eval, rhai_set_var};
+    use std::sync::Mutex;
+
+    lazy_static::lazy_static! {
+        static ref NOTIFICATIONS: Mutex<Vec<(i64, String)>> = Mutex::new(Vec::new());
+    }
+
+    extern "C" fn record_notification(callback_id: i64, payload: *const c_char) -> *mut c_char {
+        let payload = unsafe { CStr::from_ptr(payload).to_str().unwrap().to_string() };
+        NOTIFICATIONS.lock().unwrap().push((callback_id, payload));
+        std::ptr::null_mut()
+    }
+
+    fn eval(engine: *const CRhaiEngine, script: &str) {
+        let script = CString::new(script).unwrap();
+        let mut result_ptr: *mut c_char = std::ptr::null_mut();
+        assert_eq!(rhai_eval(engine, script.as_ptr(), &mut result_ptr), 0);
+        unsafe { drop(CString::from_raw(result_ptr)) };
+    }
+
+    #[test]
+    fn test_sync_eval_notifies_watched_changes_once() {
+        let engine = rhai_engine_new(std::ptr::null());
+        let name = CString::new("total").unwrap();
+        let value = CString::new("1").unwrap();
+        assert_eq!(rhai_set_var(engine, name.as_ptr(), value.as_ptr(), std::ptr::null()), 0);
+
+        let names = CString::new(r#"["total", "status"]"#).unwrap();
+        assert_eq!(rhai_scope_watch(engine, std::ptr::null(), names.as_ptr(), 4242, record_notification), 0);
+
+        eval(engine, "total += 1; total += 1; let status = \"ok\"; let hidden = 1;");
+        eval(engine, "let unrelated = 5;");
+
+        let recorded: Vec<(i64, String)> = NOTIFICATIONS.lock().unwrap()
+            .iter()
+            .filter(|(id, _)| *id == 4242)
+            .cloned()
+            .collect();
+        assert_eq!(recorded.len(), 1);
+
+        let payload: serde_json::Value = serde_json::from_str(&recorded[0].1).unwrap();
+        assert_eq!(payload["callback_id"], 4242);
+        assert_eq!(payload["changes"]["modified"], serde_json::json!({ "total": 3 }));
+        assert_eq!(payload["changes"]["added"], serde_json::json!({ "status": "ok" }));
+
+        rhai_engine_free(engine);
+    }
+}
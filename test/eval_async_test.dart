@@ -198,5 +198,27 @@ void main() {
 
       expect(result, equals(26));
     });
+
+    test('concurrent evalAsync on two engines calls each engine\'s function', () async {
+      final other = RhaiEngine.withDefaults();
+      addTearDown(other.dispose);
+
+      // Same name on both engines, different callbacks
+      engine.registerFunction('lookup', () async {
+        await Future.delayed(const Duration(milliseconds: 20));
+        return 'first';
+      });
+      other.registerFunction('lookup', () async {
+        await Future.delayed(const Duration(milliseconds: 20));
+        return 'second';
+      });
+
+      final results = await Future.wait([
+        engine.evalAsync('lookup() + lookup()'),
+        other.evalAsync('lookup() + lookup()'),
+      ]);
+
+      expect(results, equals(['firstfirst', 'secondsecond']));
+    });
   });
 }